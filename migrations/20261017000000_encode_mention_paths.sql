@@ -0,0 +1,4 @@
+-- Mention targets are stored percent-encoded by `url::encode_path`, which SQL can't match, so
+-- the rows are re-encoded in Rust by `db::run_data_migrations` for each name queued here.
+CREATE TABLE data_migrations (name TEXT PRIMARY KEY NOT NULL);
+INSERT INTO data_migrations VALUES ('encode_mention_paths');
@@ -0,0 +1,4 @@
+-- Authors of received mentions, read from the source's h-card so mentions can be shown as comments
+ALTER TABLE received_mentions ADD COLUMN author_name TEXT;
+ALTER TABLE received_mentions ADD COLUMN author_photo TEXT;
+ALTER TABLE received_mentions ADD COLUMN author_url TEXT;
@@ -0,0 +1,13 @@
+-- Webmentions to send, kept after they're sent so they're only sent again when the source changes
+CREATE TABLE outgoing_mentions (
+    source TEXT NOT NULL,
+    target TEXT NOT NULL,
+    -- The source's content hash when the mention was queued
+    revision INTEGER NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    -- Unix time of the next try, or NULL once it's been sent or given up on
+    next_attempt INTEGER,
+    last_error TEXT,
+    PRIMARY KEY (source, target)
+);
+CREATE INDEX outgoing_mentions_due ON outgoing_mentions (next_attempt);
@@ -0,0 +1,6 @@
+-- When each mention was last received, in Unix time, so mentions can be listed in order.
+-- Mentions from before this are dated to the migration.
+-- (from_url, to_path) is already the primary key, which received mentions are upserted on.
+ALTER TABLE received_mentions ADD COLUMN received_at INTEGER;
+UPDATE received_mentions SET received_at = CAST(strftime('%s', 'now') AS INTEGER);
+CREATE INDEX received_mentions_by_target ON received_mentions (to_path, received_at);
@@ -0,0 +1,2 @@
+-- What mentioning posts say, from their h-entry's content, sanitized and cut short
+ALTER TABLE received_mentions ADD COLUMN excerpt TEXT;
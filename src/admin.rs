@@ -0,0 +1,65 @@
+use base64::{prelude::BASE64_STANDARD, Engine};
+use rocket::{
+    http::{Header, Status},
+    request::{FromRequest, Outcome},
+    Request,
+};
+
+use crate::config::CONFIG;
+
+/// A request guard for routes only the site's author may use.
+///
+/// The configured admin token is accepted as a bearer token, or as the password of HTTP basic
+/// auth so browsers can prompt for it. Admin routes 404 if no token is configured.
+pub struct Admin;
+
+#[async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = &'static str;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(token) = &CONFIG.admin_token else {
+            return Outcome::Error((Status::NotFound, "Admin routes are disabled"));
+        };
+        let Some(header) = request.headers().get_one("Authorization") else {
+            return Outcome::Error((Status::Unauthorized, "No credentials"));
+        };
+        let given = if let Some(bearer) = header.strip_prefix("Bearer ") {
+            bearer.trim().to_string()
+        } else if let Some(basic) = header.strip_prefix("Basic ") {
+            let Some(credentials) = BASE64_STANDARD
+                .decode(basic.trim())
+                .ok()
+                .and_then(|c| String::from_utf8(c).ok())
+            else {
+                return Outcome::Error((Status::BadRequest, "Malformed basic auth"));
+            };
+            let (_, password) = credentials.split_once(':').unwrap_or_default();
+            password.to_string()
+        } else {
+            return Outcome::Error((Status::Unauthorized, "Unsupported credentials"));
+        };
+        if constant_time_eq(given.as_bytes(), token.as_bytes()) {
+            Outcome::Success(Admin)
+        } else {
+            Outcome::Error((Status::Unauthorized, "Wrong token"))
+        }
+    }
+}
+
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Asks browsers to prompt for credentials when an admin route rejects a request.
+#[derive(Responder)]
+#[response(status = 401)]
+pub struct Challenge(&'static str, Header<'static>);
+
+#[catch(401)]
+pub fn unauthorized() -> Challenge {
+    Challenge(
+        "Unauthorized",
+        Header::new("WWW-Authenticate", "Basic realm=\"wolog\""),
+    )
+}
@@ -1,33 +1,56 @@
 use async_recursion::async_recursion;
-use chrono::{DateTime, Local, NaiveDate};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
 use dashmap::{DashMap, DashSet};
 use error::ArticleError;
 use pandoc_ast::{Block, Inline, MetaValue, Pandoc};
 use rocket::{
     form::{FromFormField, ValueField},
-    http::uri::Segments,
+    http::{uri::Segments, ContentType},
     request::FromSegments,
-    tokio::{self, sync::Mutex},
+    response::content::RawHtml,
+    tokio::{
+        self,
+        io::AsyncWriteExt,
+        process::Command,
+        sync::{Mutex, Semaphore},
+    },
 };
-use rocket_dyn_templates::{context, Template};
+use rocket_dyn_templates::context;
 use serde::{Deserialize, Serialize};
 use serde_yml::Value;
+use sha2::{Digest, Sha256};
 use std::{
+    collections::HashSet,
     ffi::OsStr,
     fmt::Display,
-    io::Write,
     ops::{Bound, Deref, RangeBounds},
-    path::{Path, PathBuf},
-    process::{Command, Stdio},
+    path::{Component, Path, PathBuf},
+    process::Stdio,
     str::FromStr,
     sync::{Arc, LazyLock},
     time::{Duration, Instant, SystemTime},
 };
 use strum::EnumString;
 
-use crate::{db, filters::apply_filters};
+use crate::{
+    config::CONFIG,
+    db,
+    filters::{apply_filters, plain_text, presentation_filters, render_page, template_names},
+    related::{Related, RelatedBy},
+    roots, shutdown, timezone,
+    url::FeedLink,
+};
 
+mod disk_cache;
 pub mod error;
+mod front_matter;
+mod git;
+mod native;
+mod write;
+
+pub use disk_cache::load as load_cache;
+pub use front_matter::rename_tag;
+pub use write::{read_article, write_article, write_source};
 
 static LAST_REAL_SEARCH: LazyLock<tokio::sync::Mutex<Instant>> =
     LazyLock::new(|| Mutex::new(Instant::now() - Duration::from_secs(3600)));
@@ -37,8 +60,8 @@ async fn find_articles(
     path: Arc<Path>,
 ) -> Result<Vec<(Arc<Path>, Arc<ArticleMeta>)>, ArticleError> {
     if path.is_file() && path.extension() == Some(OsStr::new("md")) {
-        if let Ok((meta, _)) = get_metadata(&path).await {
-            return Ok(vec![(path.clone(), meta)]);
+        if let Ok(entry) = get_metadata(&path).await {
+            return Ok(vec![(path.clone(), entry.meta)]);
         }
     }
     if !path.is_dir() {
@@ -56,82 +79,470 @@ async fn find_articles(
 }
 
 pub async fn search(search: &Search) -> Result<Vec<(Arc<Path>, Arc<ArticleMeta>)>, ArticleError> {
+    search_page(search).await.map(|(articles, _)| articles)
+}
+
+/// Run a search, also reporting whether results were cut off by its limit or
+/// [`max_search_results`](crate::config::Config::max_search_results), meaning there's another
+/// page.
+pub async fn search_page(
+    search: &Search,
+) -> Result<(Vec<(Arc<Path>, Arc<ArticleMeta>)>, bool), ArticleError> {
+    search_page_within(search, CONFIG.max_search_results).await
+}
+
+/// Like [`search_page`], returning at most `max` results.
+async fn search_page_within(
+    search: &Search,
+    max: usize,
+) -> Result<(Vec<(Arc<Path>, Arc<ArticleMeta>)>, bool), ArticleError> {
     let mut search_time = LAST_REAL_SEARCH.lock().await;
     let mut articles = if search_time.elapsed() > Duration::from_secs(1800) {
         println!("Do full search");
         *search_time = Instant::now();
         std::mem::drop(search_time);
-        find_articles(Path::new("articles").into()).await?
+        let mut articles = vec![];
+        for root in &CONFIG.roots {
+            articles.extend(find_articles(root.dir.as_path().into()).await?);
+        }
+        articles
     } else {
         std::mem::drop(search_time);
         AST_CACHE
             .iter()
-            .map(|kv| (kv.key().clone(), kv.value().0.clone()))
+            .map(|kv| (kv.key().clone(), kv.value().meta.clone()))
             .collect()
     };
-    articles.retain(|(_, article)| {
-        search.created.contains(&article.created)
-            && search.updated.contains(&article.updated)
-            && !article.hidden
-            && search.tags.iter().all(|t| article.tags.contains(t))
-            && article
-                .title
-                .contains(search.title_filter.as_deref().unwrap_or(""))
+    articles.retain(|(path, article)| {
+        search.matches(article) && roots::site_rel(path).starts_with(&search.search_path)
     });
     let sort = search.sort_type.sort_fn();
-    articles.sort_by(|a, b| (sort)(&(&*a.0, &*a.1), &(&*b.0, &*b.1)));
-    articles = articles
+    articles.sort_by(|a, b| {
+        let pins = if search.ignore_pins {
+            std::cmp::Ordering::Equal
+        } else {
+            b.1.pinned.cmp(&a.1.pinned)
+        };
+        pins.then_with(|| (sort)(&(&*a.0, &*a.1), &(&*b.0, &*b.1)))
+    });
+    let (articles, capped) = page_of(articles, search, max);
+    let articles = articles
         .into_iter()
-        .map(|(p, a)| (p.strip_prefix("articles").unwrap_or(&p).into(), a))
+        .map(|(p, a)| (roots::site_rel(&p).into(), a))
         .collect();
-    Ok(articles)
+    Ok((articles, capped))
 }
 
-pub async fn get_article(path: &Arc<Path>) -> Result<Arc<Article>, ArticleError> {
-    let (meta, ast) = get_metadata(path).await?;
+/// The page of sorted `articles` that `search` asks for, holding no more than `max`, and whether
+/// any follow it.
+fn page_of<T>(articles: Vec<T>, search: &Search, max: usize) -> (Vec<T>, bool) {
+    let limit = search.limit.unwrap_or(usize::MAX).min(max);
+    let capped = articles.len() > search.offset.saturating_add(limit);
+    let page = articles
+        .into_iter()
+        .skip(search.offset)
+        .take(limit)
+        .collect();
+    (page, capped)
+}
 
-    let mut meta = (*meta).clone();
-    meta.mentioners.append({
-        let path = path.with_extension("");
-        let path = path.strip_prefix("articles").unwrap();
-        let path = path.to_string_lossy();
-        &mut db::mentions_of(&path).await
-    });
+/// Run a search for every matching article, however many pages of results they take up.
+pub async fn search_all(
+    search: &Search,
+) -> Result<Vec<(Arc<Path>, Arc<ArticleMeta>)>, ArticleError> {
+    search_all_within(search, CONFIG.max_search_results).await
+}
 
-    let ast = ast.to_json();
+/// Like [`search_all`], taking at most `max` results a page.
+async fn search_all_within(
+    search: &Search,
+    max: usize,
+) -> Result<Vec<(Arc<Path>, Arc<ArticleMeta>)>, ArticleError> {
+    let mut search = search.clone();
+    let mut articles = vec![];
+    loop {
+        let (page, capped) = search_page_within(&search, max).await?;
+        // With a cap of nothing, every page is empty and still cut off, so stop at an empty one.
+        let done = !capped || page.is_empty();
+        search.offset += page.len();
+        articles.extend(page);
+        if done {
+            return Ok(articles);
+        }
+    }
+}
 
-    let content = rocket::tokio::task::spawn_blocking({
-        move || -> Result<_, error::ArticleError> {
-            let mut pandoc = Command::new("pandoc")
-                .args(["-f", "json", "-t", "html", "--mathml"])
-                .stdin(Stdio::piped())
-                .stdout(Stdio::piped())
-                .spawn()?;
+/// The tags of the article at `path`, as of its last render.
+pub fn tags_of(path: &Path) -> Option<Vec<String>> {
+    AST_CACHE.get(path).map(|entry| entry.meta.tags.clone())
+}
 
-            pandoc.stdin.as_mut().unwrap().write_all(ast.as_bytes())?;
-            let pandoc = pandoc.wait_with_output()?;
+/// Find articles related to the one at `path`, most related first.
+pub async fn related(
+    path: &Path,
+    related: &Related,
+) -> Result<Vec<(Arc<Path>, Arc<ArticleMeta>)>, ArticleError> {
+    let me = roots::site_rel(path);
+    let mut candidates = search(&Search::default()).await?;
+    candidates.retain(|(p, _)| **p != *me);
+    let mut articles = match related.by {
+        RelatedBy::Tags => {
+            let tags = tags_of(path).unwrap_or_default();
+            let shared = |meta: &ArticleMeta| meta.tags.iter().filter(|t| tags.contains(t)).count();
+            candidates.retain(|(_, meta)| shared(meta) > 0);
+            candidates.sort_by_key(|(_, meta)| std::cmp::Reverse(shared(meta)));
+            candidates
+        }
+        RelatedBy::Content => crate::related::related_by_content(path, usize::MAX)
+            .into_iter()
+            .filter_map(|(p, _)| {
+                let p = roots::site_rel(&p);
+                candidates.iter().find(|(c, _)| **c == *p).cloned()
+            })
+            .collect(),
+    };
+    articles.truncate(related.limit);
+    Ok(articles)
+}
 
-            if !pandoc.status.success() {
-                return Err(error::ArticleError::PandocFailed(String::from_utf8(
-                    pandoc.stdout,
-                )?));
-            }
+pub async fn get_article(path: &Arc<Path>) -> Result<Arc<Article>, ArticleError> {
+    let CacheEntry {
+        meta,
+        body,
+        plain_text,
+        served,
+        ..
+    } = get_public(path).await?;
 
-            Ok(String::from_utf8(pandoc.stdout)?)
-        }
-    })
-    .await??;
+    let content = served
+        .html
+        .get_or_try_init(|| to_html(&meta, body))
+        .await?
+        .to_string();
+
+    let mut meta = (*meta).clone();
+    let password_hash = meta.password_hash.take();
+    let mut mentions: Vec<_> = meta
+        .mentioners
+        .iter()
+        .map(|source| db::MentionDetail {
+            source: source.clone(),
+            ..Default::default()
+        })
+        .collect();
+    let received = db::mentions_detail(&crate::url::article_rel(path)).await;
+    meta.mentioners
+        .extend(received.iter().map(|m| m.source.clone()));
+    mentions.extend(received);
+
+    let og_image = match &meta.image {
+        Some(image) => reqwest::Url::parse(&crate::url::article_url(path))
+            .and_then(|base| base.join(image))
+            .ok()
+            .map(String::from),
+        None => crate::og_image::generated(path, &meta.title).await,
+    };
 
     let article = Arc::new(Article {
         content,
         meta,
+        plain_text: plain_text.to_string(),
         rendered_at: SystemTime::now(),
+        mentions,
+        password_hash,
+        og_image,
+        compressed: served.compressed.clone(),
     });
 
     Ok(article)
 }
 
-async fn get_metadata(path: &Arc<Path>) -> Result<(Arc<ArticleMeta>, Arc<Pandoc>), ArticleError> {
+/// An article's final HTML content: its body converted to HTML, then sanitized and minified as
+/// configured.
+async fn to_html(meta: &ArticleMeta, body: Body) -> Result<Arc<str>, ArticleError> {
+    let content = match body {
+        Body::Pandoc(ast) => {
+            let lang = format!("lang={}", meta.lang());
+            let args = ["-f", "json", "-t", "html", "--metadata", &lang];
+            let content = run_pandoc(
+                args.iter().chain(math_args(meta.renders_math())),
+                ast.to_json().into_bytes(),
+            )
+            .await?;
+            String::from_utf8(content)?
+        }
+        Body::Html(html) => html.to_string(),
+    };
+    let content = if meta.sanitize.unwrap_or(CONFIG.sanitize_html) {
+        crate::sanitize::sanitize(&content)
+    } else {
+        content
+    };
+    Ok(crate::minify::content(content).into())
+}
+
+/// Render markdown that isn't an article, such as an editor's unsaved draft, to an HTML fragment.
+///
+/// Nothing is cached or written to disk. `search` and `related` blocks are left as code, since
+/// there's no article for their results to belong to.
+pub async fn preview(markdown: &str) -> Result<String, ArticleError> {
+    let html = match CONFIG.renderer {
+        Renderer::Pandoc => {
+            let input = front_matter::for_pandoc(markdown)?.unwrap_or_else(|| markdown.to_string());
+            let ast = run_pandoc(
+                ["-f", "markdown", "-t", "json"]
+                    .map(OsStr::new)
+                    .into_iter()
+                    .chain(filter_args()),
+                input.into_bytes(),
+            )
+            .await?;
+            let ast = presentation_filters(Pandoc::from_json(&String::from_utf8(ast)?));
+            let html = run_pandoc(
+                ["-f", "json", "-t", "html"]
+                    .iter()
+                    .chain(math_args(math_by_default())),
+                ast.to_json().into_bytes(),
+            )
+            .await?;
+            String::from_utf8(html)?
+        }
+        Renderer::Native => native::preview(markdown, math_by_default()).await,
+    };
+    Ok(if CONFIG.sanitize_html {
+        crate::sanitize::sanitize(&html)
+    } else {
+        html
+    })
+}
+
+/// Whether math is rendered to MathML for articles that don't say.
+fn math_by_default() -> bool {
+    CONFIG.math.unwrap_or(CONFIG.renderer == Renderer::Pandoc)
+}
+
+/// The pandoc arguments that render math as `math` asks: MathML, or else TeX left in spans for
+/// a script on the page to typeset.
+fn math_args(math: bool) -> &'static [&'static str] {
+    if math {
+        &["--mathml"]
+    } else {
+        &[]
+    }
+}
+
+/// Arguments passing the configured Lua and JSON filters to pandoc.
+fn filter_args() -> impl Iterator<Item = &'static OsStr> {
+    filter_args_for(&CONFIG.lua_filters, &CONFIG.pandoc_filters)
+}
+
+/// Arguments passing `lua` filters and then `json` filters to pandoc, which runs them in order.
+fn filter_args_for<'a>(lua: &'a [PathBuf], json: &'a [PathBuf]) -> impl Iterator<Item = &'a OsStr> {
+    let lua = lua
+        .iter()
+        .flat_map(|filter| [OsStr::new("--lua-filter"), filter.as_os_str()]);
+    let json = json
+        .iter()
+        .flat_map(|filter| [OsStr::new("--filter"), filter.as_os_str()]);
+    lua.chain(json)
+}
+
+/// Whether any configured pandoc filter has been edited since `time`, which makes articles
+/// rendered before then out of date.
+fn filters_changed_since(time: SystemTime) -> bool {
+    CONFIG.renderer == Renderer::Pandoc
+        && modified_since(
+            CONFIG.lua_filters.iter().chain(&CONFIG.pandoc_filters),
+            time,
+        )
+}
+
+/// Whether any of `files` has been modified since `time`. Missing files don't count.
+fn modified_since<'a>(files: impl IntoIterator<Item = &'a PathBuf>, time: SystemTime) -> bool {
+    files.into_iter().any(|file| {
+        std::fs::metadata(file)
+            .and_then(|m| m.modified())
+            .is_ok_and(|modified| modified > time)
+    })
+}
+
+/// Panic if a configured pandoc filter is missing, rather than failing every render later.
+pub fn check_filters() {
+    for filter in CONFIG.lua_filters.iter().chain(&CONFIG.pandoc_filters) {
+        assert!(filter.is_file(), "Pandoc filter {filter:?} doesn't exist");
+    }
+}
+
+/// Bounds how many pandoc processes run at once.
+static PANDOC_PERMITS: LazyLock<Semaphore> = LazyLock::new(|| Semaphore::new(CONFIG.pandoc_jobs));
+
+/// Run pandoc with the given arguments, feeding it `input` and returning its output.
+async fn run_pandoc<I, S>(args: I, input: Vec<u8>) -> Result<Vec<u8>, ArticleError>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let _permit = PANDOC_PERMITS.acquire().await.unwrap();
+    let mut command = Command::new("pandoc");
+    command.args(args);
+    run_process(command, input, Duration::from_secs(CONFIG.render_timeout)).await
+}
+
+/// Run a pandoc-like `command`, feeding it `input` and returning its output.
+///
+/// It's killed if it runs longer than `timeout`, or if the future is dropped first, such as when
+/// the request waiting on it goes away.
+async fn run_process(
+    mut command: Command,
+    input: Vec<u8>,
+    timeout: Duration,
+) -> Result<Vec<u8>, ArticleError> {
+    let mut pandoc = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    let mut stdin = pandoc.stdin.take().unwrap();
+    let feed = async move {
+        stdin.write_all(&input).await?;
+        // Closing stdin tells pandoc its input is over.
+        drop(stdin);
+        Ok::<_, std::io::Error>(())
+    };
+    // Reading its output and waiting for it to exit are both under the timeout.
+    let run = async {
+        let (fed, output) = tokio::join!(feed, pandoc.wait_with_output());
+        fed?;
+        output
+    };
+
+    let Ok(output) = tokio::time::timeout(timeout, run).await else {
+        eprintln!("Pandoc took longer than {timeout:?}, killing it");
+        return Err(ArticleError::RenderTimeout);
+    };
+    let output = output?;
+
+    if !output.status.success() {
+        return Err(ArticleError::PandocFailed(
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        ));
+    }
+
+    Ok(output.stdout)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ExportFormat {
+    Pdf,
+    Epub,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Pdf => "pdf",
+            ExportFormat::Epub => "epub",
+        }
+    }
+
+    pub fn content_type(&self) -> ContentType {
+        match self {
+            ExportFormat::Pdf => ContentType::PDF,
+            ExportFormat::Epub => ContentType::new("application", "epub+zip"),
+        }
+    }
+
+    fn pandoc_args(&self, meta: &ArticleMeta, from: &str) -> Vec<String> {
+        let mut args = vec!["-f".to_string(), from.to_string()];
+        match self {
+            ExportFormat::Pdf => {
+                args.extend(["-t".to_string(), "pdf".to_string()]);
+                if let Some(engine) = &CONFIG.pdf_engine {
+                    args.push(format!("--pdf-engine={engine}"));
+                }
+            }
+            ExportFormat::Epub => {
+                args.extend([
+                    "-t".to_string(),
+                    "epub".to_string(),
+                    "--metadata".to_string(),
+                    format!(
+                        "author={}",
+                        meta.authors
+                            .iter()
+                            .map(|a| a.name.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                    "--metadata".to_string(),
+                    format!("date={}", meta.created),
+                ]);
+            }
+        }
+        args.extend([
+            "--metadata".to_string(),
+            format!("lang={}", meta.lang()),
+            "-o".to_string(),
+            "-".to_string(),
+        ]);
+        args
+    }
+}
+
+type ExportEntry = (SystemTime, Arc<Vec<u8>>);
+
+static EXPORT_CACHE: LazyLock<DashMap<(Arc<Path>, ExportFormat), ExportEntry>> =
+    LazyLock::new(DashMap::new);
+
+/// Convert an article's cached AST into another document format, caching the result until the
+/// source file changes.
+pub async fn export(
+    path: &Arc<Path>,
+    format: ExportFormat,
+) -> Result<(Arc<ArticleMeta>, Arc<Vec<u8>>), ArticleError> {
+    let CacheEntry { meta, body, .. } = get_public(path).await?;
+    let modified = tokio::fs::metadata(path).await?.modified()?;
+    let key = (path.clone(), format);
+    if let Some(cached) = EXPORT_CACHE.get(&key) {
+        if cached.0 == modified {
+            return Ok((meta, cached.1.clone()));
+        }
+    }
+
+    println!("Exporting {path:?} as {format:?}");
+    let (from, input) = match body {
+        Body::Pandoc(ast) => ("json", ast.to_json().into_bytes()),
+        Body::Html(html) => ("html", html.as_bytes().to_vec()),
+    };
+    let output = run_pandoc(format.pandoc_args(&meta, from), input)
+        .await
+        .map_err(|e| match (format, e) {
+            (ExportFormat::Pdf, ArticleError::PandocFailed(e)) if e.contains("pdf-engine") => {
+                ArticleError::PdfUnavailable
+            }
+            (_, e) => e,
+        })?;
+    let output = Arc::new(output);
+    EXPORT_CACHE.insert(key, (modified, output.clone()));
+    Ok((meta, output))
+}
+
+/// The hash of the password protecting the article at `path`, if it's protected.
+pub async fn password_hash(path: &Arc<Path>) -> Result<Option<String>, ArticleError> {
+    Ok(get_public(path).await?.meta.password_hash.clone())
+}
+
+/// Like [`get_metadata`], but refuses articles that aren't ready to be published.
+async fn get_public(path: &Arc<Path>) -> Result<CacheEntry, ArticleError> {
+    let entry = get_metadata(path).await?;
+    if !entry.meta.is_public() {
+        return Err(ArticleError::NotForPublication);
+    }
+    Ok(entry)
+}
+
+async fn get_metadata(path: &Arc<Path>) -> Result<CacheEntry, ArticleError> {
     let disk_modified_time = tokio::fs::metadata(&path)
         .await
         .and_then(|m| m.modified())
@@ -140,52 +551,134 @@ async fn get_metadata(path: &Arc<Path>) -> Result<(Arc<ArticleMeta>, Arc<Pandoc>
     match (disk_modified_time, cached) {
         (None, _) => Err(ArticleError::NoArticle),
         (Some(disk_modified_time), Some(cached))
-            if cached.2 >= disk_modified_time && !cached.0.always_rerender =>
+            if cached.rendered_at >= disk_modified_time
+                && !STALE.contains(path)
+                && cached.deps.unchanged_since(cached.rendered_at)
+                && !filters_changed_since(cached.rendered_at)
+                && (!cached.meta.always_rerender || CONFIG.rerender_interval.is_some()) =>
         {
-            Ok((cached.0.clone(), cached.1.clone()))
+            Ok(cached)
         }
         (Some(_), cached) => match prerender_article(path).await {
             Ok(v) => Ok(v),
-            Err(e) => cached.map(|c| (c.0.clone(), c.1.clone())).ok_or(e),
+            Err(e) => cached.ok_or(e),
         },
     }
 }
 
-async fn prerender_article(
-    path: &Arc<Path>,
-) -> Result<(Arc<ArticleMeta>, Arc<Pandoc>), ArticleError> {
+/// Forget every article rendered from under `dir`, for tests to clean up after themselves.
+#[cfg(test)]
+pub fn forget(dir: &Path) {
+    AST_CACHE.retain(|path, _| !path.starts_with(dir));
+    SLUGS.retain(|_, path| !path.starts_with(dir));
+}
+
+/// Re-render an article whose source was just changed, so searches see its new metadata without
+/// waiting for its next request.
+pub async fn refresh(path: &Arc<Path>) {
+    STALE.insert(path.clone());
+    if let Err(e) = get_metadata(path).await {
+        eprintln!("Couldn't re-render {path:?}: {e}");
+    }
+}
+
+tokio::task_local! {
+    /// The articles being rendered, outermost first, whose embedded searches led to rendering the
+    /// next.
+    static RENDERING: Vec<Arc<Path>>;
+}
+
+/// The articles being rendered further up this task, outermost first.
+pub fn rendering_chain() -> Vec<Arc<Path>> {
+    RENDERING.try_with(Clone::clone).unwrap_or_default()
+}
+
+/// Run `future` as part of rendering the articles in `chain`, for rendering work that has to
+/// leave the task doing it.
+pub async fn in_rendering_chain<F: std::future::Future>(
+    chain: Vec<Arc<Path>>,
+    future: F,
+) -> F::Output {
+    RENDERING.scope(chain, future).await
+}
+
+/// Render an article, unless it's being rendered for an embedded search nested more than
+/// [`max_embed_depth`](crate::config::Config::max_embed_depth) deep.
+///
+/// Cycles are already cut short by [`BUSY_ASTS`], but a long enough chain of articles embedding
+/// searches for the next would otherwise render all of them in one request.
+async fn prerender_article(path: &Arc<Path>) -> Result<CacheEntry, ArticleError> {
+    let mut chain = rendering_chain();
+    // An article already in the chain is a cycle, which it's left to `BUSY_ASTS` to cut.
+    if !chain.contains(path) && chain.len() >= CONFIG.max_embed_depth.max(1) {
+        let chain: Vec<_> = chain
+            .iter()
+            .chain([path])
+            .map(|path| path.to_string_lossy())
+            .collect();
+        eprintln!(
+            "Not rendering {path:?}, since embedded searches nest too deeply: {}",
+            chain.join(" -> ")
+        );
+        return Err(ArticleError::EmbedsTooDeep);
+    }
+    chain.push(path.clone());
+    RENDERING.scope(chain, render_article(path)).await
+}
+
+/// Render an article and cache the result.
+async fn render_article(path: &Arc<Path>) -> Result<CacheEntry, ArticleError> {
     if !BUSY_ASTS.insert(path.clone()) {
         println!("Skipping prerendering {path:?} since we're already working on it");
         return AST_CACHE
             .get(path)
-            .map(|a| (a.value().0.clone(), a.value().1.clone()))
+            .map(|a| a.value().clone())
             .ok_or(ArticleError::NoArticle);
     }
+    let _busy = Busy(path.clone());
+    STALE.remove(path);
+    RENDER_DEPS.remove(path);
     println!("Rendering {path:?}");
-    let ast = tokio::task::spawn_blocking({
-        let path = path.clone();
-        move || -> Result<_, error::ArticleError> {
-            let pandoc = Command::new("pandoc")
-                .args(["-f", "markdown", "-t", "json"])
-                .arg(path.as_os_str())
-                .stdin(Stdio::null())
-                .stdout(Stdio::piped())
-                .output()?;
-
-            if !pandoc.status.success() {
-                return Err(error::ArticleError::PandocFailed(String::from_utf8(
-                    pandoc.stdout,
-                )?));
-            }
-
-            let ast = String::from_utf8(pandoc.stdout)?;
-            let ast = Pandoc::from_json(&ast);
-            Ok(ast)
+    let source = tokio::fs::read_to_string(path).await?;
+    let (mut meta, body, plain_text) = match CONFIG.renderer {
+        Renderer::Pandoc => {
+            let mut args: Vec<&OsStr> = ["-f", "markdown", "-t", "json"].map(OsStr::new).into();
+            args.extend(filter_args().map(|arg| -> &OsStr { arg }));
+            // Pandoc reads the file itself, unless its front matter had to be rewritten.
+            let input = match front_matter::for_pandoc(&source)? {
+                Some(rewritten) => rewritten.into_bytes(),
+                None => {
+                    args.push(path.as_os_str());
+                    vec![]
+                }
+            };
+            let ast = run_pandoc(args, input).await?;
+            let ast = Pandoc::from_json(&String::from_utf8(ast)?);
+            let ast = Arc::new(apply_filters(path.clone(), ast).await);
+            let meta = ArticleMeta::try_from(&*ast)?;
+            let plain_text = plain_text(&ast);
+            (meta, Body::Pandoc(ast), plain_text)
         }
-    })
-    .await??;
-    let ast = Arc::new(apply_filters(path.clone(), ast).await);
-    let mut meta = ArticleMeta::try_from(&*ast)?;
+        Renderer::Native => {
+            let rendered = native::render(path, &source).await?;
+            (
+                rendered.meta,
+                Body::Html(rendered.html.into()),
+                rendered.plain_text,
+            )
+        }
+    };
+
+    meta.excerpt = excerpt(&source);
+    let content_hash = content_hash(&source);
+
+    if !template_names().contains(&meta.template) {
+        eprintln!(
+            "{path:?} asks for missing template {:?}; using the default instead",
+            meta.template
+        );
+        meta.template = DEFAULT_TEMPLATE();
+    }
 
     let fsmeta = tokio::fs::metadata(path).await.ok();
 
@@ -198,26 +691,321 @@ async fn prerender_article(
         .and_then(|m| m.created().ok())
         .unwrap_or(SystemTime::now());
 
+    // Keep the last `updated` date worked out for the article if its body hasn't really changed,
+    // so touching it or fixing its front matter doesn't move it up the list.
+    let backfilled = meta.updated == NaiveDate::default();
+    if backfilled && !CONFIG.updated_from_any_edit {
+        let previous = AST_CACHE.get(path).map(|entry| entry.clone());
+        if let Some(previous) = previous.filter(|previous| previous.content_hash == content_hash) {
+            meta.updated = previous.meta.updated;
+        }
+    }
+
+    let undated = meta.updated == NaiveDate::default() || meta.created == NaiveDate::default();
+    if undated && CONFIG.dates_from_git {
+        if let Some(dates) = git::dates(path).await {
+            if meta.updated == NaiveDate::default() {
+                meta.updated = dates.updated;
+            }
+            if meta.created == NaiveDate::default() {
+                meta.created = dates.created;
+            }
+        }
+    }
+
     if meta.updated == NaiveDate::default() {
-        meta.updated = DateTime::<Local>::from(disk_time).date_naive();
+        meta.updated = timezone::in_zone(disk_time).date_naive();
     }
     if meta.created == NaiveDate::default() {
-        meta.created = DateTime::<Local>::from(created_time).date_naive();
+        meta.created = timezone::in_zone(created_time).date_naive();
     }
 
-    if !meta.ready && std::env::var("WOLOG_PREVIEW_NONREADY").is_err() {
-        return Err(ArticleError::NotForPublication);
+    register_slug(path, meta.slug.as_deref());
+
+    meta.translations.retain(|translation| {
+        let exists = source_path(Path::new(&translation.path)).is_some();
+        if !exists {
+            eprintln!(
+                "{path:?} links to a missing {} translation at {:?}",
+                translation.lang, translation.path
+            );
+        }
+        exists
+    });
+
+    let entry = CacheEntry {
+        meta: Arc::new(meta),
+        plain_text: plain_text.into(),
+        body,
+        rendered_at: SystemTime::now(),
+        deps: Arc::new(
+            RENDER_DEPS
+                .remove(path)
+                .map(|(_, deps)| deps)
+                .unwrap_or_default(),
+        ),
+        content_hash,
+        served: Default::default(),
+    };
+
+    let previous = AST_CACHE.insert(path.clone(), entry.clone());
+    let meta_changed = previous.is_none_or(|previous| {
+        serde_json::to_value(&previous.meta).ok() != serde_json::to_value(&entry.meta).ok()
+    });
+    if meta_changed {
+        invalidate_dependents(path, &entry.meta);
+    }
+    if CONFIG.send_webmentions {
+        queue_webmentions(path, &entry.meta, content_hash);
+    }
+    disk_cache::store(path, &entry).await;
+    Ok(entry)
+}
+
+/// Queue webmentions to the pages a published article mentions, to go out when it's published.
+fn queue_webmentions(path: &Path, meta: &ArticleMeta, revision: u64) {
+    if meta.status() == ArticleStatus::Draft || meta.password_hash.is_some() {
+        return;
+    }
+    let due = meta
+        .publish_at
+        .map_or(0, |publish_at| timezone::local(publish_at).timestamp());
+    let source = crate::url::article_url(path);
+    let Ok(base) = reqwest::Url::parse(&source) else {
+        return;
+    };
+    for target in &meta.mentions {
+        let Some(target) = base
+            .join(target)
+            .ok()
+            .filter(|target| matches!(target.scheme(), "http" | "https"))
+        else {
+            continue;
+        };
+        shutdown::spawn(db::send_webmention(
+            source.clone(),
+            target.into(),
+            revision as i64,
+            due,
+        ));
+    }
+}
+
+/// Articles by slug, keyed on the URL path they're reachable at.
+static SLUGS: LazyLock<DashMap<PathBuf, Arc<Path>>> = LazyLock::new(DashMap::new);
+
+/// Make the article at `path` reachable by its slug instead of its old one, if any.
+///
+/// A slug that would shadow another article or slug is logged and ignored.
+fn register_slug(path: &Arc<Path>, slug: Option<&str>) {
+    SLUGS.retain(|_, p| p != path);
+    let Some(slug) = slug else {
+        return;
+    };
+    if slug.is_empty() || slug.contains('/') {
+        eprintln!("{path:?} has an invalid slug {slug:?}");
+        return;
+    }
+    let rel = crate::url::article_rel(path);
+    let key = Path::new(&rel).parent().unwrap_or(Path::new("")).join(slug);
+    let taken = SLUGS
+        .get(&key)
+        .map(|other| other.to_path_buf())
+        .or_else(|| source_path(&key));
+    match taken {
+        Some(other) if *other != **path => {
+            eprintln!("{path:?} wants the slug {key:?}, but {other:?} already has it");
+        }
+        _ => {
+            SLUGS.insert(key, path.clone());
+        }
     }
+}
 
-    let meta = Arc::new(meta);
+/// Marks a path in [`BUSY_ASTS`] as free again when rendering it ends, however it ends.
+struct Busy(Arc<Path>);
 
-    AST_CACHE.insert(path.clone(), (meta.clone(), ast.clone(), SystemTime::now()));
-    BUSY_ASTS.remove(path);
-    Ok((meta, ast))
+impl Drop for Busy {
+    fn drop(&mut self) {
+        BUSY_ASTS.remove(&self.0);
+    }
 }
 
-static AST_CACHE: LazyLock<DashMap<Arc<Path>, (Arc<ArticleMeta>, Arc<Pandoc>, SystemTime)>> =
-    LazyLock::new(DashMap::new);
+/// Periodically re-render every cached `always_rerender` article, so readers get warm pages.
+///
+/// While this runs, requests for those articles are served from the cache.
+pub async fn rerender_periodically(interval: Duration) {
+    let mut clock = tokio::time::interval(interval);
+    loop {
+        clock.tick().await;
+        let paths: Vec<_> = AST_CACHE
+            .iter()
+            .filter(|kv| kv.value().meta.always_rerender && !BUSY_ASTS.contains(kv.key()))
+            .map(|kv| kv.key().clone())
+            .collect();
+        for path in paths {
+            if let Err(e) = prerender_article(&path).await {
+                eprintln!("Background re-render of {path:?} failed: {e:?}");
+            }
+        }
+    }
+}
+
+/// Refresh pages listing articles as their `publish_at` times pass, so the articles show up in
+/// embedded searches without waiting for anything else to change.
+///
+/// Everything else checks `publish_at` against the clock when it's asked.
+pub async fn publish_scheduled(interval: Duration) {
+    let mut clock = tokio::time::interval(interval);
+    let mut last = timezone::now().naive_local();
+    loop {
+        clock.tick().await;
+        let now = timezone::now().naive_local();
+        let due: Vec<_> = AST_CACHE
+            .iter()
+            .filter(|kv| {
+                kv.value()
+                    .meta
+                    .publish_at
+                    .is_some_and(|publish_at| last < publish_at && publish_at <= now)
+            })
+            .map(|kv| (kv.key().clone(), kv.value().meta.clone()))
+            .collect();
+        for (path, meta) in due {
+            println!("Publishing {path:?}");
+            invalidate_dependents(&path, &meta);
+        }
+        last = now;
+    }
+}
+
+/// Which backend turns markdown into HTML.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Renderer {
+    /// Pandoc, with all its extensions and filters.
+    Pandoc,
+    /// pulldown-cmark, which is much cheaper but only understands CommonMark and a few
+    /// extensions.
+    Native,
+}
+
+/// How much of each article a feed carries.
+#[derive(Deserialize, Serialize, FromFormField, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedContent {
+    /// The whole article.
+    #[default]
+    Full,
+    /// Only a summary, so readers visit the site for the rest.
+    Summary,
+    /// The summary as the entry's content, followed by a link to read the rest.
+    Truncated,
+}
+
+/// An article's body, as far as its renderer gets it before a request needs HTML.
+#[derive(Clone)]
+enum Body {
+    /// A filtered pandoc AST, converted to HTML on request.
+    Pandoc(Arc<Pandoc>),
+    /// HTML from the native renderer.
+    Html(Arc<str>),
+}
+
+/// An article as parsed and filtered, before conversion to HTML.
+#[derive(Clone)]
+struct CacheEntry {
+    meta: Arc<ArticleMeta>,
+    body: Body,
+    plain_text: Arc<str>,
+    rendered_at: SystemTime,
+    deps: Arc<Dependencies>,
+    /// See [`content_hash`].
+    content_hash: u64,
+    served: Arc<Served>,
+}
+
+/// What's worked out from a [`CacheEntry`] as it's served, kept with it so it's only worked out
+/// once per render of the article.
+#[derive(Default)]
+struct Served {
+    /// The article's final HTML content.
+    html: tokio::sync::OnceCell<Arc<str>>,
+    /// Pages showing the article, as compressed by [`crate::compress::Compression`].
+    compressed: Arc<crate::compress::Bodies>,
+}
+
+/// What an article's embedded `search` and `related` blocks showed, so it's re-rendered exactly
+/// when they'd show something else.
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct Dependencies {
+    /// Source paths of the articles shown.
+    paths: HashSet<PathBuf>,
+    /// What the blocks asked for, which articles rendered later may also match.
+    queries: Vec<Query>,
+}
+
+/// An embedded block's criteria for showing an article.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum Query {
+    Search(Search),
+    /// Articles sharing any of these tags.
+    SharedTags(Vec<String>),
+    /// Any article at all, such as for text similarity.
+    Any,
+}
+
+impl Query {
+    fn matches(&self, meta: &ArticleMeta) -> bool {
+        match self {
+            Query::Search(search) => search.matches(meta),
+            Query::SharedTags(tags) => meta.tags.iter().any(|t| tags.contains(t)),
+            Query::Any => true,
+        }
+    }
+}
+
+impl Dependencies {
+    /// Whether none of the articles shown have changed on disk since `time`.
+    fn unchanged_since(&self, time: SystemTime) -> bool {
+        self.paths.iter().all(|path| {
+            std::fs::metadata(path)
+                .and_then(|m| m.modified())
+                .is_ok_and(|modified| modified <= time)
+        })
+    }
+}
+
+/// Dependencies recorded so far by articles being rendered.
+static RENDER_DEPS: LazyLock<DashMap<Arc<Path>, Dependencies>> = LazyLock::new(DashMap::new);
+/// Articles to re-render on their next request, since something they show has changed.
+static STALE: LazyLock<DashSet<Arc<Path>>> = LazyLock::new(DashSet::new);
+
+/// Note that the article at `page`, which is being rendered, shows the articles at `shown`
+/// (relative to the site) because of `query`.
+pub fn record_dependencies<'a>(
+    page: &Path,
+    shown: impl IntoIterator<Item = &'a Path>,
+    query: Query,
+) {
+    let mut deps = RENDER_DEPS.entry(page.into()).or_default();
+    deps.paths.extend(shown.into_iter().map(roots::disk_path));
+    deps.queries.push(query);
+}
+
+/// Mark the articles showing, or that could now show, the article at `path` as stale.
+fn invalidate_dependents(path: &Path, meta: &ArticleMeta) {
+    for kv in AST_CACHE.iter() {
+        let deps = &kv.value().deps;
+        if &**kv.key() != path
+            && (deps.paths.contains(path) || deps.queries.iter().any(|q| q.matches(meta)))
+        {
+            STALE.insert(kv.key().clone());
+        }
+    }
+}
+
+static AST_CACHE: LazyLock<DashMap<Arc<Path>, CacheEntry>> = LazyLock::new(DashMap::new);
 static BUSY_ASTS: LazyLock<DashSet<Arc<Path>>> = LazyLock::new(DashSet::new);
 
 pub type Bounds<B> = (Bound<B>, Bound<B>);
@@ -226,10 +1014,12 @@ fn unbounded<B>() -> Bounds<B> {
     (Bound::Unbounded, Bound::Unbounded)
 }
 
-#[derive(Serialize, Deserialize, Default, Clone, Copy, Debug, EnumString)]
+#[derive(Serialize, Deserialize, Default, Clone, Copy, Debug, PartialEq, Eq, EnumString)]
 pub enum SortType {
-    CreateAsc,
+    PublishAsc,
     #[default]
+    PublishDesc,
+    CreateAsc,
     CreateDesc,
     UpdateAsc,
     UpdateDesc,
@@ -251,20 +1041,66 @@ impl<'r> FromFormField<'r> for SortType {
 
 pub type Sorter = dyn Fn(&(&Path, &ArticleMeta), &(&Path, &ArticleMeta)) -> std::cmp::Ordering;
 
+/// Where articles without a date go when sorting by it.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UndatedSort {
+    First,
+    #[default]
+    Last,
+}
+
+/// Whether a date is the placeholder of an article with no date of its own, and none could be
+/// worked out from git or its file.
+pub fn is_undated(date: NaiveDate) -> bool {
+    date == NaiveDate::default()
+}
+
+/// Order two dates, putting undated ones where [`undated_sort`](crate::config::Config::undated_sort)
+/// says whichever way the rest go.
+fn cmp_dates(l: NaiveDate, r: NaiveDate, descending: bool) -> std::cmp::Ordering {
+    cmp_dates_with(CONFIG.undated_sort, l, r, descending)
+}
+
+fn cmp_dates_with(
+    undated_sort: UndatedSort,
+    l: NaiveDate,
+    r: NaiveDate,
+    descending: bool,
+) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+    let undated_first = undated_sort == UndatedSort::First;
+    match (is_undated(l), is_undated(r)) {
+        (true, true) => Ordering::Equal,
+        (true, false) if undated_first => Ordering::Less,
+        (true, false) => Ordering::Greater,
+        (false, true) if undated_first => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) if descending => r.cmp(&l),
+        (false, false) => l.cmp(&r),
+    }
+}
+
 impl SortType {
     pub fn sort_fn(&self) -> &Sorter {
         match self {
-            SortType::CreateAsc => &|(_, l), (_, r)| l.created.cmp(&r.created),
-            SortType::CreateDesc => &|(_, l), (_, r)| r.created.cmp(&l.created),
-            SortType::UpdateAsc => &|(_, l), (_, r)| l.updated.cmp(&r.updated),
-            SortType::UpdateDesc => &|(_, l), (_, r)| r.updated.cmp(&l.updated),
+            SortType::PublishAsc => {
+                &|(_, l), (_, r)| cmp_dates(l.published_date(), r.published_date(), false)
+            }
+            SortType::PublishDesc => {
+                &|(_, l), (_, r)| cmp_dates(l.published_date(), r.published_date(), true)
+            }
+            SortType::CreateAsc => &|(_, l), (_, r)| cmp_dates(l.created, r.created, false),
+            SortType::CreateDesc => &|(_, l), (_, r)| cmp_dates(l.created, r.created, true),
+            SortType::UpdateAsc => &|(_, l), (_, r)| cmp_dates(l.updated, r.updated, false),
+            SortType::UpdateDesc => &|(_, l), (_, r)| cmp_dates(l.updated, r.updated, true),
             SortType::NameAsc => &|(_, l), (_, r)| l.title.cmp(&r.title),
             SortType::NameDesc => &|(_, l), (_, r)| r.title.cmp(&l.title),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Search {
     #[serde(default)]
     pub search_path: PathBuf,
@@ -282,6 +1118,49 @@ pub struct Search {
     pub sort_type: SortType,
     #[serde(default)]
     pub limit: Option<usize>,
+    /// How many results to skip, for pagination.
+    #[serde(default)]
+    pub offset: usize,
+    /// Include articles that aren't ready to be published.
+    #[serde(skip)]
+    pub include_unready: bool,
+    /// Sort pinned articles like any others.
+    #[serde(skip)]
+    pub ignore_pins: bool,
+    /// Include articles that are hidden from listings.
+    #[serde(skip)]
+    pub include_hidden: bool,
+    /// Only include articles carried by a kind of feed.
+    #[serde(skip)]
+    pub in_feed: InFeed,
+}
+
+/// Which feeds a search is for, since articles can choose theirs with `feeds`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum InFeed {
+    /// Not for a feed at all.
+    #[default]
+    Any,
+    /// The feed of everything and the feeds by path and tag.
+    Main,
+    /// A feed that articles are routed into by name.
+    Named(String),
+}
+
+impl Search {
+    /// Whether an article's metadata fits the search's criteria.
+    fn matches(&self, article: &ArticleMeta) -> bool {
+        self.created.contains(&article.created)
+            && self.updated.contains(&article.updated)
+            && (self.include_hidden || article.is_listed())
+            && article.password_hash.is_none()
+            && (self.include_unready || article.is_public())
+            && self.tags.iter().all(|t| article.tags.contains(t))
+            && article.in_feed(&self.in_feed)
+            && article
+                .title
+                .contains(self.title_filter.as_deref().unwrap_or(""))
+    }
 }
 
 impl Default for Search {
@@ -295,15 +1174,134 @@ impl Default for Search {
             sort_type: Default::default(),
             exclude_paths: vec![],
             limit: None,
+            offset: 0,
+            include_unready: false,
+            ignore_pins: false,
+            include_hidden: false,
+            in_feed: InFeed::Any,
+        }
+    }
+}
+
+impl Search {
+    /// Start building a search that finds every published, listed article.
+    pub fn builder() -> SearchBuilder {
+        SearchBuilder::default()
+    }
+}
+
+/// Builds a [`Search`] a criterion at a time. Criteria that take an `Option` are left out when
+/// given `None`, so optional query parameters can be passed straight through.
+#[derive(Clone, Debug, Default)]
+pub struct SearchBuilder {
+    search: Search,
+    page: usize,
+}
+
+impl SearchBuilder {
+    /// Only articles under the site-relative `path`.
+    pub fn path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.search.search_path = path.into();
+        self
+    }
+
+    /// Only articles with all of `tags`, along with any tags already asked for.
+    pub fn tags<T: Into<String>>(mut self, tags: impl IntoIterator<Item = T>) -> Self {
+        self.search.tags.extend(tags.into_iter().map(Into::into));
+        self
+    }
+
+    /// Only articles created on or after `date`.
+    pub fn created_since(mut self, date: impl Into<Option<NaiveDate>>) -> Self {
+        self.search.created.0 = included(date.into());
+        self
+    }
+
+    /// Only articles created on or before `date`.
+    pub fn created_before(mut self, date: impl Into<Option<NaiveDate>>) -> Self {
+        self.search.created.1 = included(date.into());
+        self
+    }
+
+    /// Only articles updated on or after `date`.
+    pub fn updated_since(mut self, date: impl Into<Option<NaiveDate>>) -> Self {
+        self.search.updated.0 = included(date.into());
+        self
+    }
+
+    /// Only articles updated on or before `date`.
+    pub fn updated_before(mut self, date: impl Into<Option<NaiveDate>>) -> Self {
+        self.search.updated.1 = included(date.into());
+        self
+    }
+
+    /// Only articles whose titles contain `filter`.
+    pub fn title_filter(mut self, filter: impl Into<Option<String>>) -> Self {
+        self.search.title_filter = filter.into();
+        self
+    }
+
+    /// Sort results this way, or the default way for `None`.
+    pub fn sort(mut self, sort_type: impl Into<Option<SortType>>) -> Self {
+        self.search.sort_type = sort_type.into().unwrap_or_default();
+        self
+    }
+
+    /// Find at most `limit` articles, or as many as
+    /// [`max_search_results`](crate::config::Config::max_search_results) allows for `None`.
+    pub fn limit(mut self, limit: impl Into<Option<usize>>) -> Self {
+        self.search.limit = limit.into();
+        self
+    }
+
+    /// Find the `page`th page of results, counting from 0, each page being as long as the limit.
+    pub fn page(mut self, page: impl Into<Option<usize>>) -> Self {
+        self.page = page.into().unwrap_or(0);
+        self
+    }
+
+    /// Only articles a kind of feed carries.
+    pub fn in_feed(mut self, feed: InFeed) -> Self {
+        self.search.in_feed = feed;
+        self
+    }
+
+    /// The search, with its limit cut to
+    /// [`max_search_results`](crate::config::Config::max_search_results), since no more are ever
+    /// returned. Pages start from that limit, so they don't skip any results.
+    pub fn build(self) -> Search {
+        let max = CONFIG.max_search_results;
+        let limit = self.search.limit.map(|limit| limit.min(max));
+        Search {
+            limit,
+            offset: self.page.saturating_mul(limit.unwrap_or(max)),
+            ..self.search
         }
     }
 }
 
+/// A bound including `value`, or no bound without one.
+fn included<T>(value: Option<T>) -> Bound<T> {
+    value.map_or(Bound::Unbounded, Bound::Included)
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Article {
     pub content: String,
     pub meta: ArticleMeta,
+    /// The article's text with all markup removed.
+    pub plain_text: String,
     pub rendered_at: SystemTime,
+    /// Received webmentions, with their authors where known.
+    pub mentions: Vec<db::MentionDetail>,
+    /// Taken out of the metadata, so it's never rendered or serialized.
+    #[serde(skip)]
+    pub password_hash: Option<String>,
+    /// The absolute URL of the article's social preview image, from its `image` or generated.
+    pub og_image: Option<String>,
+    /// Where its pages are kept once they're compressed.
+    #[serde(skip)]
+    pub compressed: Arc<crate::compress::Bodies>,
 }
 
 impl Default for Article {
@@ -311,11 +1309,57 @@ impl Default for Article {
         Self {
             content: Default::default(),
             meta: Default::default(),
+            plain_text: Default::default(),
             rendered_at: SystemTime::now(),
+            mentions: vec![],
+            password_hash: None,
+            og_image: None,
+            compressed: Default::default(),
         }
     }
 }
 
+/// Field names of [`ArticleMeta`], used to spot typos that would otherwise land in `extra`.
+const KNOWN_FIELDS: &[&str] = &[
+    "title",
+    "blurb",
+    "tags",
+    "template",
+    "toc",
+    "status",
+    "exclude_from_rss",
+    "feeds",
+    "hidden",
+    "updated",
+    "created",
+    "ready",
+    "always_rerender",
+    "mentioners",
+    "mentions",
+    "footnotes",
+    "no_edit",
+    "published",
+    "changelog",
+    "authors",
+    "lang",
+    "translations",
+    "slug",
+    "noindex",
+    "link_favicons",
+    "sitemap_exclude",
+    "excerpt",
+    "pinned",
+    "csp",
+    "image",
+    "sanitize",
+    "math",
+    "password_hash",
+    "publish_at",
+    "feed_content",
+];
+
+/// What `feeds` calls the main feeds.
+pub const MAIN_FEED: &str = "main";
 const DEFAULT_TITLE: &dyn Fn() -> String = &|| "Untitled Page".to_string();
 const DEFAULT_TEMPLATE: &dyn Fn() -> String = &|| "article".to_string();
 
@@ -331,16 +1375,34 @@ pub struct ArticleMeta {
     pub template: String,
     #[serde(default)]
     pub toc: Vec<Toc>,
+    /// What the article is for, which sets `ready`, `hidden` and `exclude_from_rss` when given.
+    #[serde(default)]
+    pub status: Option<ArticleStatus>,
     #[serde(default)]
     pub exclude_from_rss: bool,
+    /// The named feeds, like `announcements`, the article is in instead of the main feeds, which
+    /// are named `main` for an article that's in both.
+    #[serde(default)]
+    pub feeds: Option<Vec<String>>,
     #[serde(default)]
     pub hidden: bool,
     #[serde(default)]
     pub updated: NaiveDate,
     #[serde(default)]
     pub created: NaiveDate,
+    /// When the article was first published, if that differs from when it was created.
+    #[serde(default)]
+    pub published: Option<NaiveDate>,
     #[serde(default)]
     pub ready: bool,
+    /// When a ready article goes live, in the site's time zone; until then it's treated as
+    /// unready.
+    #[serde(default, deserialize_with = "deserialize_publish_at")]
+    pub publish_at: Option<NaiveDateTime>,
+    /// How much of the article feeds carry, overriding the site's `feed_content` and the feed's
+    /// own choice.
+    #[serde(default)]
+    pub feed_content: Option<FeedContent>,
     #[serde(default)]
     pub always_rerender: bool,
     #[serde(flatten)]
@@ -349,6 +1411,186 @@ pub struct ArticleMeta {
     pub mentioners: Vec<String>,
     #[serde(default)]
     pub mentions: Vec<String>,
+    #[serde(default)]
+    pub footnotes: Vec<Footnote>,
+    #[serde(default)]
+    pub no_edit: bool,
+    /// Notes on substantive edits, sorted newest-first.
+    #[serde(default)]
+    pub changelog: Vec<ChangelogEntry>,
+    /// Who wrote the article, defaulting to the site's author.
+    ///
+    /// Accepts a single name, or a list of names and `{name, email, uri}` maps.
+    #[serde(default, deserialize_with = "deserialize_authors")]
+    pub authors: Vec<Author>,
+    /// The article's language, defaulting to the site's.
+    #[serde(default)]
+    pub lang: Option<String>,
+    /// Versions of this article in other languages.
+    #[serde(default)]
+    pub translations: Vec<TranslationLink>,
+    /// A stable name for the article's URL, replacing its file name.
+    #[serde(default)]
+    pub slug: Option<String>,
+    /// Ask search engines not to index the article, and leave it out of the sitemap.
+    #[serde(default)]
+    pub noindex: bool,
+    /// Show the icon of the site each external link leads to before the link.
+    #[serde(default)]
+    pub link_favicons: bool,
+    /// Leave the article out of the sitemap.
+    #[serde(default)]
+    pub sitemap_exclude: bool,
+    /// The markdown before the article's excerpt marker, shown in lists and feeds instead of the
+    /// whole article.
+    #[serde(default)]
+    pub excerpt: Option<String>,
+    /// Lists show pinned articles first, highest priority first.
+    ///
+    /// `true` is priority 1, and `false` or 0 leaves the article unpinned.
+    #[serde(default, deserialize_with = "deserialize_pin")]
+    pub pinned: u32,
+    /// Replaces the site's `Content-Security-Policy`, e.g. to allow embedded widgets.
+    #[serde(default)]
+    pub csp: Option<String>,
+    /// The image social sites show in previews of the article, relative to it or absolute.
+    ///
+    /// Without one, an image is generated if the site has an `og_image_font`.
+    #[serde(default)]
+    pub image: Option<String>,
+    /// Whether to strip scripts and other active content from the article, overriding the
+    /// site's `sanitize_html`.
+    #[serde(default)]
+    pub sanitize: Option<bool>,
+    /// Whether to render TeX math to static MathML, overriding the site's `math`.
+    #[serde(default)]
+    pub math: Option<bool>,
+    /// An argon2 hash (in PHC format) of the password visitors need to read the article.
+    ///
+    /// Protected articles are left out of every listing and feed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_hash: Option<String>,
+}
+
+/// Who an article is shown to, as one choice instead of the `ready`, `hidden` and
+/// `exclude_from_rss` flags, which are still read for articles that don't give a status.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ArticleStatus {
+    /// Not shown to readers yet.
+    Draft,
+    /// Shown everywhere.
+    Published,
+    /// Readable by anyone with its link, but left out of listings, feeds and the sitemap.
+    Unlisted,
+    /// Still readable and listed, but no longer in feeds.
+    Archived,
+}
+
+/// A link to a translation of an article.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TranslationLink {
+    pub lang: String,
+    /// The translation's site-relative path, like `/blog/post.fr`.
+    pub path: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Author {
+    pub name: String,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub uri: Option<String>,
+}
+
+fn deserialize_authors<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Vec<Author>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneAuthor {
+        Name(String),
+        Full(Author),
+    }
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Authors {
+        One(OneAuthor),
+        Many(Vec<OneAuthor>),
+    }
+    let authors = match Authors::deserialize(d)? {
+        Authors::One(author) => vec![author],
+        Authors::Many(authors) => authors,
+    };
+    Ok(authors
+        .into_iter()
+        .map(|author| match author {
+            OneAuthor::Name(name) => Author {
+                name,
+                email: None,
+                uri: None,
+            },
+            OneAuthor::Full(author) => author,
+        })
+        .collect())
+}
+
+fn deserialize_pin<'de, D: serde::Deserializer<'de>>(d: D) -> Result<u32, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Pin {
+        Flag(bool),
+        Priority(u32),
+        // Pandoc passes numbers through as text.
+        Text(String),
+    }
+    match Pin::deserialize(d)? {
+        Pin::Flag(pinned) => Ok(pinned.into()),
+        Pin::Priority(priority) => Ok(priority),
+        Pin::Text(text) => match text.trim() {
+            "true" => Ok(1),
+            "false" => Ok(0),
+            text => text.parse().map_err(serde::de::Error::custom),
+        },
+    }
+}
+
+fn deserialize_publish_at<'de, D: serde::Deserializer<'de>>(
+    d: D,
+) -> Result<Option<NaiveDateTime>, D::Error> {
+    let Some(text) = Option::<String>::deserialize(d)? else {
+        return Ok(None);
+    };
+    let text = text.trim();
+    [
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%d %H:%M:%S%.f",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+    .or_else(|| {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d")
+            .ok()
+            .map(|date| date.and_time(NaiveTime::MIN))
+    })
+    .map(Some)
+    .ok_or_else(|| serde::de::Error::custom(format!("Bad publish_at time {text:?}")))
+}
+
+/// A note about an edit to an article.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChangelogEntry {
+    pub date: NaiveDate,
+    pub note: String,
+}
+
+/// A footnote, with the ids of its definition and reference in the rendered HTML.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Footnote {
+    pub id: String,
+    pub ref_id: String,
+    pub text: String,
 }
 
 impl TryFrom<&Pandoc> for ArticleMeta {
@@ -406,10 +1648,145 @@ impl TryFrom<&Pandoc> for ArticleMeta {
             .collect();
         let meta = serde_json::Value::Object(meta);
         let meta: ArticleMeta = serde_json::from_value(meta)?;
-        Ok(meta)
+        meta.finish()
+    }
+}
+
+impl ArticleMeta {
+    /// Check freshly parsed metadata and fill in site-wide defaults.
+    fn finish(mut self) -> Result<Self, ArticleError> {
+        let meta = &mut self;
+        meta.check_extra()?;
+        // The older flags are kept in step with the status, for templates that read them.
+        if let Some(status) = meta.status {
+            meta.ready = status != ArticleStatus::Draft;
+            meta.hidden = status == ArticleStatus::Unlisted;
+            meta.exclude_from_rss =
+                matches!(status, ArticleStatus::Unlisted | ArticleStatus::Archived);
+            meta.sitemap_exclude |= status == ArticleStatus::Unlisted;
+        }
+        if meta.authors.is_empty() {
+            meta.authors.push(Author {
+                name: CONFIG.author.clone(),
+                email: None,
+                uri: Some(crate::url::author_url()),
+            });
+        }
+        meta.lang.get_or_insert_with(|| CONFIG.lang.clone());
+        meta.changelog
+            .sort_by_key(|entry| std::cmp::Reverse(entry.date));
+        Ok(self)
+    }
+}
+
+impl ArticleMeta {
+    /// The article's status, worked out from the older flags if it doesn't give one.
+    pub fn status(&self) -> ArticleStatus {
+        if let Some(status) = self.status {
+            status
+        } else if !self.ready {
+            ArticleStatus::Draft
+        } else if self.hidden {
+            ArticleStatus::Unlisted
+        } else if self.exclude_from_rss {
+            ArticleStatus::Archived
+        } else {
+            ArticleStatus::Published
+        }
+    }
+
+    /// Whether the article's math is rendered to MathML on the server.
+    pub fn renders_math(&self) -> bool {
+        self.math.unwrap_or_else(math_by_default)
+    }
+
+    /// Whether the article may be shown to readers.
+    pub fn is_public(&self) -> bool {
+        (self.status() != ArticleStatus::Draft && !self.is_scheduled()) || CONFIG.preview_nonready
+    }
+
+    /// Whether the article is listed in searches and on tag pages.
+    pub fn is_listed(&self) -> bool {
+        self.status() != ArticleStatus::Unlisted
+    }
+
+    /// Whether feeds carry the article.
+    pub fn in_feeds(&self) -> bool {
+        self.status() == ArticleStatus::Published
+    }
+
+    /// Whether a kind of feed carries the article. Articles are in the main feeds unless their
+    /// `feeds` route them elsewhere without also naming `main`.
+    pub fn in_feed(&self, feed: &InFeed) -> bool {
+        let named = |name: &str| {
+            self.feeds
+                .as_ref()
+                .is_some_and(|f| f.iter().any(|f| f == name))
+        };
+        match feed {
+            InFeed::Any => true,
+            InFeed::Main => self.in_feeds() && (self.feeds.is_none() || named(MAIN_FEED)),
+            InFeed::Named(name) => self.in_feeds() && named(name),
+        }
+    }
+
+    /// Whether the article is waiting for its `publish_at` time.
+    pub fn is_scheduled(&self) -> bool {
+        self.is_scheduled_at(timezone::now().naive_local())
+    }
+
+    /// Whether the article is still waiting for its `publish_at` time at `now`.
+    fn is_scheduled_at(&self, now: NaiveDateTime) -> bool {
+        self.publish_at.is_some_and(|publish_at| publish_at > now)
+    }
+
+    /// The article's language, falling back to the site's.
+    pub fn lang(&self) -> &str {
+        self.lang.as_deref().unwrap_or(&CONFIG.lang)
+    }
+
+    /// The date the article was published, falling back to its creation date.
+    pub fn published_date(&self) -> NaiveDate {
+        self.published.unwrap_or(self.created)
+    }
+
+    /// Warn about keys in `extra` that are suspiciously close to a known field name.
+    fn check_extra(&self) -> Result<(), ArticleError> {
+        let Value::Mapping(extra) = &self.extra else {
+            return Ok(());
+        };
+        for key in extra.keys().filter_map(Value::as_str) {
+            let Some(known) = typo_of(key) else {
+                continue;
+            };
+            eprintln!(
+                "Front-matter key `{key}` in {:?} looks like a typo of `{known}`",
+                self.title
+            );
+            if CONFIG.strict_meta {
+                return Err(ArticleError::SuspiciousMeta(key.to_string(), known));
+            }
+        }
+        Ok(())
     }
 }
 
+/// The known field a front-matter key is probably a typo of.
+///
+/// Short keys are allowed fewer edits, since a custom key like `tag` or `to` is only an edit or
+/// two from a short field like `tags` or `toc`. Keys of three characters or fewer aren't checked.
+fn typo_of(key: &str) -> Option<&'static str> {
+    let max_distance = match key.chars().count() {
+        0..=3 => return None,
+        4 => 1,
+        _ => 2,
+    };
+    KNOWN_FIELDS
+        .iter()
+        .copied()
+        .find(|field| strsim::levenshtein(key, field) <= max_distance)
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum Toc {
     Text(String),
@@ -466,24 +1843,799 @@ impl<'r> FromSegments<'r> for ArticlePath {
         let path = segments
             .to_path_buf(false)
             .map_err(error::ArticleError::MalformedPath)?;
-        let mut path = Path::new("articles").join(path);
-        path.set_extension("md");
-        if !path.exists() {
-            return Err(error::ArticleError::NotMarkdown);
+        resolve(&path)
+            .map(Self)
+            .ok_or(error::ArticleError::NotMarkdown)
+    }
+}
+
+/// Find the source of the article at a site-relative path, which may be its slug.
+pub fn resolve(rel: &Path) -> Option<PathBuf> {
+    source_path(rel).or_else(|| SLUGS.get(rel).map(|p| p.to_path_buf()))
+}
+
+/// Whether any rendered article routes itself into the feed `name` with `feeds`.
+pub fn names_feed(name: &str) -> bool {
+    AST_CACHE.iter().any(|kv| {
+        kv.value()
+            .meta
+            .feeds
+            .iter()
+            .flatten()
+            .any(|feed| feed == name)
+    })
+}
+
+/// The markdown of an article's source before its excerpt marker, less any front matter.
+///
+/// This reads the source rather than the rendered article, since the marker is an HTML comment
+/// that renderers are free to drop.
+fn excerpt(source: &str) -> Option<String> {
+    let mut excerpt = vec![];
+    for line in body_lines(source) {
+        if line.trim() == CONFIG.excerpt_marker {
+            let excerpt = excerpt.join("\n").trim().to_string();
+            return (!excerpt.is_empty()).then_some(excerpt);
+        }
+        excerpt.push(line);
+    }
+    None
+}
+
+/// The lines of a markdown source after its front matter.
+fn body_lines(source: &str) -> impl Iterator<Item = &str> {
+    front_matter::split(source).1.lines()
+}
+
+/// A hash of an article's body that ignores its front matter and whitespace, so only real edits
+/// change it.
+fn content_hash(source: &str) -> u64 {
+    let mut digest = Sha256::new();
+    for word in body_lines(source).flat_map(str::split_whitespace) {
+        digest.update(word.as_bytes());
+        digest.update([0]);
+    }
+    let digest = digest.finalize();
+    u64::from_le_bytes(
+        digest[..8]
+            .try_into()
+            .expect("SHA-256 is longer than 8 bytes"),
+    )
+}
+
+/// Find the markdown source of the article at a site-relative path.
+///
+/// `post.fr` is looked up as `post.fr.md` first, so dots in names can mark translations, and
+/// then as `post.md`, so old links with other extensions keep working. A content root's own
+/// prefix leads to its `index.md`.
+pub fn source_path(rel: &Path) -> Option<PathBuf> {
+    let (root, rel) = roots::for_rel(rel)?;
+    if rel.as_os_str().is_empty() {
+        return find_file(&root.dir, Path::new("index.md"));
+    }
+    let mut path = rel.as_os_str().to_owned();
+    path.push(".md");
+    find_file(&root.dir, Path::new(&path))
+        .or_else(|| find_file(&root.dir, &rel.with_extension("md")))
+}
+
+/// The file at `rel` in `dir`, if there is one.
+///
+/// With [`case_insensitive_paths`](crate::config::Config::case_insensitive_paths), names that
+/// differ only in case match too, and the path is spelled as it is on disk, preferring exact
+/// matches.
+fn find_file(dir: &Path, rel: &Path) -> Option<PathBuf> {
+    let exact = dir.join(rel);
+    if !CONFIG.case_insensitive_paths {
+        return exact.is_file().then_some(exact);
+    }
+    let mut path = dir.to_path_buf();
+    for component in rel.components() {
+        let Component::Normal(name) = component else {
+            return None;
+        };
+        let wanted = name.to_string_lossy().to_lowercase();
+        let mut found = None;
+        for entry in std::fs::read_dir(&path).ok()?.flatten() {
+            let entry = entry.file_name();
+            if entry == name {
+                found = Some(entry);
+                break;
+            }
+            if found.is_none() && entry.to_string_lossy().to_lowercase() == wanted {
+                found = Some(entry);
+            }
         }
-        Ok(Self(path))
+        path.push(found?);
     }
+    path.is_file().then_some(path)
 }
 
-impl From<&Article> for Template {
-    fn from(article: &Article) -> Template {
-        Template::render(
-            article.meta.template.clone(),
-            context! {
-                toc: article.meta.toc.iter().map(ToString::to_string).collect::<String>(),
-                meta: &article.meta,
-                content: &article.content,
+impl Article {
+    /// Render the article through its template, with `extra` added to the template context.
+    pub fn render(&self, extra: impl Serialize) -> Result<RawHtml<String>, ArticleError> {
+        render_page(&self.meta.template, self.template_context(extra)?)
+    }
+
+    /// What the article's template sees, with `extra` added.
+    fn template_context(&self, extra: impl Serialize) -> Result<serde_json::Value, ArticleError> {
+        let mut ctx = serde_json::to_value(context! {
+            toc: self.meta.toc.iter().map(ToString::to_string).collect::<String>(),
+            meta: &self.meta,
+            content: &self.content,
+            mentions: &self.mentions,
+            og_image: &self.og_image,
+            // Front-matter keys wolog doesn't know, for custom templates.
+            custom: match &self.meta.extra {
+                extra @ Value::Mapping(_) => extra.clone(),
+                _ => Value::Mapping(Default::default()),
             },
+            feeds: std::iter::once(FeedLink::main())
+                .chain(self.meta.tags.iter().map(|tag| FeedLink::new("", std::slice::from_ref(tag))))
+                .collect::<Vec<_>>(),
+        })?;
+        if let (Some(ctx), serde_json::Value::Object(extra)) =
+            (ctx.as_object_mut(), serde_json::to_value(extra)?)
+        {
+            ctx.extend(extra);
+        }
+        Ok(ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{config, pandoc_installed, run_with, Fixture};
+
+    /// The titles of the articles `search` finds within `fixture`, in order.
+    async fn titles(fixture: &Fixture, search: Search) -> Vec<String> {
+        let search = Search {
+            search_path: fixture.rel("").into(),
+            ..search
+        };
+        search_all(&search)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(_, meta)| meta.title.clone())
+            .collect()
+    }
+
+    #[test]
+    fn typos_of_fields_are_caught() {
+        assert_eq!(typo_of("hiddne"), Some("hidden"));
+        assert_eq!(typo_of("tittle"), Some("title"));
+        assert_eq!(typo_of("tgs"), None);
+        assert_eq!(typo_of("slgu"), None);
+        assert_eq!(typo_of("slugg"), Some("slug"));
+    }
+
+    #[test]
+    fn short_custom_keys_are_not_typos() {
+        for key in ["tag", "tip", "to", "lng", "mood", "series"] {
+            assert_eq!(typo_of(key), None, "{key}");
+        }
+    }
+
+    #[rocket::async_test]
+    async fn served_html_is_kept_with_the_cache_entry() {
+        let fixture = Fixture::new("served-html");
+        let path = fixture
+            .article(
+                "post",
+                "---\ntitle: Kept\nstatus: published\n---\n\nSome *text*.\n",
+            )
+            .await;
+        let first = get_article(&path).await.unwrap();
+        let html = AST_CACHE.get(&path).unwrap().served.html.get().cloned();
+        assert_eq!(html.as_deref(), Some(&*first.content));
+
+        let second = get_article(&path).await.unwrap();
+        assert!(Arc::ptr_eq(&first.compressed, &second.compressed));
+
+        fixture
+            .article(
+                "post",
+                "---\ntitle: Kept\nstatus: published\n---\n\nNew text.\n",
+            )
+            .await;
+        let third = get_article(&path).await.unwrap();
+        assert!(!Arc::ptr_eq(&first.compressed, &third.compressed));
+        assert!(third.content.contains("New text."));
+    }
+
+    #[rocket::async_test]
+    async fn missing_templates_fall_back_to_the_default() {
+        let fixture = Fixture::new("missing-template");
+        let path = fixture
+            .article(
+                "post",
+                "---\ntitle: Templated\nstatus: published\ntemplate: no-such-template\n---\n\nHi.\n",
+            )
+            .await;
+        let article = get_article(&path).await.unwrap();
+        assert_eq!(article.meta.template, DEFAULT_TEMPLATE());
+        assert!(article.render(rocket_dyn_templates::context! {}).is_ok());
+    }
+
+    #[rocket::async_test]
+    async fn plain_text_is_cached_with_the_article() {
+        let fixture = Fixture::new("plain-text");
+        let path = fixture
+            .article(
+                "post",
+                "---\ntitle: Plain\nstatus: published\n---\n\nHello *world*, [linked](/x).\n\n<div>raw</div>\n",
+            )
+            .await;
+        let cached = AST_CACHE.get(&path).unwrap().plain_text.clone();
+        assert_eq!(&*cached, "Hello world, linked.");
+        assert_eq!(get_article(&path).await.unwrap().plain_text, &*cached);
+    }
+
+    #[rocket::async_test]
+    async fn changelog_entries_are_shown_newest_first() {
+        let fixture = Fixture::new("changelog");
+        let path = fixture
+            .article(
+                "post",
+                "---\ntitle: Changing\nstatus: published\nchangelog:\n  - date: 2024-01-05\n    note: Fixed a typo\n  - date: 2024-03-01\n    note: Added a **section**\n  - date: 2024-02-10\n    note: Clarified the intro\n---\n\nBody.\n",
+            )
+            .await;
+        let article = get_article(&path).await.unwrap();
+        let notes: Vec<_> = article.meta.changelog.iter().map(|e| &*e.note).collect();
+        assert_eq!(
+            notes,
+            ["Added a **section**", "Clarified the intro", "Fixed a typo"]
+        );
+
+        let page = article.render(rocket_dyn_templates::context! {}).unwrap().0;
+        let section = page
+            .find("Updates")
+            .expect("The page should have an Updates section");
+        let added = page.find("Added a <strong>section</strong>").unwrap();
+        let clarified = page.find("Clarified the intro").unwrap();
+        let fixed = page.find("Fixed a typo").unwrap();
+        assert!(section < added && added < clarified && clarified < fixed);
+    }
+
+    #[test]
+    fn searches_return_at_most_the_cap() {
+        let articles: Vec<usize> = (0..50).collect();
+        let limitless = Search::default();
+        assert_eq!(
+            page_of(articles.clone(), &limitless, 20),
+            ((0..20).collect(), true)
+        );
+
+        let greedy = Search {
+            limit: Some(1000),
+            ..Default::default()
+        };
+        assert_eq!(page_of(articles.clone(), &greedy, 20).0.len(), 20);
+
+        let last = Search {
+            offset: 40,
+            ..Default::default()
+        };
+        assert_eq!(
+            page_of(articles.clone(), &last, 20),
+            ((40..50).collect(), false)
+        );
+
+        let small = Search {
+            limit: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(page_of(articles, &small, 20), ((0..5).collect(), true));
+    }
+
+    #[rocket::async_test]
+    async fn slow_conversions_time_out() {
+        let mut command = Command::new("sh");
+        command.args(["-c", "exec sleep 5"]);
+        let started = Instant::now();
+        let result = run_process(command, vec![], Duration::from_millis(200)).await;
+        assert!(matches!(result, Err(ArticleError::RenderTimeout)));
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        // Closing its output doesn't let a process that keeps running outlast the timeout.
+        let mut command = Command::new("sh");
+        command.args(["-c", "exec >&- 2>&-; sleep 5"]);
+        let started = Instant::now();
+        let result = run_process(command, vec![], Duration::from_millis(200)).await;
+        assert!(matches!(result, Err(ArticleError::RenderTimeout)));
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        let quick = run_process(Command::new("cat"), b"hi".to_vec(), Duration::from_secs(5)).await;
+        assert_eq!(quick.unwrap(), b"hi");
+    }
+
+    #[rocket::async_test]
+    async fn always_rerender_articles_are_refreshed_unless_busy() {
+        let fixture = Fixture::new("rerender");
+        let source = |title: &str| {
+            format!("---\ntitle: {title}\nstatus: published\nalways_rerender: true\n---\n\nHi.\n")
+        };
+        let idle = fixture.article("idle", &source("Idle")).await;
+        let busy = fixture.article("busy", &source("Busy")).await;
+        let rendered_at = |path: &Arc<Path>| AST_CACHE.get(path).unwrap().rendered_at;
+        let (idle_before, busy_before) = (rendered_at(&idle), rendered_at(&busy));
+        // As though a request were rendering it already.
+        BUSY_ASTS.insert(busy.clone());
+
+        let task = tokio::spawn(rerender_periodically(Duration::from_millis(20)));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while rendered_at(&idle) == idle_before {
+            assert!(Instant::now() < deadline, "The article wasn't re-rendered");
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        task.abort();
+        assert_eq!(rendered_at(&busy), busy_before);
+        BUSY_ASTS.remove(&busy);
+    }
+
+    #[test]
+    fn pandoc_renders_are_cut_off_at_the_configured_timeout() {
+        // Pandoc isn't always installed where tests run.
+        if !pandoc_installed() {
+            return;
+        }
+        let timing_out = config(|c| {
+            c.renderer = Renderer::Pandoc;
+            c.render_timeout = 0;
+        });
+        let result = run_with(timing_out, preview("Hi."));
+        assert!(
+            matches!(result, Err(ArticleError::RenderTimeout)),
+            "{result:?}"
+        );
+
+        let result = run_with(config(|c| c.renderer = Renderer::Pandoc), preview("Hi."));
+        assert!(result.unwrap().contains("Hi."));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[rocket::async_test]
+    async fn cancelled_conversions_leave_no_process_behind() {
+        // The odd duration marks this test's process among any others.
+        let duration = format!("30.{}", std::process::id());
+        let cmdline = format!("sleep\0{duration}\0");
+        let running = || {
+            std::fs::read_dir("/proc").unwrap().flatten().any(|entry| {
+                std::fs::read(entry.path().join("cmdline")).is_ok_and(|c| c == cmdline.as_bytes())
+            })
+        };
+        let mut command = Command::new("sleep");
+        command.arg(&duration);
+        let render = tokio::spawn(run_process(command, vec![], Duration::from_secs(60)));
+        for _ in 0..100 {
+            if running() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(running());
+
+        render.abort();
+        assert!(render.await.unwrap_err().is_cancelled());
+        assert!(!running());
+    }
+
+    #[test]
+    fn excerpts_end_at_the_marker() {
+        let marked = format!(
+            "---\ntitle: Marked\n---\n\nThe opening.\n\nMore opening.\n{}\n\nThe rest.\n",
+            CONFIG.excerpt_marker
+        );
+        assert_eq!(
+            excerpt(&marked).as_deref(),
+            Some("The opening.\n\nMore opening.")
+        );
+
+        let unmarked = "---\ntitle: Unmarked\n---\n\nThe opening.\n\nThe rest.\n";
+        assert_eq!(excerpt(unmarked), None);
+    }
+
+    #[rocket::async_test]
+    async fn pinned_articles_sort_first() {
+        let fixture = Fixture::new("pins");
+        for (name, created, pinned) in [
+            ("old", "2020-01-01", "2"),
+            ("older", "2019-01-01", "true"),
+            ("new", "2024-01-01", "false"),
+            ("newer", "2025-01-01", "0"),
+        ] {
+            fixture
+                .article(
+                    name,
+                    &format!(
+                        "---\ntitle: {name}\nstatus: published\ncreated: {created}\npinned: {pinned}\n---\n\nHi.\n"
+                    ),
+                )
+                .await;
+        }
+        let newest_first = Search {
+            sort_type: SortType::CreateDesc,
+            ..Default::default()
+        };
+        assert_eq!(
+            titles(&fixture, newest_first.clone()).await,
+            ["old", "older", "newer", "new"]
+        );
+        let chronological = Search {
+            ignore_pins: true,
+            ..newest_first
+        };
+        assert_eq!(
+            titles(&fixture, chronological).await,
+            ["newer", "new", "old", "older"]
+        );
+    }
+
+    #[rocket::async_test]
+    async fn editing_a_matched_article_refreshes_the_page_embedding_it() {
+        let fixture = Fixture::new("dependencies");
+        let post = |title: &str| format!("---\ntitle: {title}\nstatus: published\n---\n\nHi.\n");
+        fixture.article("posts/first", &post("First title")).await;
+        let index = fixture
+            .article(
+                "index",
+                &format!(
+                    "---\ntitle: Index\nstatus: published\n---\n\n```search\nsearch_path: {}\n```\n",
+                    fixture.rel("posts")
+                ),
+            )
+            .await;
+        assert!(get_article(&index)
+            .await
+            .unwrap()
+            .content
+            .contains("First title"));
+
+        fixture.article("posts/first", &post("Renamed title")).await;
+        let content = get_article(&index).await.unwrap().content.clone();
+        assert!(content.contains("Renamed title"), "{content}");
+        assert!(!content.contains("First title"));
+
+        // Articles rendered later that the search matches show up too.
+        fixture.article("posts/second", &post("Second title")).await;
+        assert!(get_article(&index)
+            .await
+            .unwrap()
+            .content
+            .contains("Second title"));
+    }
+
+    #[rocket::async_test]
+    async fn articles_embedding_searches_for_each_other_still_render() {
+        let fixture = Fixture::new("embed-cycle");
+        let article = |title: &str, tag: &str, wants: &str| {
+            format!(
+                "---\ntitle: {title}\nstatus: published\ntags: [{tag}]\n---\n\n```search\nsearch_path: {}\ntags: [{wants}]\n```\n",
+                fixture.rel("")
+            )
+        };
+        let ping = fixture.write("ping", &article("Ping", "ping", "pong"));
+        let pong = fixture.write("pong", &article("Pong", "pong", "ping"));
+
+        let rendered = tokio::time::timeout(Duration::from_secs(10), get_article(&ping))
+            .await
+            .expect("Rendering articles that embed each other never finished")
+            .unwrap();
+        assert!(rendered.content.contains("Pong"), "{}", rendered.content);
+        assert!(get_article(&pong).await.unwrap().content.contains("Ping"));
+    }
+
+    #[rocket::async_test]
+    async fn embedded_searches_nest_at_most_the_configured_depth() {
+        let fixture = Fixture::new("embed-depth");
+        let path = fixture.write("post", "---\ntitle: Deep\nstatus: published\n---\n\nHi.\n");
+        let chain = |len: usize| -> Vec<Arc<Path>> {
+            (0..len)
+                .map(|i| fixture.dir().join(format!("outer-{i}.md")).into())
+                .collect()
+        };
+
+        let too_deep = in_rendering_chain(chain(CONFIG.max_embed_depth), get_article(&path)).await;
+        assert!(matches!(too_deep, Err(ArticleError::EmbedsTooDeep)));
+        let deep_enough =
+            in_rendering_chain(chain(CONFIG.max_embed_depth - 1), get_article(&path)).await;
+        assert_eq!(deep_enough.unwrap().meta.title, "Deep");
+    }
+
+    #[test]
+    fn articles_without_dates_are_undated_and_sort_to_one_end() {
+        let undated = ArticleMeta::from_front_matter(None).unwrap();
+        assert!(is_undated(undated.created));
+        assert!(is_undated(undated.updated));
+
+        let date = |year| NaiveDate::from_ymd_opt(year, 1, 1).unwrap();
+        let sorted = |undated_sort, descending| {
+            let mut dates = vec![date(2020), undated.created, date(2024)];
+            dates.sort_by(|l, r| cmp_dates_with(undated_sort, *l, *r, descending));
+            dates
+        };
+        let none = undated.created;
+        assert_eq!(
+            sorted(UndatedSort::Last, true),
+            [date(2024), date(2020), none]
+        );
+        assert_eq!(
+            sorted(UndatedSort::Last, false),
+            [date(2020), date(2024), none]
+        );
+        assert_eq!(
+            sorted(UndatedSort::First, true),
+            [none, date(2024), date(2020)]
+        );
+        assert_eq!(
+            sorted(UndatedSort::First, false),
+            [none, date(2020), date(2024)]
+        );
+
+        // Listings sort by the configured end, which is last by default.
+        let dated = ArticleMeta {
+            created: date(2020),
+            ..undated.clone()
+        };
+        let newest_first = SortType::CreateDesc.sort_fn();
+        assert_eq!(
+            newest_first(&(Path::new("a"), &undated), &(Path::new("b"), &dated)),
+            std::cmp::Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn built_searches_match_searches_written_out() {
+        let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let built = Search::builder()
+            .path("posts")
+            .tags(["a"])
+            .tags(vec!["b".to_string()])
+            .created_since(date)
+            .created_before(None)
+            .updated_before(date)
+            .title_filter(Some("word".to_string()))
+            .sort(SortType::NameAsc)
+            .limit(10)
+            .page(2)
+            .in_feed(InFeed::Main)
+            .build();
+        let written = Search {
+            search_path: PathBuf::from("posts"),
+            tags: vec!["a".to_string(), "b".to_string()],
+            created: (Bound::Included(date), Bound::Unbounded),
+            updated: (Bound::Unbounded, Bound::Included(date)),
+            title_filter: Some("word".to_string()),
+            sort_type: SortType::NameAsc,
+            limit: Some(10),
+            offset: 20,
+            in_feed: InFeed::Main,
+            ..Default::default()
+        };
+        assert_eq!(built, written);
+
+        // Options left as `None` change nothing.
+        let nothing = Search::builder()
+            .title_filter(None)
+            .sort(None)
+            .limit(None)
+            .page(None)
+            .build();
+        assert_eq!(nothing, Search::default());
+
+        // Pages are as long as the limit searches actually use.
+        let max = CONFIG.max_search_results;
+        assert_eq!(
+            Search::builder().limit(max * 3).page(2).build(),
+            Search {
+                limit: Some(max),
+                offset: max * 2,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            Search::builder().page(3).build(),
+            Search {
+                offset: max * 3,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[rocket::async_test]
+    async fn lua_filters_transform_content_and_invalidate_when_edited() {
+        let fixture = Fixture::new("lua-filter");
+        let filter = fixture.dir().join("shout.lua");
+        std::fs::create_dir_all(fixture.dir()).unwrap();
+        std::fs::write(
+            &filter,
+            "function Str(el) return pandoc.Str(el.text:upper()) end\n",
+        )
+        .unwrap();
+        let before = SystemTime::now() - Duration::from_secs(60);
+        assert!(modified_since([&filter], before));
+        assert!(!modified_since(
+            [&filter],
+            SystemTime::now() + Duration::from_secs(60)
+        ));
+        assert!(!modified_since(
+            [&fixture.dir().join("missing.lua")],
+            before
+        ));
+
+        let lua = [filter];
+        let args: Vec<_> = filter_args_for(&lua, &[]).collect();
+        assert_eq!(args, [OsStr::new("--lua-filter"), lua[0].as_os_str()]);
+        // Pandoc isn't always installed where tests run.
+        let Ok(html) = run_pandoc(
+            ["-f", "markdown", "-t", "html"]
+                .map(OsStr::new)
+                .into_iter()
+                .chain(args),
+            b"quiet words".to_vec(),
+        )
+        .await
+        else {
+            return;
+        };
+        let html = String::from_utf8(html).unwrap();
+        assert!(html.contains("QUIET WORDS"), "{html}");
+    }
+
+    #[test]
+    fn configured_lua_filters_apply_to_articles() {
+        // Pandoc isn't always installed where tests run.
+        if !pandoc_installed() {
+            return;
+        }
+        let filter = std::env::temp_dir().join(format!("wolog-shout-{}.lua", std::process::id()));
+        std::fs::write(
+            &filter,
+            "function Str(el) return pandoc.Str(el.text:upper()) end\n",
+        )
+        .unwrap();
+        let shouting = config(|c| {
+            c.renderer = Renderer::Pandoc;
+            c.lua_filters = vec![filter.clone()];
+        });
+        run_with(shouting, async {
+            let fixture = Fixture::new("lua-article");
+            let path = fixture
+                .article(
+                    "post",
+                    "---\ntitle: Shouted\nstatus: published\n---\n\nquiet words\n",
+                )
+                .await;
+            let article = get_article(&path).await.unwrap();
+            assert!(
+                article.content.contains("QUIET WORDS"),
+                "{}",
+                article.content
+            );
+        });
+        std::fs::remove_file(&filter).unwrap();
+    }
+
+    #[rocket::async_test]
+    async fn scheduled_articles_go_live_at_their_publish_time() {
+        let meta: ArticleMeta = serde_yml::from_str(
+            "title: Later\ncreated: 2024-05-01\npublish_at: 2024-05-01 09:30\n",
+        )
+        .unwrap();
+        let publish_at = NaiveDate::from_ymd_opt(2024, 5, 1)
+            .unwrap()
+            .and_hms_opt(9, 30, 0)
+            .unwrap();
+        assert_eq!(meta.publish_at, Some(publish_at));
+        assert!(meta.is_scheduled_at(publish_at - chrono::Duration::seconds(1)));
+        assert!(!meta.is_scheduled_at(publish_at));
+        assert!(!meta.is_scheduled_at(publish_at + chrono::Duration::seconds(1)));
+
+        let fixture = Fixture::new("scheduled");
+        let post = |title: &str, publish_at: &str| {
+            format!(
+                "---\ntitle: {title}\nstatus: published\npublish_at: {publish_at}\n---\n\nHi.\n"
+            )
+        };
+        fixture
+            .article("past", &post("Past", "2000-01-01 00:00"))
+            .await;
+        let future = fixture
+            .article("future", &post("Future", "2999-01-01 00:00"))
+            .await;
+        assert_eq!(titles(&fixture, Search::default()).await, ["Past"]);
+        assert!(matches!(
+            get_article(&future).await,
+            Err(ArticleError::NotForPublication)
+        ));
+    }
+
+    #[rocket::async_test]
+    async fn custom_front_matter_reaches_templates() {
+        let fixture = Fixture::new("custom-keys");
+        let path = fixture
+            .article(
+                "post",
+                "---\ntitle: Custom\nstatus: published\nmood: sunny\nseries:\n  name: Travels\n  part: 2\n---\n\nHi.\n",
+            )
+            .await;
+        let article = get_article(&path).await.unwrap();
+        let ctx = article.template_context(context! {}).unwrap();
+        let rendered = rocket_dyn_templates::tera::Tera::one_off(
+            "{{ custom.mood }}, {{ custom.series.name }} part {{ custom.series.part }}",
+            &rocket_dyn_templates::tera::Context::from_value(ctx).unwrap(),
+            false,
         )
+        .unwrap();
+        assert_eq!(rendered, "sunny, Travels part 2");
+    }
+
+    #[rocket::async_test]
+    async fn updated_dates_ignore_edits_that_leave_the_body_alone() {
+        let fixture = Fixture::new("updated-churn");
+        let updated = |path: &Arc<Path>| AST_CACHE.get(path).unwrap().meta.updated;
+        let touch_long_ago = |path: &Arc<Path>| {
+            let long_ago = SystemTime::UNIX_EPOCH + Duration::from_secs(1_577_880_000);
+            std::fs::File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(long_ago)
+                .unwrap();
+        };
+        let path = fixture.write(
+            "post",
+            "---\ntitle: Steady\nstatus: published\ncreated: 2020-01-01\n---\n\nThe same words.\n",
+        );
+        touch_long_ago(&path);
+        refresh(&path).await;
+        let first = updated(&path);
+        assert!(first < timezone::now().date_naive());
+
+        // New front matter and whitespace don't count as an update...
+        fixture.write(
+            "post",
+            "---\ntitle: Steady, renamed\nstatus: published\ncreated: 2020-01-01\n---\n\nThe   same\nwords.\n\n",
+        );
+        refresh(&path).await;
+        assert_eq!(updated(&path), first);
+
+        // ...but new words do.
+        fixture.write(
+            "post",
+            "---\ntitle: Steady, renamed\nstatus: published\ncreated: 2020-01-01\n---\n\nSome new words.\n",
+        );
+        refresh(&path).await;
+        assert_eq!(updated(&path), timezone::now().date_naive());
+    }
+
+    #[rocket::async_test]
+    async fn searching_everything_ends_even_without_room_for_results() {
+        let fixture = Fixture::new("zero-cap");
+        for n in 0..3 {
+            fixture
+                .article(
+                    &format!("post-{n}"),
+                    "---\ntitle: Capped\nstatus: published\n---\n\nHi.\n",
+                )
+                .await;
+        }
+        let search = Search {
+            search_path: fixture.rel("").into(),
+            ..Default::default()
+        };
+        let all = |max| {
+            let search = &search;
+            tokio::time::timeout(Duration::from_secs(10), search_all_within(search, max))
+        };
+        assert!(all(0)
+            .await
+            .expect("Searching shouldn't spin")
+            .unwrap()
+            .is_empty());
+        assert_eq!(all(1).await.unwrap().unwrap().len(), 3);
+        assert_eq!(all(2).await.unwrap().unwrap().len(), 3);
     }
 }
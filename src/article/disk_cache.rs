@@ -0,0 +1,172 @@
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use pandoc_ast::Pandoc;
+use rocket::tokio;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    filters_changed_since, register_slug, ArticleMeta, Body, CacheEntry, Dependencies, AST_CACHE,
+};
+use crate::config::CONFIG;
+
+/// A [`CacheEntry`] as written to disk.
+#[derive(Serialize, Deserialize)]
+struct Stored {
+    path: PathBuf,
+    meta: ArticleMeta,
+    body: StoredBody,
+    plain_text: String,
+    rendered_at: SystemTime,
+    #[serde(default)]
+    deps: Dependencies,
+    #[serde(default)]
+    content_hash: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+enum StoredBody {
+    /// A pandoc AST in pandoc's JSON format.
+    Pandoc(String),
+    Html(String),
+}
+
+/// Where the entry for the article at `path` is stored in `dir`.
+fn file_for(dir: &Path, path: &Path) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    dir.join(format!("{:016x}.json", hasher.finish()))
+}
+
+/// Fill the article cache from disk, deleting entries older than their sources.
+pub async fn load() {
+    load_from(&CONFIG.cache_dir).await
+}
+
+async fn load_from(cache_dir: &Path) {
+    let Ok(mut dir) = tokio::fs::read_dir(cache_dir).await else {
+        return;
+    };
+    let mut loaded = 0;
+    while let Ok(Some(file)) = dir.next_entry().await {
+        let file = file.path();
+        let stored = tokio::fs::read(&file)
+            .await
+            .ok()
+            .and_then(|json| serde_json::from_slice::<Stored>(&json).ok());
+        let Some(stored) = stored.filter(|stored| is_fresh(stored, cache_dir, &file)) else {
+            if let Err(e) = tokio::fs::remove_file(&file).await {
+                eprintln!("Couldn't remove stale cache entry {file:?}: {e}");
+            }
+            continue;
+        };
+        let path: Arc<Path> = stored.path.into();
+        let body = match stored.body {
+            StoredBody::Pandoc(json) => Body::Pandoc(Arc::new(Pandoc::from_json(&json))),
+            StoredBody::Html(html) => Body::Html(html.into()),
+        };
+        register_slug(&path, stored.meta.slug.as_deref());
+        crate::related::index(&path, &stored.plain_text);
+        AST_CACHE.insert(
+            path,
+            CacheEntry {
+                meta: Arc::new(stored.meta),
+                body,
+                plain_text: stored.plain_text.into(),
+                rendered_at: stored.rendered_at,
+                deps: Arc::new(stored.deps),
+                content_hash: stored.content_hash,
+                served: Default::default(),
+            },
+        );
+        loaded += 1;
+    }
+    println!("Loaded {loaded} articles from the cache");
+}
+
+/// Whether a stored entry belongs at `file` in `dir` and was rendered since its source and the
+/// pandoc filters last changed.
+fn is_fresh(stored: &Stored, dir: &Path, file: &Path) -> bool {
+    let modified = std::fs::metadata(&stored.path).and_then(|m| m.modified());
+    file_for(dir, &stored.path) == file
+        && modified.is_ok_and(|modified| stored.rendered_at >= modified)
+        && !filters_changed_since(stored.rendered_at)
+}
+
+/// Write an article's cache entry to disk, if the cache is persisted.
+pub async fn store(path: &Path, entry: &CacheEntry) {
+    if CONFIG.persist_cache {
+        store_in(&CONFIG.cache_dir, path, entry).await;
+    }
+}
+
+async fn store_in(dir: &Path, path: &Path, entry: &CacheEntry) {
+    let stored = Stored {
+        path: path.to_path_buf(),
+        meta: (*entry.meta).clone(),
+        body: match &entry.body {
+            Body::Pandoc(ast) => StoredBody::Pandoc(ast.to_json()),
+            Body::Html(html) => StoredBody::Html(html.to_string()),
+        },
+        plain_text: entry.plain_text.to_string(),
+        rendered_at: entry.rendered_at,
+        deps: Dependencies::clone(&entry.deps),
+        content_hash: entry.content_hash,
+    };
+    let result = async {
+        tokio::fs::create_dir_all(dir).await?;
+        tokio::fs::write(file_for(dir, path), serde_json::to_vec(&stored)?).await
+    }
+    .await;
+    if let Err(e) = result {
+        eprintln!("Couldn't persist the cache entry for {path:?}: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, time::Duration};
+
+    use super::*;
+    use crate::testing::Fixture;
+
+    #[rocket::async_test]
+    async fn entries_survive_a_restart_until_their_source_changes() {
+        let fixture = Fixture::new("disk-cache");
+        let dir = std::env::temp_dir().join(format!("wolog-cache-{}", std::process::id()));
+        let path = fixture
+            .article(
+                "post",
+                "---\ntitle: Persisted\nstatus: published\n---\n\nKept.\n",
+            )
+            .await;
+        let entry = AST_CACHE.get(&path).unwrap().clone();
+        store_in(&dir, &path, &entry).await;
+
+        AST_CACHE.remove(&path);
+        load_from(&dir).await;
+        let loaded = AST_CACHE.get(&path).unwrap().clone();
+        assert_eq!(loaded.meta.title, "Persisted");
+        assert_eq!(loaded.rendered_at, entry.rendered_at);
+        assert_eq!(loaded.content_hash, entry.content_hash);
+        assert_eq!(loaded.plain_text, entry.plain_text);
+
+        // An edit after the entry was rendered makes it stale.
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(entry.rendered_at + Duration::from_secs(1))
+            .unwrap();
+        AST_CACHE.remove(&path);
+        load_from(&dir).await;
+        assert!(AST_CACHE.get(&path).is_none());
+        assert!(!file_for(&dir, &path).exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,10 +1,15 @@
 use rocket::http::uri::error::PathError;
 use rocket::http::Status;
-use rocket::response::Responder;
+use rocket::response::{content::RawHtml, Responder};
+use rocket::serde::json::{json, Json};
 use rocket::tokio::task::JoinError;
+use rocket_dyn_templates::tera;
 use std::string::FromUtf8Error;
 
-#[derive(thiserror::Error, Debug)]
+use crate::config::CONFIG;
+
+#[derive(thiserror::Error, strum::IntoStaticStr, Debug)]
+#[strum(serialize_all = "snake_case")]
 pub enum ArticleError {
     #[error("Malformed path")]
     MalformedPath(PathError),
@@ -20,24 +25,149 @@ pub enum ArticleError {
     Utf8Error(#[from] FromUtf8Error),
     #[error("Pandoc failed")]
     PandocFailed(String),
+    #[error("Pandoc took too long")]
+    RenderTimeout,
     #[error("JSON error")]
     JsonError(#[from] serde_json::Error),
+    #[error("Bad front matter")]
+    BadFrontMatter(serde_yml::Error),
+    #[error("Bad TOML front matter")]
+    BadTomlFrontMatter(toml::de::Error),
     #[error("This article isn't ready to be published yet")]
     NotForPublication,
+    #[error("Front-matter key `{0}` looks like a typo of `{1}`")]
+    SuspiciousMeta(String, &'static str),
+    #[error("No PDF engine is available")]
+    PdfUnavailable,
+    #[error("Template failed")]
+    TemplateFailed(rocket_dyn_templates::tera::Error),
+    #[error("No template named {0}")]
+    MissingTemplate(String),
+    #[error("Request body too large")]
+    TooLarge,
+    #[error("This article is password protected")]
+    Locked,
+    #[error("{0:?} isn't a usable tag")]
+    BadTag(String),
+    #[error("The front matter's tags couldn't be rewritten without changing anything else")]
+    TagsNotRewritable,
+    #[error("The metadata can't be written as front matter")]
+    FrontMatterNotWritable,
+    #[error("Embedded searches nest too deeply")]
+    EmbedsTooDeep,
 }
 
-impl<'r, 'o: 'r> Responder<'r, 'o> for ArticleError {
-    fn respond_to(self, request: &'r rocket::Request<'_>) -> rocket::response::Result<'o> {
+impl ArticleError {
+    pub fn status(&self) -> Status {
         match self {
-            ArticleError::MalformedPath(_) => Status::BadRequest.respond_to(request),
+            ArticleError::MalformedPath(_) | ArticleError::BadTag(_) => Status::BadRequest,
             ArticleError::NoArticle
             | ArticleError::NotMarkdown
-            | ArticleError::NotForPublication => Status::NotFound.respond_to(request),
+            | ArticleError::NotForPublication => Status::NotFound,
             ArticleError::IoError(_)
             | ArticleError::JoinError(_)
             | ArticleError::Utf8Error(_)
             | ArticleError::PandocFailed(_)
-            | ArticleError::JsonError(_) => Status::InternalServerError.respond_to(request),
+            | ArticleError::JsonError(_)
+            | ArticleError::BadFrontMatter(_)
+            | ArticleError::BadTomlFrontMatter(_)
+            | ArticleError::SuspiciousMeta(..)
+            | ArticleError::TemplateFailed(_)
+            | ArticleError::MissingTemplate(_)
+            | ArticleError::TagsNotRewritable
+            | ArticleError::FrontMatterNotWritable
+            | ArticleError::EmbedsTooDeep => Status::InternalServerError,
+            ArticleError::PdfUnavailable => Status::NotImplemented,
+            ArticleError::RenderTimeout => Status::ServiceUnavailable,
+            ArticleError::TooLarge => Status::PayloadTooLarge,
+            ArticleError::Locked => Status::Forbidden,
+        }
+    }
+}
+
+/// An error as a JSON body of its message and kind, for API clients.
+#[derive(Debug)]
+pub struct JsonError(pub ArticleError);
+
+impl From<ArticleError> for JsonError {
+    fn from(error: ArticleError) -> Self {
+        JsonError(error)
+    }
+}
+
+impl<'r, 'o: 'r> Responder<'r, 'o> for JsonError {
+    fn respond_to(self, request: &'r rocket::Request<'_>) -> rocket::response::Result<'o> {
+        let kind: &'static str = (&self.0).into();
+        let body = json!({ "error": self.0.to_string(), "kind": kind });
+        (self.0.status(), Json(body)).respond_to(request)
+    }
+}
+
+/// Clients preferring JSON get a [`JsonError`], and others a bare status or, in development, the
+/// details of template errors.
+impl<'r, 'o: 'r> Responder<'r, 'o> for ArticleError {
+    fn respond_to(self, request: &'r rocket::Request<'_>) -> rocket::response::Result<'o> {
+        if request.accept().is_some_and(|a| a.preferred().is_json()) {
+            return JsonError(self).respond_to(request);
         }
+        match self {
+            ArticleError::TemplateFailed(_) | ArticleError::MissingTemplate(_) => {
+                eprintln!("Rendering failed: {self:#?}");
+                if CONFIG.dev {
+                    let detail = tera::escape_html(&format!("{self:#?}"));
+                    (
+                        Status::InternalServerError,
+                        RawHtml(format!("<h1>Rendering failed</h1><pre>{detail}</pre>")),
+                    )
+                        .respond_to(request)
+                } else {
+                    Status::InternalServerError.respond_to(request)
+                }
+            }
+            _ => self.status().respond_to(request),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::{Accept, ContentType, Status};
+
+    use crate::testing::{client, Fixture};
+
+    #[rocket::async_test]
+    async fn missing_articles_are_described_in_json() {
+        let fixture = Fixture::new("json-errors");
+        let client = client().await;
+        let cases = [
+            (
+                format!("/{}", fixture.rel("missing")),
+                "no_article",
+                "No article",
+            ),
+            (
+                format!("/toc/{}", fixture.rel("missing")),
+                "not_markdown",
+                "Not markdown",
+            ),
+        ];
+        for (uri, kind, error) in cases {
+            let response = client.get(&uri).header(Accept::JSON).dispatch().await;
+            assert_eq!(response.status(), Status::NotFound, "{uri}");
+            assert_eq!(response.content_type(), Some(ContentType::JSON), "{uri}");
+            let body: serde_json::Value = response.into_json().await.unwrap();
+            assert_eq!(
+                body,
+                serde_json::json!({ "error": error, "kind": kind }),
+                "{uri}"
+            );
+        }
+
+        let response = client
+            .get(format!("/{}", fixture.rel("missing")))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::NotFound);
+        assert_ne!(response.content_type(), Some(ContentType::JSON));
     }
 }
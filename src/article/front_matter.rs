@@ -0,0 +1,284 @@
+//! Front matter in YAML (`---`) or TOML (`+++`) blocks.
+
+use std::ops::Range;
+
+use serde_yml::Value;
+
+use super::error::ArticleError;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    Yaml,
+    Toml,
+}
+
+/// Separate a markdown source's front matter, if it starts with any, from its body.
+///
+/// YAML blocks end with `---` or `...`, as in pandoc, and TOML blocks with `+++`. A block that's
+/// never closed isn't front matter.
+pub fn split(source: &str) -> (Option<(Format, &str)>, &str) {
+    let mut lines = source.split_inclusive('\n');
+    let Some(first) = lines.next() else {
+        return (None, source);
+    };
+    let (format, ends): (Format, &[&str]) = match first.trim_end() {
+        "---" => (Format::Yaml, &["---", "..."]),
+        "+++" => (Format::Toml, &["+++"]),
+        _ => return (None, source),
+    };
+    let start = first.len();
+    let mut end = start;
+    for line in lines {
+        if ends.contains(&line.trim_end()) {
+            return (
+                Some((format, &source[start..end])),
+                &source[end + line.len()..],
+            );
+        }
+        end += line.len();
+    }
+    (None, source)
+}
+
+/// Parse front matter into the YAML it'd be if it were written in YAML.
+pub fn parse(format: Format, text: &str) -> Result<Value, ArticleError> {
+    if text.trim().is_empty() {
+        return Ok(Value::Mapping(Default::default()));
+    }
+    match format {
+        Format::Yaml => serde_yml::from_str(text).map_err(ArticleError::BadFrontMatter),
+        Format::Toml => toml::from_str::<toml::Table>(text)
+            .map(|table| toml_to_yaml(toml::Value::Table(table)))
+            .map_err(ArticleError::BadTomlFrontMatter),
+    }
+}
+
+fn toml_to_yaml(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::Number(i.into()),
+        toml::Value::Float(f) => Value::Number(f.into()),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        // TOML writes dates as RFC 3339, like the YAML front matter does.
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(items) => Value::Sequence(items.into_iter().map(toml_to_yaml).collect()),
+        toml::Value::Table(table) => Value::Mapping(
+            table
+                .into_iter()
+                .map(|(key, value)| (Value::String(key), toml_to_yaml(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// The source to give pandoc, which only understands YAML front matter, with any TOML front
+/// matter rewritten as YAML.
+///
+/// Returns `None` if the source can be read as it is.
+pub fn for_pandoc(source: &str) -> Result<Option<String>, ArticleError> {
+    let (Some((Format::Toml, text)), body) = split(source) else {
+        return Ok(None);
+    };
+    let yaml =
+        serde_yml::to_string(&parse(Format::Toml, text)?).map_err(ArticleError::BadFrontMatter)?;
+    Ok(Some(format!("---\n{yaml}---\n{body}")))
+}
+
+/// Rename a tag in a markdown source's front matter, returning the new source, or `None` if the
+/// source doesn't have the tag.
+///
+/// Only the `tags` entry is rewritten, as a one-line list, so comments and formatting elsewhere
+/// survive. The result is parsed again to check nothing else changed.
+pub fn rename_tag(source: &str, from: &str, to: &str) -> Result<Option<String>, ArticleError> {
+    let (Some((format, text)), _) = split(source) else {
+        return Ok(None);
+    };
+    let mut before = parse(format, text)?;
+    let Some(Value::Sequence(tags)) = before.get("tags") else {
+        return Ok(None);
+    };
+    if !tags.iter().any(|tag| tag.as_str() == Some(from)) {
+        return Ok(None);
+    }
+    let mut renamed: Vec<&str> = vec![];
+    for tag in tags {
+        let tag = tag.as_str().ok_or(ArticleError::TagsNotRewritable)?;
+        let tag = if tag == from { to } else { tag };
+        if !renamed.contains(&tag) {
+            renamed.push(tag);
+        }
+    }
+
+    let entry = tags_entry(format, text).ok_or(ArticleError::TagsNotRewritable)?;
+    let newline = if text[entry.clone()].ends_with("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let list = match format {
+        Format::Yaml => format!(
+            "tags: [{}]",
+            renamed
+                .iter()
+                .map(|tag| yaml_scalar(tag))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Format::Toml => format!(
+            "tags = [{}]",
+            renamed
+                .iter()
+                .map(|tag| quoted(tag))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    // `text` is a slice of `source`, so this is where it starts.
+    let offset = text.as_ptr() as usize - source.as_ptr() as usize;
+    let rewritten = format!(
+        "{}{list}{newline}{}",
+        &source[..offset + entry.start],
+        &source[offset + entry.end..]
+    );
+
+    let (Some((_, new_text)), _) = split(&rewritten) else {
+        return Err(ArticleError::TagsNotRewritable);
+    };
+    let mut after = parse(format, new_text)?;
+    let expected: Vec<Value> = renamed.iter().map(|tag| Value::from(*tag)).collect();
+    if after.get("tags") != Some(&Value::Sequence(expected)) {
+        return Err(ArticleError::TagsNotRewritable);
+    }
+    before.as_mapping_mut().map(|m| m.remove("tags"));
+    after.as_mapping_mut().map(|m| m.remove("tags"));
+    if before != after {
+        return Err(ArticleError::TagsNotRewritable);
+    }
+    Ok(Some(rewritten))
+}
+
+/// Where the top-level `tags` entry is in front matter, including any lines its value continues
+/// onto.
+fn tags_entry(format: Format, text: &str) -> Option<Range<usize>> {
+    let mut offset = 0;
+    let mut entry: Option<Range<usize>> = None;
+    // How many of a TOML array's brackets are still open.
+    let mut depth = 0;
+    for line in text.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        if let Some(entry) = &mut entry {
+            let continues = match format {
+                Format::Yaml => line.starts_with([' ', '\t', '-']) || line.trim().is_empty(),
+                Format::Toml => depth > 0,
+            };
+            if !continues {
+                break;
+            }
+            depth += brackets(line);
+            // Blank lines after the entry belong to whatever's next.
+            if !line.trim().is_empty() {
+                entry.end = offset;
+            }
+            continue;
+        }
+        let separator = match format {
+            Format::Yaml => ':',
+            Format::Toml => '=',
+        };
+        let Some(value) = line
+            .strip_prefix("tags")
+            .and_then(|rest| rest.trim_start().strip_prefix(separator))
+        else {
+            continue;
+        };
+        if format == Format::Toml {
+            depth = brackets(value);
+        }
+        entry = Some(start..offset);
+    }
+    entry
+}
+
+/// How many more `[` than `]` there are in a line of TOML, outside strings.
+fn brackets(line: &str) -> i32 {
+    let mut depth = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    for c in line.chars() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => break,
+            (None, '[') => depth += 1,
+            (None, ']') => depth -= 1,
+            _ => {}
+        }
+        escaped = false;
+    }
+    depth
+}
+
+/// A tag as YAML, left bare unless that would read as something other than the same string.
+fn yaml_scalar(tag: &str) -> String {
+    let bare = !tag.contains([',', '[', ']', '{', '}', '#', '"', '\''])
+        && serde_yml::from_str::<Value>(tag).is_ok_and(|value| value.as_str() == Some(tag));
+    if bare {
+        tag.to_string()
+    } else {
+        quoted(tag)
+    }
+}
+
+/// A tag as a double-quoted string, which YAML and TOML read the same way JSON writes it.
+fn quoted(tag: &str) -> String {
+    serde_json::Value::from(tag).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::{
+        article::{get_article, ArticleStatus},
+        testing::Fixture,
+    };
+
+    #[test]
+    fn toml_blocks_are_split_from_the_body() {
+        let (front, body) = split("+++\ntitle = \"T\"\n+++\nBody\n");
+        assert_eq!(front, Some((Format::Toml, "title = \"T\"\n")));
+        assert_eq!(body, "Body\n");
+        // Unclosed blocks are just text.
+        assert_eq!(
+            split("+++\ntitle = \"T\"\n"),
+            (None, "+++\ntitle = \"T\"\n")
+        );
+    }
+
+    #[rocket::async_test]
+    async fn toml_front_matter_fills_in_the_metadata() {
+        let fixture = Fixture::new("toml-front-matter");
+        let path = fixture
+            .article(
+                "post",
+                "+++\ntitle = \"Written in TOML\"\nstatus = \"published\"\ncreated = 2024-03-02\ntags = [\"toml\", \"config\"]\nmood = \"calm\"\n+++\n\nThe body.\n",
+            )
+            .await;
+        let article = get_article(&path).await.unwrap();
+        let meta = &article.meta;
+        assert_eq!(meta.title, "Written in TOML");
+        assert_eq!(meta.status(), ArticleStatus::Published);
+        assert_eq!(meta.created, NaiveDate::from_ymd_opt(2024, 3, 2).unwrap());
+        assert_eq!(meta.tags, ["toml", "config"]);
+        assert_eq!(meta.extra["mood"].as_str(), Some("calm"));
+        assert!(article.content.contains("The body."));
+        assert!(!article.content.contains("+++"));
+        assert!(!article.content.contains("Written in TOML"));
+    }
+}
@@ -0,0 +1,70 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    sync::LazyLock,
+};
+
+use chrono::{DateTime, NaiveDate};
+use dashmap::DashMap;
+use rocket::tokio::task::spawn_blocking;
+
+use crate::timezone;
+
+#[derive(Clone, Copy, Debug)]
+pub struct GitDates {
+    pub created: NaiveDate,
+    pub updated: NaiveDate,
+}
+
+/// Git-derived dates, keyed on path and tagged with the file's last commit.
+static GIT_DATES: LazyLock<DashMap<PathBuf, (String, GitDates)>> = LazyLock::new(DashMap::new);
+
+/// Run git in the file's directory, returning its output if it succeeded.
+fn git(path: &Path, args: &[&str]) -> Option<String> {
+    let dir = path.parent().filter(|p| !p.as_os_str().is_empty());
+    let output = Command::new("git")
+        .current_dir(dir.unwrap_or(Path::new(".")))
+        .args(args)
+        .arg("--")
+        .arg(path.file_name()?)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
+
+/// The dates of the first and last commits touching a file, following renames.
+///
+/// Returns `None` if git isn't available or the file isn't tracked.
+pub async fn dates(path: &Path) -> Option<GitDates> {
+    let path = path.to_path_buf();
+    spawn_blocking(move || {
+        let last_commit = git(&path, &["log", "-1", "--format=%H"])?;
+        let last_commit = last_commit.trim();
+        if last_commit.is_empty() {
+            return None;
+        }
+        if let Some(cached) = GIT_DATES.get(&path) {
+            if cached.0 == last_commit {
+                return Some(cached.1);
+            }
+        }
+
+        let log = git(&path, &["log", "--follow", "--format=%aI"])?;
+        let mut dates = log.lines().filter_map(|line| {
+            DateTime::parse_from_rfc3339(line.trim())
+                .ok()
+                .map(|d| timezone::in_zone(d).date_naive())
+        });
+        let updated = dates.next()?;
+        let created = dates.next_back().unwrap_or(updated);
+        let dates = GitDates { created, updated };
+        GIT_DATES.insert(path, (last_commit.to_string(), dates));
+        Some(dates)
+    })
+    .await
+    .ok()
+    .flatten()
+}
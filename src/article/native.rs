@@ -0,0 +1,437 @@
+use std::{collections::HashSet, path::Path, sync::Arc};
+
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+
+use super::{
+    error::ArticleError,
+    front_matter::{self, Format},
+    ArticleMeta,
+};
+use crate::{
+    config::CONFIG,
+    filters::{
+        code_header, embed_results, external_attributes, highlight, Highlighter, EXTERNAL_ICON,
+    },
+    url::is_external,
+};
+use rocket_dyn_templates::tera::escape_html;
+
+/// An article rendered without pandoc.
+pub struct Rendered {
+    pub meta: ArticleMeta,
+    pub html: String,
+    pub plain_text: String,
+}
+
+/// Classes of fenced code blocks that are replaced with embedded article lists.
+const EMBEDS: &[&str] = &["search", "related"];
+
+/// Render a markdown article with pulldown-cmark, reading its YAML or TOML front matter
+/// directly.
+///
+/// This covers CommonMark plus tables, footnotes, strikethrough and task lists, and supports
+/// `search` and `related` blocks, but none of pandoc's other extensions. Articles that render
+/// math also get `$` and `$$` math, which is turned into MathML by pandoc.
+pub async fn render(path: &Arc<Path>, source: &str) -> Result<Rendered, ArticleError> {
+    let meta = ArticleMeta::from_front_matter(front_matter::split(source).0)?;
+    let events = parse(source, meta.renders_math());
+
+    let plain_text = plain_text(&events);
+    // Index before embedding results, so `related` blocks can compare against this article.
+    crate::related::index(path, &plain_text);
+
+    let html = to_html(events, Some(path), meta.link_favicons).await;
+    Ok(Rendered {
+        meta,
+        html,
+        plain_text,
+    })
+}
+
+/// Render markdown that doesn't belong to an article, leaving `search` and `related` blocks as
+/// code.
+pub async fn preview(source: &str, math: bool) -> String {
+    to_html(parse(source, math), None, false).await
+}
+
+fn parse(source: &str, math: bool) -> Vec<Event<'_>> {
+    let mut options = Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_SMART_PUNCTUATION
+        | Options::ENABLE_HEADING_ATTRIBUTES
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS
+        | Options::ENABLE_PLUSES_DELIMITED_METADATA_BLOCKS;
+    options.set(Options::ENABLE_MATH, math);
+    Parser::new_ext(source, options).collect()
+}
+
+/// Turn parsed markdown into HTML, leaving out its front matter and embedding results for the
+/// article at `path` if there is one. With `favicons`, external links get their site's icon.
+async fn to_html(events: Vec<Event<'_>>, path: Option<&Arc<Path>>, favicons: bool) -> String {
+    let mut ids = HashSet::new();
+    // Whether each link being written is external, which pulldown-cmark can't add attributes to.
+    let mut links = vec![];
+    let mut out = Vec::with_capacity(events.len());
+    let mut events = events.into_iter();
+    while let Some(event) = events.next() {
+        match event {
+            Event::Start(Tag::MetadataBlock(_)) => {
+                for event in events.by_ref() {
+                    if let Event::End(TagEnd::MetadataBlock(_)) = event {
+                        break;
+                    }
+                }
+            }
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(kind)))
+                if EMBEDS.contains(&kind.as_ref()) =>
+            {
+                let mut contents = String::new();
+                for event in events.by_ref() {
+                    match event {
+                        Event::Text(text) => contents.push_str(&text),
+                        Event::End(TagEnd::CodeBlock) => break,
+                        _ => {}
+                    }
+                }
+                let embedded = match path {
+                    Some(path) => embed_results(path, &kind, &contents).await,
+                    None => None,
+                };
+                match embedded {
+                    Some(html) => out.push(Event::Html(html.into())),
+                    None => out.extend([
+                        Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(kind))),
+                        Event::Text(contents.into()),
+                        Event::End(TagEnd::CodeBlock),
+                    ]),
+                }
+            }
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(lang)))
+                if CONFIG.highlighter == Highlighter::Syntect && !lang.is_empty() =>
+            {
+                let mut code = String::new();
+                for event in events.by_ref() {
+                    match event {
+                        Event::Text(text) => code.push_str(&text),
+                        Event::End(TagEnd::CodeBlock) => break,
+                        _ => {}
+                    }
+                }
+                let lang = lang.split_whitespace().next().unwrap_or_default();
+                match highlight(lang, &code) {
+                    Some(html) => out.push(Event::Html(html.into())),
+                    None => out.extend([
+                        open_code_block(lang),
+                        Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(
+                            lang.to_string().into(),
+                        ))),
+                        Event::Text(code.into()),
+                        Event::End(TagEnd::CodeBlock),
+                        Event::Html("</div>".into()),
+                    ]),
+                }
+            }
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let lang = match &kind {
+                    CodeBlockKind::Fenced(lang) => {
+                        lang.split_whitespace().next().unwrap_or_default()
+                    }
+                    CodeBlockKind::Indented => "",
+                };
+                out.push(open_code_block(lang));
+                out.push(Event::Start(Tag::CodeBlock(kind)));
+                for event in events.by_ref() {
+                    let end = matches!(event, Event::End(TagEnd::CodeBlock));
+                    out.push(event);
+                    if end {
+                        break;
+                    }
+                }
+                out.push(Event::Html("</div>".into()));
+            }
+            Event::Start(Tag::Image { ref dest_url, .. }) => {
+                let attributes = crate::images::attributes(dest_url, &[]);
+                let mut image = vec![event];
+                let mut depth = 1;
+                for event in events.by_ref() {
+                    match event {
+                        Event::Start(Tag::Image { .. }) => depth += 1,
+                        Event::End(TagEnd::Image) => depth -= 1,
+                        _ => {}
+                    }
+                    image.push(event);
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                let mut html = String::new();
+                pulldown_cmark::html::push_html(&mut html, image.into_iter());
+                // pulldown-cmark has no way to add attributes, so splice them in before `/>`.
+                let end = html.rfind(" />").unwrap_or(html.len());
+                let attributes: String = attributes
+                    .iter()
+                    .map(|(key, value)| format!(" {key}=\"{value}\""))
+                    .collect();
+                html.insert_str(end, &attributes);
+                out.push(Event::InlineHtml(html.into()));
+            }
+            Event::Start(Tag::Heading {
+                level,
+                id,
+                classes,
+                attrs,
+            }) => {
+                let mut inner = vec![];
+                for event in events.by_ref() {
+                    if let Event::End(TagEnd::Heading(_)) = event {
+                        break;
+                    }
+                    inner.push(event);
+                }
+                let id = id.unwrap_or_else(|| heading_id(&inner, &mut ids).into());
+                out.push(Event::Start(Tag::Heading {
+                    level,
+                    id: Some(id),
+                    classes,
+                    attrs,
+                }));
+                out.extend(inner);
+                out.push(Event::End(TagEnd::Heading(level)));
+            }
+            Event::Start(Tag::Link { ref dest_url, .. }) if is_external(dest_url) => {
+                let mut html = String::new();
+                pulldown_cmark::html::push_html(&mut html, std::iter::once(event.clone()));
+                // As with images, splice the attributes in before the tag's `>`.
+                let end = html.rfind('>').unwrap_or(html.len());
+                let attributes: String = external_attributes()
+                    .iter()
+                    .map(|(key, value)| format!(" {key}=\"{value}\""))
+                    .collect();
+                html.insert_str(end, &format!(" class=\"external\"{attributes}"));
+                if favicons {
+                    if let Some(icon) = crate::favicons::favicon(dest_url).await {
+                        html.push_str(&crate::favicons::img(&icon));
+                    }
+                }
+                links.push(true);
+                out.push(Event::InlineHtml(html.into()));
+            }
+            Event::Start(Tag::Link { .. }) => {
+                links.push(false);
+                out.push(event);
+            }
+            Event::End(TagEnd::Link) if links.pop() == Some(true) => {
+                let icon = if CONFIG.external_links.icon {
+                    EXTERNAL_ICON
+                } else {
+                    ""
+                };
+                out.push(Event::InlineHtml(format!("{icon}</a>").into()));
+            }
+            Event::InlineMath(ref tex) | Event::DisplayMath(ref tex) => {
+                let display = matches!(event, Event::DisplayMath(_));
+                match mathml(tex, display).await {
+                    Some(html) => out.push(Event::InlineHtml(html.into())),
+                    None => out.push(event),
+                }
+            }
+            event => out.push(event),
+        }
+    }
+
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, out.into_iter());
+    html
+}
+
+/// TeX math as MathML, rendered by pandoc, or `None` if pandoc couldn't.
+///
+/// The MathML becomes part of the article's rendered HTML, so it's only worked out again when
+/// the article's source changes.
+async fn mathml(tex: &str, display: bool) -> Option<String> {
+    let latex = if display {
+        format!("\\[{tex}\\]")
+    } else {
+        format!("\\({tex}\\)")
+    };
+    let html = match super::run_pandoc(
+        ["-f", "latex", "-t", "html", "--mathml"],
+        latex.into_bytes(),
+    )
+    .await
+    {
+        Ok(html) => String::from_utf8(html).ok()?,
+        Err(e) => {
+            eprintln!("Couldn't render math {tex:?} to MathML: {e}");
+            return None;
+        }
+    };
+    // Pandoc puts the equation in a paragraph of its own, which would break the one it's in.
+    let html = html.trim();
+    let html = html.strip_prefix("<p>").unwrap_or(html);
+    let html = html.strip_suffix("</p>").unwrap_or(html);
+    Some(html.to_string())
+}
+
+/// The start of the wrapper [`crate::filters`] puts around code blocks, closed by a `</div>`.
+fn open_code_block(lang: &str) -> Event<'static> {
+    Event::Html(
+        format!(
+            "<div class=\"code-block\" data-lang=\"{}\">{}",
+            escape_html(lang),
+            code_header(lang)
+        )
+        .into(),
+    )
+}
+
+/// Make a pandoc-style identifier for a heading, so links and tables of contents written against
+/// pandoc's output still work.
+fn heading_id(inner: &[Event], taken: &mut HashSet<String>) -> String {
+    let text: String = inner
+        .iter()
+        .filter_map(|e| match e {
+            Event::Text(t) | Event::Code(t) => Some(t.as_ref()),
+            Event::SoftBreak => Some(" "),
+            _ => None,
+        })
+        .collect();
+    let base: String = text
+        .trim_start_matches(|c: char| !c.is_alphabetic())
+        .chars()
+        .filter(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ' '))
+        .map(|c| {
+            if c == ' ' {
+                '-'
+            } else {
+                c.to_ascii_lowercase()
+            }
+        })
+        .collect();
+    let base = if base.is_empty() {
+        "section".to_string()
+    } else {
+        base
+    };
+    let mut id = base.clone();
+    let mut n = 1;
+    while !taken.insert(id.clone()) {
+        id = format!("{base}-{n}");
+        n += 1;
+    }
+    id
+}
+
+/// The readable text of an article, leaving out front matter, embeds, raw HTML and footnotes, to
+/// match [`crate::filters::plain_text`].
+fn plain_text(events: &[Event]) -> String {
+    let mut text = String::new();
+    let mut skipping = 0;
+    let mut in_embed = false;
+    for event in events {
+        match event {
+            Event::Start(Tag::MetadataBlock(_) | Tag::FootnoteDefinition(_)) => skipping += 1,
+            Event::End(TagEnd::MetadataBlock(_) | TagEnd::FootnoteDefinition) => skipping -= 1,
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(kind)))
+                if EMBEDS.contains(&kind.as_ref()) =>
+            {
+                in_embed = true
+            }
+            Event::End(TagEnd::CodeBlock) if in_embed => in_embed = false,
+            _ if skipping > 0 || in_embed => {}
+            Event::Text(t) | Event::Code(t) | Event::InlineMath(t) | Event::DisplayMath(t) => {
+                text.push_str(t)
+            }
+            Event::SoftBreak => text.push(' '),
+            Event::HardBreak => text.push('\n'),
+            Event::End(
+                TagEnd::Paragraph
+                | TagEnd::Heading(_)
+                | TagEnd::CodeBlock
+                | TagEnd::Item
+                | TagEnd::TableCell,
+            ) if !text.is_empty() && !text.ends_with("\n\n") => text.push_str("\n\n"),
+            _ => {}
+        }
+    }
+    text.trim_end().to_string()
+}
+
+impl ArticleMeta {
+    /// Parse front matter, as pandoc would have read it into the article's metadata.
+    pub(super) fn from_front_matter(
+        front_matter: Option<(Format, &str)>,
+    ) -> Result<Self, ArticleError> {
+        let value = match front_matter {
+            Some((format, text)) => front_matter::parse(format, text)?,
+            None => serde_yml::Value::Mapping(Default::default()),
+        };
+        let meta: ArticleMeta = serde_json::from_value(serde_json::to_value(value)?)?;
+        meta.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{article::Renderer, testing};
+
+    const SOURCE: &str =
+        "# A heading\n\nSome *emphasis*, **strength** and `code`.\n\n- one\n- two\n\n> Quoted\n";
+
+    const MARKUP: &[&str] = &[
+        r#"<h1 id="a-heading">A heading</h1>"#,
+        "<em>emphasis</em>",
+        "<strong>strength</strong>",
+        "<code>code</code>",
+        "<li>one</li>",
+        "<blockquote>",
+    ];
+
+    #[test]
+    fn basic_markup_matches_pandoc() {
+        let render = |renderer| {
+            let config = testing::config(|c| c.renderer = renderer);
+            testing::run_with(config, crate::article::preview(SOURCE)).unwrap()
+        };
+        let native = render(Renderer::Native);
+        for markup in MARKUP {
+            assert!(native.contains(markup), "{markup} in {native}");
+        }
+        // Pandoc isn't always installed where tests run.
+        if !testing::pandoc_installed() {
+            return;
+        }
+        let pandoc = render(Renderer::Pandoc);
+        for markup in MARKUP {
+            assert!(pandoc.contains(markup), "{markup} in {pandoc}");
+        }
+    }
+
+    #[rocket::async_test]
+    async fn equations_become_mathml_inline() {
+        // Articles that don't render math keep their dollar signs.
+        let plain = preview("It costs $5 or $6.", false).await;
+        assert_eq!(plain, "<p>It costs $5 or $6.</p>\n");
+
+        let source = "Squares like $x^2$ grow.\n\n$$\\frac{1}{2}$$\n";
+        let html = preview(source, true).await;
+        // Pandoc isn't always installed where tests run, and without it the TeX is left for a
+        // script to typeset.
+        if mathml("x", false).await.is_none() {
+            assert!(
+                html.contains(r#"<span class="math math-inline">x^2</span>"#),
+                "{html}"
+            );
+            return;
+        }
+        assert!(html.contains("<math"), "{html}");
+        assert!(html.contains("<msup>"), "{html}");
+        assert!(html.contains(r#"display="block""#), "{html}");
+        assert!(!html.contains("$"), "{html}");
+        // The inline equation stays in its sentence.
+        assert!(html.starts_with("<p>Squares like <math"), "{html}");
+    }
+}
@@ -0,0 +1,205 @@
+//! Changing articles' sources on disk.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use rocket::tokio;
+use serde_yml::{Mapping, Value};
+
+use super::{
+    error::ArticleError,
+    front_matter::{self, Format},
+    refresh, ArticleMeta,
+};
+
+/// Metadata worked out while rendering rather than written in front matter, which is never
+/// written back.
+const DERIVED_FIELDS: &[&str] = &["toc", "footnotes", "excerpt"];
+
+/// Read an article's source as the metadata its front matter sets and its body.
+///
+/// Unlike the metadata of a rendered article, nothing is filled in from git or the file's dates,
+/// so writing it back with [`write_article`] changes nothing.
+pub async fn read_article(path: &Path) -> Result<(ArticleMeta, String), ArticleError> {
+    let source = tokio::fs::read_to_string(path).await?;
+    let (front_matter, body) = front_matter::split(&source);
+    Ok((
+        ArticleMeta::from_front_matter(front_matter)?,
+        body.to_string(),
+    ))
+}
+
+/// Write an article with the given metadata and body, creating it if it doesn't exist.
+///
+/// The front matter keeps the file's format, YAML unless it was TOML, and the order of its keys.
+/// Fields left at their defaults are only written if the file already set them, and unknown keys
+/// in `meta.extra` are kept as they are. Comments in the old front matter are lost.
+pub async fn write_article(
+    path: &Arc<Path>,
+    meta: &ArticleMeta,
+    body: &str,
+) -> Result<(), ArticleError> {
+    let previous = match tokio::fs::read_to_string(path).await {
+        Ok(source) => Some(source),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    let (format, before) = match previous.as_deref().map(front_matter::split) {
+        Some((Some((format, text)), _)) => (format, front_matter::parse(format, text)?),
+        _ => (Format::Yaml, Value::Mapping(Mapping::new())),
+    };
+    let fields = front_matter_fields(meta, before.as_mapping())?;
+    let source = match format {
+        Format::Yaml => {
+            let yaml = if fields.is_empty() {
+                String::new()
+            } else {
+                serde_yml::to_string(&fields).map_err(ArticleError::BadFrontMatter)?
+            };
+            format!("---\n{yaml}---\n{body}")
+        }
+        Format::Toml => {
+            let toml =
+                toml::to_string(&fields).map_err(|_| ArticleError::FrontMatterNotWritable)?;
+            format!("+++\n{toml}+++\n{body}")
+        }
+    };
+    write_source(path, &source).await
+}
+
+/// The front matter that sets `meta`, given what the file's front matter was before.
+fn front_matter_fields(
+    meta: &ArticleMeta,
+    before: Option<&Mapping>,
+) -> Result<Mapping, ArticleError> {
+    let mut meta = meta.clone();
+    if !meta.extra.is_mapping() {
+        meta.extra = Value::Mapping(Mapping::new());
+    }
+    let Value::Mapping(mut fields) =
+        serde_yml::to_value(&meta).map_err(ArticleError::BadFrontMatter)?
+    else {
+        return Err(ArticleError::FrontMatterNotWritable);
+    };
+    // The flags a status implies count as defaults too, so setting one doesn't spell them out.
+    let mut defaults = ArticleMeta::from_front_matter(None)?;
+    defaults.status = meta.status;
+    let defaults = serde_yml::to_value(defaults.finish()?).map_err(ArticleError::BadFrontMatter)?;
+    let extra = meta.extra.as_mapping().cloned().unwrap_or_default();
+    let was_set = |key: &Value| before.is_some_and(|before| before.contains_key(key));
+    fields.retain(|key, value| {
+        if extra.contains_key(key) {
+            return true;
+        }
+        if key
+            .as_str()
+            .is_some_and(|key| DERIVED_FIELDS.contains(&key))
+            || value.is_null()
+        {
+            return false;
+        }
+        let keep = was_set(key) || defaults.get(key) != Some(&*value);
+        drop_nulls(value);
+        keep
+    });
+
+    // Keys the file already had stay where they were, with new ones after them.
+    let mut ordered = Mapping::new();
+    for key in before.into_iter().flat_map(Mapping::keys) {
+        if let Some(value) = fields.remove(key) {
+            ordered.insert(key.clone(), value);
+        }
+    }
+    ordered.extend(fields);
+    Ok(ordered)
+}
+
+/// Leave out unset optional fields inside a known field, like an author's email.
+fn drop_nulls(value: &mut Value) {
+    match value {
+        Value::Mapping(map) => {
+            map.retain(|_, value| !value.is_null());
+            map.values_mut().for_each(drop_nulls);
+        }
+        Value::Sequence(items) => items.iter_mut().for_each(drop_nulls),
+        _ => {}
+    }
+}
+
+/// Replace an article's whole source, then re-render it so searches and the pages listing it
+/// see the change straight away.
+///
+/// The source is written to a temporary file beside it and renamed into place, so nothing ever
+/// reads half of it. Every change wolog makes to articles goes through here.
+pub async fn write_source(path: &Arc<Path>, source: &str) -> Result<(), ArticleError> {
+    let mut partial = path.to_path_buf().into_os_string();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    tokio::fs::write(&partial, source).await?;
+    if let Err(e) = tokio::fs::rename(&partial, path).await {
+        let _ = tokio::fs::remove_file(&partial).await;
+        return Err(e.into());
+    }
+    refresh(path).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{article::get_article, testing::Fixture};
+
+    /// A body with the kinds of details a careless rewrite would lose.
+    const BODY: &str =
+        "\nFirst line with trailing spaces  \n\n\tTabbed code\n\n---\n\nNo newline at the end";
+
+    #[rocket::async_test]
+    async fn yaml_round_trips_keep_the_body_and_other_fields() {
+        let fixture = Fixture::new("write-yaml");
+        let source =
+            format!("---\nstatus: published\ntitle: Before\nmood: calm\ntags:\n- one\n---\n{BODY}");
+        let path = fixture.write("post", &source);
+
+        // Reading and writing back unchanged changes nothing.
+        let (meta, body) = read_article(&path).await.unwrap();
+        assert_eq!(body, BODY);
+        write_article(&path, &meta, &body).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), source);
+
+        let (mut meta, body) = read_article(&path).await.unwrap();
+        meta.title = "After".to_string();
+        meta.tags.push("two".to_string());
+        write_article(&path, &meta, &body).await.unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            written,
+            format!(
+                "---\nstatus: published\ntitle: After\nmood: calm\ntags:\n- one\n- two\n---\n{BODY}"
+            )
+        );
+        assert!(!fixture.dir().join("post.md.partial").exists());
+        // It's re-rendered straight away.
+        assert_eq!(get_article(&path).await.unwrap().meta.title, "After");
+    }
+
+    #[rocket::async_test]
+    async fn toml_round_trips_stay_toml() {
+        let fixture = Fixture::new("write-toml");
+        let path = fixture.write(
+            "post",
+            &format!("+++\ntitle = \"Before\"\nstatus = \"published\"\n+++\n{BODY}"),
+        );
+        let (mut meta, body) = read_article(&path).await.unwrap();
+        meta.title = "After".to_string();
+        write_article(&path, &meta, &body).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            format!("+++\ntitle = \"After\"\nstatus = \"published\"\n+++\n{BODY}")
+        );
+    }
+}
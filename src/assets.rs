@@ -0,0 +1,85 @@
+use std::{
+    hash::{DefaultHasher, Hasher},
+    path::{Path, PathBuf},
+    sync::LazyLock,
+    time::SystemTime,
+};
+
+use dashmap::DashMap;
+
+/// Content hashes of static files, keyed on path and invalidated when the file's mtime changes.
+static HASHES: LazyLock<DashMap<PathBuf, (SystemTime, String)>> = LazyLock::new(DashMap::new);
+
+/// Hash the contents of an embedded static file.
+#[cfg(feature = "embed-assets")]
+pub fn fingerprint(path: &Path) -> Option<String> {
+    let rel = path.strip_prefix("static").ok()?;
+    if let Some(cached) = HASHES.get(path) {
+        return Some(cached.1.clone());
+    }
+    let contents = crate::embedded::STATIC.get_file(rel)?.contents();
+    let mut hasher = DefaultHasher::new();
+    hasher.write(contents);
+    let hash = format!("{:016x}", hasher.finish());
+    HASHES.insert(path.to_path_buf(), (SystemTime::UNIX_EPOCH, hash.clone()));
+    Some(hash)
+}
+
+/// Hash the contents of a file, reusing the previous hash if it hasn't been modified.
+#[cfg(not(feature = "embed-assets"))]
+pub fn fingerprint(path: &Path) -> Option<String> {
+    let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    if let Some(cached) = HASHES.get(path) {
+        if cached.0 == modified {
+            return Some(cached.1.clone());
+        }
+    }
+    let contents = std::fs::read(path).ok()?;
+    let mut hasher = DefaultHasher::new();
+    hasher.write(&contents);
+    let hash = format!("{:016x}", hasher.finish());
+    HASHES.insert(path.to_path_buf(), (modified, hash.clone()));
+    Some(hash)
+}
+
+/// The URL of a file under `./static`, with a cache-busting content hash.
+pub fn asset_url(rel: &str) -> String {
+    let rel = rel.trim_start_matches('/');
+    match fingerprint(&Path::new("static").join(rel)) {
+        Some(hash) => crate::url::site_path(&format!("/static/{rel}?v={hash}")),
+        None => crate::url::site_path(&format!("/static/{rel}")),
+    }
+}
+
+#[cfg(all(test, not(feature = "embed-assets")))]
+mod tests {
+    use std::{fs::File, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn hashes_follow_the_file_contents() {
+        let path = std::env::temp_dir().join(format!("wolog-asset-{}.css", std::process::id()));
+        let write = |contents: &str, modified: SystemTime| {
+            std::fs::write(&path, contents).unwrap();
+            File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        };
+        let then = SystemTime::now() - Duration::from_secs(60);
+
+        write("body { color: red }", then);
+        let first = fingerprint(&path).unwrap();
+        assert_eq!(fingerprint(&path).unwrap(), first);
+
+        write("body { color: blue }", then + Duration::from_secs(1));
+        let changed = fingerprint(&path).unwrap();
+        assert_ne!(changed, first);
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(fingerprint(&path), None);
+    }
+}
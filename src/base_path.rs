@@ -0,0 +1,84 @@
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::uri::Origin,
+    Data, Request,
+};
+
+use crate::url;
+
+/// Strips the configured base path from incoming requests, so routes can be written as if the
+/// site were served from the root of its domain.
+///
+/// Requests outside the base path are routed as they are; a reverse proxy serving the site under
+/// a subpath won't forward them anyway.
+pub struct StripBasePath;
+
+#[rocket::async_trait]
+impl Fairing for StripBasePath {
+    fn info(&self) -> Info {
+        Info {
+            name: "Base path",
+            kind: Kind::Request,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        strip(request, url::base_path());
+    }
+}
+
+/// Route `request` as if it were made without the leading `base`, if it's under it.
+fn strip(request: &mut Request<'_>, base: &str) {
+    if base.is_empty() {
+        return;
+    }
+    let path = request.uri().path().as_str();
+    let Some(rest) = path
+        .strip_prefix(base)
+        .filter(|rest| rest.is_empty() || rest.starts_with('/'))
+    else {
+        return;
+    };
+    let rest = if rest.is_empty() { "/" } else { rest };
+    let uri = match request.uri().query() {
+        Some(query) => format!("{rest}?{query}"),
+        None => rest.to_string(),
+    };
+    match Origin::parse_owned(uri) {
+        Ok(uri) => request.set_uri(uri),
+        Err(e) => eprintln!("Couldn't strip the base path from {path:?}: {e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::{fairing::AdHoc, http::Status, local::asynchronous::Client};
+
+    use super::*;
+    use crate::testing::Fixture;
+
+    #[rocket::async_test]
+    async fn routes_are_found_under_the_base_path() {
+        let fixture = Fixture::new("base-path");
+        fixture
+            .article("post", "---\ntitle: Based\nstatus: published\n---\n\nHi.\n")
+            .await;
+        let rocket = crate::rocket().attach(AdHoc::on_request("Test base path", |request, _| {
+            Box::pin(async move { strip(request, "/blog") })
+        }));
+        let client = Client::tracked(rocket).await.unwrap();
+
+        let article = format!("/blog/{}", fixture.rel("post"));
+        let response = client.get(&article).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        assert!(response.into_string().await.unwrap().contains("Based"));
+
+        for uri in ["/blog", "/blog/feeds?x=1"] {
+            let response = client.get(uri).dispatch().await;
+            assert_eq!(response.status(), Status::Ok, "{uri}");
+        }
+        // Only whole segments match.
+        let response = client.get("/blogroll").dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+    }
+}
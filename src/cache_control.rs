@@ -0,0 +1,116 @@
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::{Header, Status},
+    Request, Response,
+};
+use serde::Deserialize;
+
+use crate::config::CONFIG;
+
+/// A `Cache-Control` value applied to every path under `prefix`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct CacheRule {
+    pub prefix: String,
+    pub header: String,
+}
+
+pub fn default_rules() -> Vec<CacheRule> {
+    [
+        ("/static", "public, max-age=3600"),
+        ("/assets", "public, max-age=3600"),
+        ("/search", "no-cache"),
+        ("/admin", "no-store"),
+    ]
+    .into_iter()
+    .map(|(prefix, header)| CacheRule {
+        prefix: prefix.to_string(),
+        header: header.to_string(),
+    })
+    .collect()
+}
+
+/// Sets `Cache-Control` on responses that don't already have one.
+///
+/// Anything about a password-protected article is private, overriding what the route set.
+///
+/// Fingerprinted assets (those with a `v` query parameter) are cached forever, since their URL
+/// changes with their contents. Otherwise the rule with the longest matching prefix applies,
+/// falling back to the default page policy.
+pub struct CacheControl;
+
+#[rocket::async_trait]
+impl Fairing for CacheControl {
+    fn info(&self) -> Info {
+        Info {
+            name: "Cache-Control policy",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        if crate::protect::is_private(request) {
+            response.set_header(Header::new("Cache-Control", "private, no-store"));
+            return;
+        }
+        if response.headers().contains("Cache-Control")
+            || !(response.status().class().is_success() || response.status() == Status::NotModified)
+        {
+            return;
+        }
+        let path = request.uri().path();
+        let is_asset = path.starts_with("/static") || path.starts_with("/assets");
+        let header = if is_asset && request.query_value::<&str>("v").is_some() {
+            &CONFIG.cache_fingerprinted
+        } else {
+            CONFIG
+                .cache_rules
+                .iter()
+                .filter(|rule| path.starts_with(rule.prefix.as_str()))
+                .max_by_key(|rule| rule.prefix.len())
+                .map(|rule| &rule.header)
+                .unwrap_or(&CONFIG.cache_default)
+        };
+        response.set_header(Header::new("Cache-Control", header.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::CONFIG,
+        testing::{client, Fixture},
+    };
+
+    #[rocket::async_test]
+    async fn each_route_class_gets_its_policy() {
+        let fixture = Fixture::new("cache-control");
+        fixture
+            .article(
+                "post",
+                "---\ntitle: Cached\nstatus: published\n---\n\nHi.\n",
+            )
+            .await;
+        let client = client().await;
+        let cases = [
+            (
+                "/static/index.css?v=1234",
+                Some(&*CONFIG.cache_fingerprinted),
+            ),
+            ("/static/index.css", Some("public, max-age=3600")),
+            (&*format!("/search/{}", fixture.rel("")), Some("no-cache")),
+            (
+                &*format!("/{}", fixture.rel("post")),
+                Some(&*CONFIG.cache_default),
+            ),
+            (&*format!("/{}", fixture.rel("missing")), None),
+        ];
+        for (uri, expected) in cases {
+            let response = client.get(uri).dispatch().await;
+            assert_eq!(
+                response.headers().get_one("Cache-Control"),
+                expected,
+                "{uri}"
+            );
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{Cursor, Write},
+    sync::{Arc, LazyLock},
+};
+
+use dashmap::DashMap;
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::{ContentType, Header},
+    tokio::task::spawn_blocking,
+    Request, Response,
+};
+
+/// Compresses text responses for clients that advertise support for it.
+///
+/// Files served by the `FileServer` mounts are left alone, since they may already be compressed.
+pub struct Compression;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Encoding {
+    Brotli,
+    Gzip,
+}
+
+impl Encoding {
+    fn name(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+        }
+    }
+
+    /// Pick the best encoding the client accepts.
+    fn negotiate(request: &Request<'_>) -> Option<Self> {
+        let accepted: Vec<&str> = request
+            .headers()
+            .get("Accept-Encoding")
+            .flat_map(|h| h.split(','))
+            .filter_map(|e| {
+                let mut parts = e.split(';').map(str::trim);
+                let name = parts.next()?;
+                let q = parts
+                    .find_map(|p| p.strip_prefix("q="))
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                (q > 0.0).then_some(name)
+            })
+            .collect();
+        [Encoding::Brotli, Encoding::Gzip]
+            .into_iter()
+            .find(|e| accepted.contains(&e.name()))
+    }
+
+    fn compress(&self, body: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Encoding::Brotli => {
+                let mut out = vec![];
+                brotli::CompressorWriter::new(&mut out, 4096, 9, 22).write_all(body)?;
+                Ok(out)
+            }
+            Encoding::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+                encoder.write_all(body)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+const MAX_CACHED: usize = 256;
+/// How many compressed versions of its pages an article keeps, since the same article can be
+/// shown with different mentions or suggestions.
+const MAX_CACHED_PER_ARTICLE: usize = 8;
+
+/// Compressed bodies keyed on a hash of the uncompressed body and their encoding, so unchanged
+/// pages are only compressed once.
+#[derive(Default, Debug)]
+pub struct Bodies(DashMap<(u64, Encoding), Vec<u8>>);
+
+impl Bodies {
+    fn get(&self, key: &(u64, Encoding)) -> Option<Vec<u8>> {
+        self.0.get(key).map(|c| c.clone())
+    }
+
+    fn insert(&self, key: (u64, Encoding), compressed: Vec<u8>, max: usize) {
+        if self.0.len() >= max {
+            self.0.clear();
+        }
+        self.0.insert(key, compressed);
+    }
+}
+
+/// Bodies of responses that aren't kept anywhere else.
+static COMPRESSED: LazyLock<Bodies> = LazyLock::new(Bodies::default);
+
+/// Where the response to a request keeps its compressed bodies, if not in [`COMPRESSED`].
+struct KeptIn(Option<Arc<Bodies>>);
+
+/// Keep the compressed bodies of the response to `request` in `bodies`, such as an article's
+/// cache entry, so they're dropped along with it rather than crowding out other pages.
+pub fn keep_in(request: &Request<'_>, bodies: Arc<Bodies>) {
+    request.local_cache(|| KeptIn(Some(bodies)));
+}
+
+fn compressible(content_type: &ContentType) -> bool {
+    content_type.is_html()
+        || content_type.is_xml()
+        || content_type.is_json()
+        || content_type.is_plain()
+        || content_type.sub() == "atom+xml"
+}
+
+/// Add `field` to the response's `Vary` header, keeping whatever it already varies on.
+fn vary_on(response: &mut Response<'_>, field: &str) {
+    let mut fields: Vec<String> = response
+        .headers()
+        .get("Vary")
+        .flat_map(|v| v.split(','))
+        .map(|f| f.trim().to_string())
+        .filter(|f| !f.is_empty())
+        .collect();
+    if !fields.iter().any(|f| f.eq_ignore_ascii_case(field)) {
+        fields.push(field.to_string());
+    }
+    response.set_header(Header::new("Vary", fields.join(", ")));
+}
+
+#[rocket::async_trait]
+impl Fairing for Compression {
+    fn info(&self) -> Info {
+        Info {
+            name: "Response compression",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let path = request.uri().path();
+        if path.starts_with("/static") || path.starts_with("/assets") {
+            return;
+        }
+        if response.headers().contains("Content-Encoding")
+            || !response.content_type().is_some_and(|ct| compressible(&ct))
+        {
+            return;
+        }
+        // Whether or not this client gets a compressed body, the next one might.
+        vary_on(response, "Accept-Encoding");
+        let Some(encoding) = Encoding::negotiate(request) else {
+            return;
+        };
+        let Ok(body) = response.body_mut().to_bytes().await else {
+            return;
+        };
+
+        let mut hasher = DefaultHasher::new();
+        body.hash(&mut hasher);
+        let key = (hasher.finish(), encoding);
+        let (bodies, max) = match &request.local_cache(|| KeptIn(None)).0 {
+            Some(bodies) => (&**bodies, MAX_CACHED_PER_ARTICLE),
+            None => (&*COMPRESSED, MAX_CACHED),
+        };
+        let compressed = match bodies.get(&key) {
+            Some(compressed) => Some(compressed),
+            None => {
+                let compressed = spawn_blocking({
+                    let body = body.clone();
+                    move || encoding.compress(&body)
+                })
+                .await;
+                match compressed {
+                    Ok(Ok(compressed)) => {
+                        bodies.insert(key, compressed.clone(), max);
+                        Some(compressed)
+                    }
+                    _ => None,
+                }
+            }
+        };
+
+        match compressed {
+            Some(compressed) => {
+                response.set_header(Header::new("Content-Encoding", encoding.name()));
+                response.set_sized_body(compressed.len(), Cursor::new(compressed));
+            }
+            None => response.set_sized_body(body.len(), Cursor::new(body)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{client, Fixture};
+
+    #[rocket::async_test]
+    async fn only_negotiated_responses_vary() {
+        let fixture = Fixture::new("vary");
+        fixture
+            .article(
+                "post",
+                "---\ntitle: Varied\nstatus: published\n---\n\nHi.\n",
+            )
+            .await;
+        let client = client().await;
+        let cases = [
+            (
+                format!("/{}", fixture.rel("post")),
+                Some("Accept, Accept-Encoding"),
+            ),
+            (
+                format!("/feed/{}", fixture.rel("")),
+                Some("Accept-Encoding"),
+            ),
+            ("/static/index.css".to_string(), None),
+        ];
+        for (uri, expected) in cases {
+            let response = client.get(&uri).dispatch().await;
+            assert_eq!(response.headers().get_one("Vary"), expected, "{uri}");
+        }
+    }
+}
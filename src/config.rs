@@ -0,0 +1,348 @@
+use std::{ops::Deref, path::PathBuf};
+
+use serde::Deserialize;
+
+use crate::{
+    article::{FeedContent, Renderer, UndatedSort},
+    cache_control::{self, CacheRule},
+    filters::{Callout, ExternalLinks, Highlighter},
+    home::HomePage,
+    minify::MinifyHtml,
+    read_next::ReadNextPolicy,
+    roots::{self, ContentRoot},
+    summary::SummaryPolicy,
+    trailing_slash::TrailingSlash,
+};
+
+/// Site configuration, read from `Wolog.toml` and `WOLOG_`-prefixed environment variables.
+///
+/// Tests see the configuration they're run with instead, as [`crate::testing`] injects it.
+pub static CONFIG: Current = Current;
+
+#[cfg(not(test))]
+static LOADED: std::sync::LazyLock<Config> = std::sync::LazyLock::new(|| {
+    use rocket::figment::{
+        providers::{Env, Format, Toml},
+        Figment,
+    };
+
+    Figment::new()
+        .merge(Toml::file("Wolog.toml"))
+        .merge(Env::prefixed("WOLOG_"))
+        .extract_lossy::<Config>()
+        .expect("Bad wolog configuration")
+        .finish()
+});
+
+/// The configuration in effect, which [`CONFIG`] derefs to.
+pub struct Current;
+
+impl Deref for Current {
+    type Target = Config;
+
+    #[cfg(not(test))]
+    fn deref(&self) -> &Config {
+        &LOADED
+    }
+
+    #[cfg(test)]
+    fn deref(&self) -> &Config {
+        crate::testing::config_in_effect()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct Config {
+    /// The public base URL of the site.
+    pub url: String,
+    /// The path the site is served under, like `/blog`, if it isn't the root of its domain.
+    pub base_path: String,
+    /// The directories articles are read from, and where on the site each is served.
+    pub roots: Vec<ContentRoot>,
+    /// What's shown at `/`: an article, or a listing of the newest ones.
+    pub home: HomePage,
+    /// Development mode, which reloads templates as they change.
+    pub dev: bool,
+    /// Render articles that aren't marked as ready.
+    pub preview_nonready: bool,
+    /// A token granting access to the admin routes, which are disabled if it's unset.
+    pub admin_token: Option<String>,
+    /// How often (in seconds) to check for articles whose `publish_at` time has passed.
+    pub publish_check_interval: u64,
+    /// How long (in seconds) entering a protected article's password unlocks it for.
+    pub unlock_duration: u64,
+    /// The secret keying unlock cookies. If it's unset, a random one is made at startup, so
+    /// unlocked articles lock again whenever the server restarts.
+    pub unlock_secret: Option<String>,
+    /// The site author's name, used where an article doesn't name its own.
+    pub author: String,
+    /// The site author's email address, which feeds only show if `feed_include_email` is set.
+    pub author_email: Option<String>,
+    /// The site author's profile page, used to identify them in feeds and bylines, falling back
+    /// to the site's own URL.
+    pub author_url: Option<String>,
+    /// Publish authors' email addresses in feeds.
+    pub feed_include_email: bool,
+    /// The language of articles that don't name their own, as a BCP 47 tag.
+    pub lang: String,
+    /// Where articles can be edited, e.g. `https://github.com/user/repo/edit/main/`.
+    ///
+    /// Paths are relative to the articles directory.
+    pub repo_edit_base: Option<String>,
+    /// Whether page URLs canonically end in a slash; the other form redirects to it.
+    pub trailing_slash: TrailingSlash,
+    /// Let article URLs differ from their files' names in case, redirecting to the file's own
+    /// spelling. Content root prefixes still have to match exactly.
+    pub case_insensitive_paths: bool,
+    /// The `Content-Security-Policy` of every response, unless an article sets its own.
+    pub content_security_policy: String,
+    /// The `Referrer-Policy` of every response.
+    pub referrer_policy: String,
+    /// The `Permissions-Policy` of every response.
+    pub permissions_policy: String,
+    /// `Cache-Control` for responses no rule matches, mostly rendered pages.
+    pub cache_default: String,
+    /// `Cache-Control` for fingerprinted static files.
+    pub cache_fingerprinted: String,
+    /// `Cache-Control` by path prefix; the longest matching prefix wins.
+    pub cache_rules: Vec<CacheRule>,
+    /// How much of each article feeds carry, unless a feed or article asks otherwise.
+    pub feed_content: FeedContent,
+    /// What listings show in place of the dates of articles that have none.
+    pub undated_label: String,
+    /// Whether articles without a date sort before or after the rest.
+    pub undated_sort: UndatedSort,
+    /// How many of the newest articles a feed carries, with older ones on later pages.
+    pub feed_limit: usize,
+    /// The site path of the feed pages advertise as the site's feed, like `/feed/posts` or
+    /// `/feed?content=summary`. A bare `/feed`, and the paths other blogs serve feeds at, redirect
+    /// to it.
+    pub primary_feed: String,
+    /// How summaries of articles without an excerpt, and blurbs in lists, are shortened.
+    pub summary: SummaryPolicy,
+    /// Which articles are suggested for reading next at the end of each one.
+    pub read_next: ReadNextPolicy,
+    /// The IANA time zone dates are shown and scheduled in, like `Europe/Berlin`, defaulting to
+    /// the server's.
+    pub timezone: Option<chrono_tz::Tz>,
+    /// Let any change to an article's file, even to its whitespace or front matter, bump the
+    /// `updated` date of articles that don't set one.
+    pub updated_from_any_edit: bool,
+    /// A line marking the end of an article's excerpt, which lists and feeds show in place of
+    /// the whole article.
+    pub excerpt_marker: String,
+    /// The largest webmention request body accepted, in bytes.
+    pub webmention_limit: u64,
+    /// The largest markdown body the preview endpoint accepts, in bytes.
+    pub preview_limit: u64,
+    /// Keep rendered articles on disk, so a restart doesn't have to render them all again.
+    pub persist_cache: bool,
+    /// Where rendered articles are kept if `persist_cache` is set.
+    pub cache_dir: PathBuf,
+    /// A TOML file of domains and paths to accept or drop webmentions for, re-read when it
+    /// changes. See [`crate::mention_policy::MentionPolicy`].
+    pub webmention_policy: String,
+    /// Send webmentions to the pages articles link to with the `mention` class.
+    pub send_webmentions: bool,
+    /// How often (in seconds) to look for webmentions due to be sent.
+    pub webmention_queue_interval: u64,
+    /// How many times sending a webmention is tried before giving up on it.
+    pub webmention_send_attempts: i64,
+    /// How long (in seconds) to wait before retrying a webmention, doubling after each failure.
+    pub webmention_retry_delay: i64,
+    /// The least time (in seconds) between webmentions sent to the same domain.
+    pub webmention_domain_interval: u64,
+    /// The most characters of a mentioning post's content shown with the mention. Longer
+    /// content is cut short to plain text, and 0 shows none.
+    pub mention_excerpt_length: usize,
+    /// The most connections kept open to the webmention database.
+    pub database_connections: u32,
+    /// How long (in seconds) a query waits for another connection's write to finish before
+    /// failing with "database is locked".
+    pub database_busy_timeout: u64,
+    /// Open the webmention database in write-ahead-log mode, so reading mentions doesn't wait on
+    /// mentions being written.
+    pub database_wal: bool,
+    /// How many external links the link checker fetches a second.
+    pub link_check_rate: usize,
+    /// How long (in seconds) the link checker trusts an external link's last result.
+    pub link_check_cache: u64,
+    /// How long shutdown waits (in seconds) for background work like incoming webmentions.
+    pub shutdown_timeout: u64,
+    /// Strip scripts and other active content from articles that don't say otherwise.
+    pub sanitize_html: bool,
+    /// Minify articles' content or whole pages. Whitespace in `pre` blocks is kept either way.
+    pub minify_html: MinifyHtml,
+    /// Refuse to render articles with front-matter keys that look like typos of known fields.
+    pub strict_meta: bool,
+    /// The most articles a single search returns, whatever limit it asks for.
+    pub max_search_results: usize,
+    /// How many articles a tag page lists before linking to the next page.
+    pub tag_page_size: usize,
+    /// How long pandoc may run (in seconds) before it's killed.
+    pub render_timeout: u64,
+    /// Which backend turns markdown into HTML.
+    pub renderer: Renderer,
+    /// Whether articles that don't set `math` render their TeX math to static MathML. Unset,
+    /// this is on with pandoc and off with the native renderer, which needs pandoc for it.
+    pub math: Option<bool>,
+    /// The kinds of callout, like `::: warning`, that fenced divs can be.
+    pub callouts: Vec<Callout>,
+    /// How links to other sites are marked.
+    pub external_links: ExternalLinks,
+    /// What highlights code blocks.
+    pub highlighter: Highlighter,
+    /// The syntect theme code is highlighted with, if `highlighter` is `syntect`.
+    pub highlight_theme: String,
+    /// Number the lines of code highlighted by syntect.
+    pub line_numbers: bool,
+    /// The widths (in pixels) local images are resized to for `srcset`s. Images are never
+    /// enlarged.
+    pub image_widths: Vec<u32>,
+    /// A TrueType or OpenType font to draw social preview images with, for articles without an
+    /// `image`. Images are only generated if it's set.
+    pub og_image_font: Option<PathBuf>,
+    /// The background colour of generated preview images, like `#1d2021`.
+    pub og_image_background: String,
+    /// The text colour of generated preview images.
+    pub og_image_foreground: String,
+    /// How many pandoc processes may run at once.
+    pub pandoc_jobs: usize,
+    /// How many articles deep rendering may go for embedded searches, counting the article
+    /// embedding the first. Deeper articles are left out of the results.
+    pub max_embed_depth: usize,
+    /// Lua filters pandoc runs while reading each article, in order.
+    ///
+    /// Pandoc filters all run during conversion, before wolog's own filters see the document.
+    pub lua_filters: Vec<PathBuf>,
+    /// Executable JSON filters pandoc runs while reading each article, after the Lua filters.
+    pub pandoc_filters: Vec<PathBuf>,
+    /// If set, re-render `always_rerender` articles in the background this often (in seconds)
+    /// instead of on every request.
+    pub rerender_interval: Option<u64>,
+    /// Fill in missing article dates from git history rather than filesystem times, which a
+    /// fresh checkout resets.
+    pub dates_from_git: bool,
+    /// The engine pandoc uses to produce PDFs, or pandoc's own default if unset.
+    pub pdf_engine: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            url: "https://wolo.dev/".to_string(),
+            base_path: String::new(),
+            roots: roots::default_roots(),
+            home: HomePage::default(),
+            dev: false,
+            preview_nonready: false,
+            admin_token: None,
+            unlock_duration: 60 * 60,
+            unlock_secret: None,
+            publish_check_interval: 60,
+            author: "Willow".to_string(),
+            author_email: None,
+            author_url: None,
+            feed_include_email: false,
+            lang: "en".to_string(),
+            repo_edit_base: None,
+            trailing_slash: TrailingSlash::Strip,
+            case_insensitive_paths: false,
+            content_security_policy: "default-src 'self'; img-src 'self' https: data:; \
+                style-src 'self' 'unsafe-inline'; frame-ancestors 'self'"
+                .to_string(),
+            referrer_policy: "strict-origin-when-cross-origin".to_string(),
+            permissions_policy: "interest-cohort=()".to_string(),
+            cache_default: "public, max-age=60, must-revalidate".to_string(),
+            cache_fingerprinted: "public, max-age=31536000, immutable".to_string(),
+            cache_rules: cache_control::default_rules(),
+            feed_content: FeedContent::Full,
+            undated_label: "Undated".to_string(),
+            undated_sort: UndatedSort::Last,
+            summary: SummaryPolicy::default(),
+            read_next: ReadNextPolicy::default(),
+            timezone: None,
+            feed_limit: 25,
+            primary_feed: "/feed".to_string(),
+            updated_from_any_edit: false,
+            excerpt_marker: "<!--more-->".to_string(),
+            webmention_limit: 16 * 1024,
+            preview_limit: 256 * 1024,
+            persist_cache: false,
+            cache_dir: PathBuf::from(".wolog-cache"),
+            webmention_policy: "Webmentions.toml".to_string(),
+            send_webmentions: false,
+            webmention_queue_interval: 30,
+            webmention_send_attempts: 8,
+            webmention_retry_delay: 60,
+            webmention_domain_interval: 5,
+            mention_excerpt_length: 280,
+            database_connections: 4,
+            database_busy_timeout: 5,
+            database_wal: true,
+            link_check_rate: 4,
+            link_check_cache: 6 * 60 * 60,
+            shutdown_timeout: 10,
+            sanitize_html: false,
+            minify_html: MinifyHtml::Off,
+            strict_meta: false,
+            max_search_results: 200,
+            tag_page_size: 20,
+            render_timeout: 120,
+            renderer: Renderer::Pandoc,
+            math: None,
+            callouts: Callout::defaults(),
+            external_links: ExternalLinks::default(),
+            highlighter: Highlighter::Pandoc,
+            highlight_theme: "InspiredGitHub".to_string(),
+            line_numbers: false,
+            image_widths: vec![480, 960, 1440],
+            og_image_font: None,
+            og_image_background: "#1d2021".to_string(),
+            og_image_foreground: "#fbf1c7".to_string(),
+            max_embed_depth: 8,
+            pandoc_jobs: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(4),
+            lua_filters: vec![],
+            pandoc_filters: vec![],
+            rerender_interval: None,
+            dates_from_git: false,
+            pdf_engine: None,
+        }
+    }
+}
+
+impl Config {
+    /// Correct settings that would leave part of the site unable to work.
+    pub fn finish(mut self) -> Self {
+        if self.pandoc_jobs == 0 {
+            eprintln!("pandoc_jobs is 0, so no article could ever be rendered; using 1 instead");
+            self.pandoc_jobs = 1;
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_pandoc_jobs_still_renders() {
+        let config = Config {
+            pandoc_jobs: 0,
+            ..Default::default()
+        }
+        .finish();
+        assert_eq!(config.pandoc_jobs, 1);
+
+        let config = Config {
+            pandoc_jobs: 3,
+            ..Default::default()
+        }
+        .finish();
+        assert_eq!(config.pandoc_jobs, 3);
+    }
+}
@@ -1,45 +1,149 @@
 use std::{
+    path::PathBuf,
+    str::FromStr,
     sync::{Arc, LazyLock},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use reqwest::Client;
+use dashmap::DashMap;
 use rocket::tokio::{
     runtime::Handle,
     sync::{OnceCell, Semaphore},
 };
-use sqlx::{migrate, Pool, Sqlite};
+use rocket_dyn_templates::tera::escape_html;
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    migrate,
+    pool::PoolOptions,
+    sqlite::{SqliteConnectOptions, SqliteJournalMode},
+    Pool, Sqlite, Transaction,
+};
 
-use crate::WOLOG_URL;
+use crate::{config::CONFIG, mention_policy, sanitize, url};
 
 static DB: OnceCell<Pool<Sqlite>> = OnceCell::const_new();
 
+/// Where webmentions are kept if `DATABASE_URL` isn't set.
+#[cfg(not(test))]
+fn default_database() -> PathBuf {
+    PathBuf::from("data/wolog.db")
+}
+
+/// Tests keep their webmentions in a database of their own, so they never touch the site's.
+#[cfg(test)]
+fn default_database() -> PathBuf {
+    std::env::temp_dir().join(format!("wolog-test-{}.db", std::process::id()))
+}
+
 async fn db() -> &'static Pool<Sqlite> {
-    DB.get_or_init(|| async {
-        if let Some(db) = connect_to_disk().await {
-            db
-        } else {
-            connect_to_memory().await
-        }
-    })
-    .await
+    DB.get_or_try_init(open_configured)
+        .await
+        .unwrap_or_else(|e| panic!("Couldn't open the database: {e}"))
 }
 
-async fn connect_to_disk() -> Option<Pool<Sqlite>> {
-    let url = std::env::var("DATABASE_URL").ok()?;
-    let pool = Pool::connect_lazy(&url).ok()?;
-    println!("Start running migrations...");
-    migrate!().run(&pool).await.expect("Migrations failed");
-    println!("Done running migrations!");
-    Some(pool)
+/// Open the database, so the wolog can refuse to start rather than lose webmentions when it
+/// can't be.
+pub async fn init() -> Result<(), sqlx::Error> {
+    DB.get_or_try_init(open_configured).await.map(|_| ())
+}
+
+async fn open_configured() -> Result<Pool<Sqlite>, sqlx::Error> {
+    open(connect_options(std::env::var("DATABASE_URL").ok())?).await
 }
 
-async fn connect_to_memory() -> Pool<Sqlite> {
-    let pool = Pool::connect_lazy("sqlite::memory:").unwrap();
+/// Connect to a database and bring its tables up to date.
+async fn open(options: SqliteConnectOptions) -> Result<Pool<Sqlite>, sqlx::Error> {
+    let pool = pool_options().connect_with(options).await?;
     println!("Start running migrations...");
-    migrate!().run(&pool).await.expect("Migrations failed");
+    migrate!().run(&pool).await?;
+    run_data_migrations(&pool).await?;
     println!("Done running migrations!");
-    pool
+    Ok(pool)
+}
+
+/// Run the migrations the SQL ones queued in `data_migrations` because they need Rust, each in a
+/// transaction with its removal from the queue.
+async fn run_data_migrations(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
+    let queued: Vec<String> = sqlx::query_scalar("SELECT name FROM data_migrations")
+        .fetch_all(pool)
+        .await?;
+    for name in queued {
+        let mut tx = pool.begin().await?;
+        match name.as_str() {
+            "encode_mention_paths" => encode_mention_paths(&mut tx).await?,
+            _ => panic!("Unknown data migration {name}"),
+        }
+        sqlx::query("DELETE FROM data_migrations WHERE name = $1")
+            .bind(&name)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+    }
+    Ok(())
+}
+
+/// Store mention targets as [`url::encode_path`] spells them. They used to be kept as they were
+/// found in the target URL, however much of it the sender had encoded.
+async fn encode_mention_paths(tx: &mut Transaction<'_, Sqlite>) -> Result<(), sqlx::Error> {
+    let rows: Vec<(String, String)> =
+        sqlx::query_as("SELECT from_url, to_path FROM received_mentions")
+            .fetch_all(&mut **tx)
+            .await?;
+    for (from, to) in rows {
+        let encoded = url::encode_path(&url::decode_path(&to));
+        if encoded != to {
+            sqlx::query(
+                "UPDATE OR REPLACE received_mentions SET to_path = $1 WHERE from_url = $2 AND to_path = $3",
+            )
+            .bind(encoded)
+            .bind(from)
+            .bind(to)
+            .execute(&mut **tx)
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+/// How to open the database at `url`, or at [`default_database`] without one, creating it if it
+/// doesn't exist. Webmentions are only kept in memory if `url` is `sqlite::memory:`.
+fn connect_options(url: Option<String>) -> Result<SqliteConnectOptions, sqlx::Error> {
+    let options = match url {
+        Some(url) => {
+            println!("Using the database at {url}");
+            SqliteConnectOptions::from_str(&url)?
+        }
+        None => {
+            let path = default_database();
+            println!(
+                "DATABASE_URL isn't set; using the database at {}",
+                path.display()
+            );
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            SqliteConnectOptions::new().filename(path)
+        }
+    };
+    let options = options
+        .create_if_missing(true)
+        .busy_timeout(Duration::from_secs(CONFIG.database_busy_timeout));
+    Ok(if CONFIG.database_wal {
+        options.journal_mode(SqliteJournalMode::Wal)
+    } else {
+        options
+    })
+}
+
+fn pool_options() -> PoolOptions<Sqlite> {
+    PoolOptions::new().max_connections(CONFIG.database_connections.max(1))
+}
+
+/// Check that the database can be queried, for the readiness endpoint.
+pub async fn ping() -> Result<(), sqlx::Error> {
+    sqlx::query("SELECT 1").execute(db().await).await?;
+    Ok(())
 }
 
 static WEBMENTION_BUCKET: LazyLock<Arc<Semaphore>> = LazyLock::new(|| {
@@ -59,7 +163,17 @@ static WEBMENTION_BUCKET: LazyLock<Arc<Semaphore>> = LazyLock::new(|| {
     semaphore
 });
 
+/// Check that `from` mentions the raw site-relative path `to`, and record the mention if it does.
 pub async fn received_webmention(from: String, to: String) {
+    let host = url::parse_http_url(&from)
+        .and_then(|source| source.host_str().map(str::to_string))
+        .unwrap_or_default();
+    if let Some(reason) = mention_policy::rejection(&host, &to) {
+        debug!("Dropping webmention {from}->{to}; {reason}.");
+        return;
+    }
+    let expected_url = url::absolute_url(&to);
+    let to = url::encode_path(&to);
     WEBMENTION_BUCKET.acquire().await.unwrap().forget();
     let Ok(mut mentioner) = reqwest::get(&from).await else {
         println!("Processing webmention {from}->{to} failed; couldn't start request.");
@@ -84,17 +198,32 @@ pub async fn received_webmention(from: String, to: String) {
         println!("Processing webmention {from}->{to} failed; non-UTF-8 response.");
         return;
     };
-    let expected_url = WOLOG_URL.to_string() + &to.replace(" ", "%20");
     if !mentioner.contains(&expected_url) {
         println!(
             "Processing webmention {from}->{to} failed; doesn't actually mention {expected_url}."
         );
         return;
     }
+    let (author, excerpt) = {
+        let document = Html::parse_document(&mentioner);
+        (
+            Author::from_h_card(&document, &from),
+            excerpt(&document, &from),
+        )
+    };
+    let now = unix_now();
+    // A mention received again, say because its source was edited, keeps its row and has what
+    // we learned about it refreshed.
     if let Err(e) = sqlx::query!(
-        "INSERT OR REPLACE INTO received_mentions VALUES($1, $2)",
+        "INSERT INTO received_mentions (from_url, to_path, author_name, author_photo, author_url, excerpt, received_at) VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (from_url, to_path) DO UPDATE SET author_name = excluded.author_name, author_photo = excluded.author_photo, author_url = excluded.author_url, excerpt = excluded.excerpt, received_at = excluded.received_at",
         from,
-        to
+        to,
+        author.name,
+        author.photo,
+        author.url,
+        excerpt,
+        now
     )
     .execute(db().await)
     .await
@@ -103,15 +232,699 @@ pub async fn received_webmention(from: String, to: String) {
     }
 }
 
-pub async fn mentions_of(article: &str) -> Vec<String> {
-    let data: Vec<_> = sqlx::query!(
-        "SELECT from_url FROM received_mentions WHERE to_path = $1",
-        article
+/// A received mention, with whatever we learned about its author when it arrived.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct MentionDetail {
+    pub source: String,
+    pub author_name: Option<String>,
+    pub author_photo: Option<String>,
+    pub author_url: Option<String>,
+    /// What the mentioning post says, as sanitized HTML.
+    pub excerpt: Option<String>,
+    /// When it was last received, in Unix time.
+    pub received_at: Option<i64>,
+}
+
+/// Every mention of an article, newest first.
+pub async fn mentions_detail(article: &str) -> Vec<MentionDetail> {
+    mentions_of(&[article], None, 0).await
+}
+
+/// The mentions sent to any of `targets`, the site-relative paths of one article, newest first.
+///
+/// At most `limit` mentions are returned, after skipping the first `offset`. A page that
+/// mentioned the article at more than one of the paths is only counted once.
+pub async fn mentions_of(targets: &[&str], limit: Option<u32>, offset: u32) -> Vec<MentionDetail> {
+    let targets: Vec<String> = targets.iter().map(|t| url::encode_path(t)).collect();
+    let targets = serde_json::to_string(&targets).unwrap_or_default();
+    // SQLite's LIMIT takes -1 to mean no limit.
+    let limit = limit.map_or(-1, i64::from);
+    let offset = i64::from(offset);
+    // With MAX, SQLite takes the row's other columns from the latest mention of the group.
+    sqlx::query_as!(
+        MentionDetail,
+        r#"SELECT from_url AS source, author_name, author_photo, author_url, excerpt, MAX(received_at) AS "received_at: i64"
+        FROM received_mentions WHERE to_path IN (SELECT value FROM json_each($1))
+        GROUP BY from_url ORDER BY received_at DESC, from_url LIMIT $2 OFFSET $3"#,
+        targets,
+        limit,
+        offset
+    )
+    .fetch_all(db().await)
+    .await
+    .unwrap_or_else(|e| {
+        eprintln!("Error reading the webmentions of {targets}: {e}");
+        vec![]
+    })
+}
+
+/// Record a mention from `from` to the site-relative path `to` as received at `received_at`,
+/// without fetching its source.
+#[cfg(test)]
+pub async fn store_mention(from: &str, to: &str, excerpt: Option<&str>, received_at: i64) {
+    sqlx::query(
+        "INSERT OR REPLACE INTO received_mentions (from_url, to_path, excerpt, received_at) VALUES ($1, $2, $3, $4)",
+    )
+    .bind(from)
+    .bind(url::encode_path(to))
+    .bind(excerpt)
+    .bind(received_at)
+    .execute(db().await)
+    .await
+    .expect("Couldn't store the mention");
+}
+
+/// The author of a mentioning page, as described by its h-card.
+#[derive(Default)]
+struct Author {
+    name: Option<String>,
+    photo: Option<String>,
+    url: Option<String>,
+}
+
+impl Author {
+    /// Read the author from the h-card of the page's h-entry, or failing that the first h-card
+    /// on the page. Relative URLs are resolved against `base`.
+    fn from_h_card(document: &Html, base: &str) -> Self {
+        let selector = |s| Selector::parse(s).unwrap();
+        let Some(card) = document
+            .select(&selector(
+                ".h-entry .p-author.h-card, .h-entry .u-author.h-card",
+            ))
+            .next()
+            .or_else(|| document.select(&selector(".h-card")).next())
+        else {
+            return Self::default();
+        };
+        let text = |el: ElementRef| {
+            let text = el.text().collect::<String>();
+            let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            (!text.is_empty()).then_some(text)
+        };
+        let resolve = |url: &str| {
+            reqwest::Url::parse(base)
+                .and_then(|base| base.join(url))
+                .ok()
+                .map(String::from)
+                .and_then(sanitize::web_url)
+        };
+        let name = card
+            .select(&selector(".p-name"))
+            .next()
+            .and_then(text)
+            .or_else(|| text(card));
+        let photo = card
+            .select(&selector(".u-photo"))
+            .next()
+            .or_else(|| (card.value().name() == "img").then_some(card))
+            .and_then(|el| el.attr("src"))
+            .and_then(resolve);
+        let url = card
+            .select(&selector(".u-url"))
+            .next()
+            .or_else(|| (card.value().name() == "a").then_some(card))
+            .and_then(|el| el.attr("href"))
+            .and_then(resolve);
+        Self { name, photo, url }
+    }
+}
+
+/// The content of the page's h-entry, or failing that its summary, sanitized to show with the
+/// mention. Relative links are resolved against `base`.
+///
+/// Content with more text than
+/// [`mention_excerpt_length`](crate::config::Config::mention_excerpt_length) is cut short to
+/// that much of its text, without its markup.
+fn excerpt(document: &Html, base: &str) -> Option<String> {
+    static CONTENT: LazyLock<Selector> =
+        LazyLock::new(|| Selector::parse(".h-entry .e-content").unwrap());
+    static SUMMARY: LazyLock<Selector> =
+        LazyLock::new(|| Selector::parse(".h-entry .p-summary").unwrap());
+    let max = CONFIG.mention_excerpt_length;
+    if max == 0 {
+        return None;
+    }
+    let content = document
+        .select(&CONTENT)
+        .next()
+        .or_else(|| document.select(&SUMMARY).next())?;
+    let html = sanitize::mention_content(&content.inner_html(), base);
+    let text = Html::parse_fragment(&html)
+        .root_element()
+        .text()
+        .collect::<String>();
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() {
+        return None;
+    }
+    if text.chars().count() <= max {
+        return Some(html.trim().to_string());
+    }
+    let cut = text.char_indices().nth(max).map_or(text.len(), |(i, _)| i);
+    // Back to the end of the last whole word, if there is one.
+    let short = text[..cut]
+        .rsplit_once(' ')
+        .map_or(&text[..cut], |(words, _)| words);
+    Some(format!("{}…", escape_html(short.trim_end())))
+}
+
+/// Queue a webmention from `from`, an article's URL, to `to`, to be sent once it's `due` (in
+/// Unix time).
+///
+/// A mention that's already been queued is only queued again if the article's `revision`, a hash
+/// of its content, has changed, so it isn't sent again every time the article is rendered.
+pub async fn send_webmention(from: String, to: String, revision: i64, due: i64) {
+    if let Err(e) = sqlx::query!(
+        "INSERT INTO outgoing_mentions (source, target, revision, attempts, next_attempt) VALUES ($1, $2, $3, 0, $4)
+        ON CONFLICT (source, target) DO UPDATE SET revision = excluded.revision, attempts = 0, next_attempt = excluded.next_attempt, last_error = NULL
+        WHERE revision != excluded.revision",
+        from,
+        to,
+        revision,
+        due
+    )
+    .execute(db().await)
+    .await
+    {
+        eprintln!("Error queueing webmention {from}->{to}: {e}");
+    }
+}
+
+fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// When a webmention was last sent to each domain.
+static LAST_SENT: LazyLock<DashMap<String, Instant>> = LazyLock::new(DashMap::new);
+
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .user_agent(concat!("wolog/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .expect("Couldn't build the webmention HTTP client")
+});
+
+/// Send queued webmentions as they come due, checking every `interval`.
+///
+/// Failures that might be temporary are retried after
+/// [`webmention_retry_delay`](crate::config::Config::webmention_retry_delay), doubling each time,
+/// until [`webmention_send_attempts`](crate::config::Config::webmention_send_attempts) have been
+/// made.
+pub async fn send_queued_webmentions(interval: Duration) {
+    let mut clock = rocket::tokio::time::interval(interval);
+    loop {
+        clock.tick().await;
+        send_due_webmentions().await;
+    }
+}
+
+/// Try sending the queued webmentions that are due now.
+async fn send_due_webmentions() {
+    let now = unix_now();
+    let due = sqlx::query!(
+        "SELECT source, target, attempts FROM outgoing_mentions WHERE next_attempt <= $1 ORDER BY next_attempt LIMIT 32",
+        now
     )
     .fetch_all(db().await)
+    .await;
+    let due = match due {
+        Ok(due) => due,
+        Err(e) => {
+            eprintln!("Error reading the webmention queue: {e}");
+            return;
+        }
+    };
+    for mention in due {
+        let (from, to) = (mention.source, mention.target);
+        let host = url::parse_http_url(&to)
+            .and_then(|target| target.host_str().map(str::to_string))
+            .unwrap_or_default();
+        let spacing = Duration::from_secs(CONFIG.webmention_domain_interval);
+        let wait = LAST_SENT
+            .get(&host)
+            .map(|last| spacing.saturating_sub(last.elapsed()))
+            .filter(|wait| !wait.is_zero());
+        if let Some(wait) = wait {
+            // Not an attempt, so it doesn't count towards giving up.
+            let next = unix_now() + wait.as_secs() as i64 + 1;
+            reschedule(&from, &to, mention.attempts, Some(next), None).await;
+            continue;
+        }
+        WEBMENTION_BUCKET.acquire().await.unwrap().forget();
+        LAST_SENT.insert(host, Instant::now());
+        let attempts = mention.attempts + 1;
+        match deliver(&from, &to).await {
+            Ok(()) => {
+                println!("Sent webmention {from}->{to}");
+                reschedule(&from, &to, attempts, None, None).await;
+            }
+            Err(Failure::Retry(e)) if attempts < CONFIG.webmention_send_attempts => {
+                let delay = CONFIG
+                    .webmention_retry_delay
+                    .saturating_mul(1 << (attempts - 1).min(16))
+                    .min(24 * 60 * 60);
+                println!("Sending webmention {from}->{to} failed; retrying in {delay}s. {e}");
+                reschedule(&from, &to, attempts, Some(unix_now() + delay), Some(&e)).await;
+            }
+            Err(Failure::Retry(e) | Failure::GiveUp(e)) => {
+                println!("Sending webmention {from}->{to} failed; giving up. {e}");
+                reschedule(&from, &to, attempts, None, Some(&e)).await;
+            }
+        }
+    }
+}
+
+/// Record an attempt at sending a webmention, and when to try next if ever.
+async fn reschedule(from: &str, to: &str, attempts: i64, next: Option<i64>, error: Option<&str>) {
+    if let Err(e) = sqlx::query!(
+        "UPDATE outgoing_mentions SET attempts = $1, next_attempt = $2, last_error = $3 WHERE source = $4 AND target = $5",
+        attempts,
+        next,
+        error,
+        from,
+        to
+    )
+    .execute(db().await)
     .await
-    .unwrap_or_default();
-    data.into_iter().map(|v| v.from_url).collect()
+    {
+        eprintln!("Error updating webmention {from}->{to}: {e}");
+    }
+}
+
+/// Why a webmention couldn't be sent.
+enum Failure {
+    /// Something that might work later, like the target being down.
+    Retry(String),
+    /// Something that won't, like the target not accepting webmentions.
+    GiveUp(String),
 }
 
-pub async fn send_webmention(from: String, to: String) {}
+impl Failure {
+    fn from_status(status: reqwest::StatusCode) -> Self {
+        if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            Failure::Retry(status.to_string())
+        } else {
+            Failure::GiveUp(status.to_string())
+        }
+    }
+}
+
+/// Find the target's webmention endpoint and notify it.
+async fn deliver(from: &str, to: &str) -> Result<(), Failure> {
+    let response = CLIENT
+        .get(to)
+        .send()
+        .await
+        .map_err(|e| Failure::Retry(e.to_string()))?;
+    if !response.status().is_success() {
+        return Err(Failure::from_status(response.status()));
+    }
+    // Relative endpoints are relative to where any redirects ended up.
+    let base = response.url().clone();
+    let mut endpoint = response
+        .headers()
+        .get_all(reqwest::header::LINK)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .find_map(endpoint_from_link_header)
+        .map(str::to_string);
+    if endpoint.is_none() {
+        let html = response
+            .text()
+            .await
+            .map_err(|e| Failure::Retry(e.to_string()))?;
+        endpoint = endpoint_from_html(&html);
+    }
+    let endpoint = endpoint
+        .and_then(|endpoint| base.join(&endpoint).ok())
+        .filter(|endpoint| matches!(endpoint.scheme(), "http" | "https"))
+        .ok_or_else(|| Failure::GiveUp("No webmention endpoint".to_string()))?;
+    let response = CLIENT
+        .post(endpoint)
+        .form(&[("source", from), ("target", to)])
+        .send()
+        .await
+        .map_err(|e| Failure::Retry(e.to_string()))?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(Failure::from_status(response.status()))
+    }
+}
+
+/// The webmention endpoint in a `Link` header, like `<https://example.com/wm>; rel="webmention"`.
+fn endpoint_from_link_header(header: &str) -> Option<&str> {
+    header.split(',').find_map(|link| {
+        let (target, params) = link.split_once(';')?;
+        let target = target.trim().strip_prefix('<')?.strip_suffix('>')?;
+        params
+            .split(';')
+            .filter_map(|param| param.split_once('='))
+            .any(|(key, value)| {
+                key.trim() == "rel"
+                    && value
+                        .trim()
+                        .trim_matches('"')
+                        .split_whitespace()
+                        .any(|rel| rel == "webmention")
+            })
+            .then_some(target)
+    })
+}
+
+/// The first webmention endpoint a page's `<link>` or `<a>` elements name.
+fn endpoint_from_html(html: &str) -> Option<String> {
+    static ENDPOINTS: LazyLock<Selector> = LazyLock::new(|| {
+        Selector::parse(r#"link[rel~="webmention"][href], a[rel~="webmention"][href]"#).unwrap()
+    });
+    Html::parse_document(html)
+        .select(&ENDPOINTS)
+        .next()
+        .and_then(|el| el.attr("href"))
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{serve, serve_html, serve_with};
+
+    #[rocket::async_test]
+    async fn mentions_outlast_reconnecting() {
+        let path =
+            std::env::temp_dir().join(format!("wolog-test-reconnect-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let url = format!("sqlite://{}", path.display());
+        let count = |pool: Pool<Sqlite>| async move {
+            let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM received_mentions")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+            pool.close().await;
+            count
+        };
+
+        let pool = open(connect_options(Some(url.clone())).unwrap())
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO received_mentions (from_url, to_path) VALUES ($1, $2)")
+            .bind("https://elsewhere.example/")
+            .bind("/post")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count(pool).await, 1);
+        let pool = open(connect_options(Some(url)).unwrap()).await.unwrap();
+        assert_eq!(count(pool).await, 1);
+
+        // Only asking for it keeps mentions in memory, where they're gone after reconnecting.
+        let memory = || async {
+            open(connect_options(Some("sqlite::memory:".to_string())).unwrap())
+                .await
+                .unwrap()
+        };
+        let pool = memory().await;
+        sqlx::query("INSERT INTO received_mentions (from_url, to_path) VALUES ('a', 'b')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count(pool).await, 1);
+        assert_eq!(count(memory().await).await, 0);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[rocket::async_test]
+    async fn concurrent_writes_wait_for_each_other() {
+        let path =
+            std::env::temp_dir().join(format!("wolog-test-concurrent-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let url = format!("sqlite://{}", path.display());
+        let pool = open(connect_options(Some(url)).unwrap()).await.unwrap();
+
+        let mut writes = rocket::tokio::task::JoinSet::new();
+        for n in 0..64 {
+            let pool = pool.clone();
+            writes.spawn(async move {
+                // Each write is a transaction of its own, holding the lock a little while.
+                let mut transaction = pool.begin().await?;
+                sqlx::query(
+                    "INSERT INTO received_mentions (from_url, to_path) VALUES ($1, '/post')",
+                )
+                .bind(format!("https://example.org/{n}"))
+                .execute(&mut *transaction)
+                .await?;
+                sqlx::query("UPDATE received_mentions SET excerpt = $1 WHERE from_url = $2")
+                    .bind("Hi")
+                    .bind(format!("https://example.org/{n}"))
+                    .execute(&mut *transaction)
+                    .await?;
+                transaction.commit().await
+            });
+        }
+        while let Some(write) = writes.join_next().await {
+            write.unwrap().expect("A concurrent write failed");
+        }
+        let (count,): (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM received_mentions WHERE excerpt = 'Hi'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(count, 64);
+        pool.close().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[rocket::async_test]
+    async fn mentions_received_again_are_updated_in_place() {
+        let to = "/fixture-mention-twice/post";
+        let target = url::absolute_url(to);
+        let content = Arc::new(std::sync::Mutex::new("First".to_string()));
+        let from = serve_with("200 OK", {
+            let content = content.clone();
+            let target = target.clone();
+            move || {
+                format!(
+                    r#"<div class="h-entry"><a class="p-author h-card" href="/me">Ada</a><div class="e-content"><p>{} <a href="{target}">post</a></p></div></div>"#,
+                    content.lock().unwrap()
+                )
+            }
+        })
+        .await;
+        let row = || async {
+            sqlx::query_as::<_, (String, Option<String>)>(
+                "SELECT excerpt, author_name FROM received_mentions WHERE from_url = $1",
+            )
+            .bind(&from)
+            .fetch_all(db().await)
+            .await
+            .unwrap()
+        };
+
+        received_webmention(from.clone(), to.to_string()).await;
+        let first = row().await;
+        let [(excerpt, author)] = &first[..] else {
+            panic!("The mention should be stored once: {first:?}");
+        };
+        assert!(excerpt.starts_with("<p>First "), "{excerpt}");
+        assert_eq!(author.as_deref(), Some("Ada"));
+
+        *content.lock().unwrap() = "Edited".to_string();
+        received_webmention(from.clone(), to.to_string()).await;
+        let second = row().await;
+        let [(excerpt, author)] = &second[..] else {
+            panic!("Receiving it again shouldn't add a row: {second:?}");
+        };
+        assert!(excerpt.starts_with("<p>Edited "), "{excerpt}");
+        assert_eq!(author.as_deref(), Some("Ada"));
+    }
+
+    #[rocket::async_test]
+    async fn mentions_are_listed_newest_first_a_page_at_a_time() {
+        let post = "/fixture-mention-order/post";
+        let slug = "/fixture-mention-order/slug";
+        for (n, received_at) in [(0, 300), (1, 100), (2, 500), (3, 200)] {
+            let from = format!("https://example.org/order-{n}");
+            store_mention(&from, post, None, received_at).await;
+        }
+        // A source mentioning the article at two of its paths is listed once, when it was last
+        // received.
+        store_mention("https://example.org/order-1", slug, None, 400).await;
+
+        let sources = |mentions: Vec<MentionDetail>| -> Vec<String> {
+            mentions
+                .into_iter()
+                .map(|m| {
+                    m.source
+                        .trim_start_matches("https://example.org/")
+                        .to_string()
+                })
+                .collect()
+        };
+        let all = mentions_of(&[post, slug], None, 0).await;
+        assert_eq!(all[1].received_at, Some(400));
+        assert_eq!(sources(all), ["order-2", "order-1", "order-0", "order-3"]);
+        assert_eq!(
+            sources(mentions_of(&[post, slug], Some(2), 0).await),
+            ["order-2", "order-1"]
+        );
+        assert_eq!(
+            sources(mentions_of(&[post, slug], Some(2), 3).await),
+            ["order-3"]
+        );
+    }
+
+    #[rocket::async_test]
+    async fn mention_excerpts_are_stored_sanitized() {
+        let to = "/fixture-mention-excerpt/post";
+        let target = url::absolute_url(to);
+        let from = serve_html(format!(
+            r#"<html><body><p>Not part of the post</p><div class="h-entry">
+            <p class="p-summary">The summary</p>
+            <div class="e-content"><p onclick="steal()">Read <a href="{target}">this</a> and <a href="/mine">mine</a>.</p><script>steal()</script><img src="x.png"><a href="javascript:steal()">Click</a></div>
+            </div></body></html>"#
+        ))
+        .await;
+        received_webmention(from.clone(), to.to_string()).await;
+        let (stored,): (Option<String>,) =
+            sqlx::query_as("SELECT excerpt FROM received_mentions WHERE from_url = $1")
+                .bind(&from)
+                .fetch_one(db().await)
+                .await
+                .unwrap();
+        assert_eq!(
+            stored.as_deref(),
+            Some(format!(
+                r#"<p>Read <a href="{target}" rel="nofollow noopener noreferrer">this</a> and <a href="{from}mine" rel="nofollow noopener noreferrer">mine</a>.</p><a rel="nofollow noopener noreferrer">Click</a>"#
+            ).as_str())
+        );
+
+        // Long posts are cut to a line of text.
+        let long = Html::parse_document(&format!(
+            r#"<div class="h-entry"><div class="e-content"><p><b>{}</b></p></div></div>"#,
+            "word ".repeat(100)
+        ));
+        let short = excerpt(&long, &from).unwrap();
+        assert!(short.starts_with("word word"), "{short}");
+        assert!(short.ends_with("word…"), "{short}");
+        assert!(!short.contains('<'), "{short}");
+        assert!(short.chars().count() <= CONFIG.mention_excerpt_length + 1);
+    }
+
+    #[rocket::async_test]
+    async fn databases_that_cant_be_opened_are_an_error() {
+        let url = "sqlite:///nonexistent-wolog-dir/wolog.db".to_string();
+        assert!(open(connect_options(Some(url)).unwrap()).await.is_err());
+    }
+
+    #[rocket::async_test]
+    async fn stored_mention_targets_are_re_encoded() {
+        let path =
+            std::env::temp_dir().join(format!("wolog-test-encode-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let url = format!("sqlite://{}", path.display());
+        let pool = open(connect_options(Some(url)).unwrap()).await.unwrap();
+        // Targets as they were stored before, straight from the URLs mentions were sent to.
+        for (from, to) in [
+            ("https://a.example/", "my notes/first post"),
+            ("https://b.example/", "100%25 done?"),
+            ("https://c.example/", "caf%C3%A9"),
+            ("https://d.example/", "my notes/second post"),
+            ("https://d.example/", "my%20notes/second%20post"),
+        ] {
+            sqlx::query("INSERT INTO received_mentions (from_url, to_path) VALUES ($1, $2)")
+                .bind(from)
+                .bind(to)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        sqlx::query("INSERT INTO data_migrations VALUES ('encode_mention_paths')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        run_data_migrations(&pool).await.unwrap();
+        let rows: Vec<(String, String)> = sqlx::query_as(
+            "SELECT from_url, to_path FROM received_mentions ORDER BY from_url, to_path",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        let encoded = |from: &str, to: &str| (from.to_string(), url::encode_path(to));
+        assert_eq!(
+            rows,
+            [
+                encoded("https://a.example/", "my notes/first post"),
+                encoded("https://b.example/", "100% done?"),
+                encoded("https://c.example/", "café"),
+                encoded("https://d.example/", "my notes/second post"),
+            ]
+        );
+        let (queued,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM data_migrations")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(queued, 0);
+        pool.close().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    /// The attempts, next attempt and last error of a queued webmention.
+    async fn queued(from: &str, to: &str) -> (i64, Option<i64>, Option<String>) {
+        sqlx::query_as(
+            "SELECT attempts, next_attempt, last_error FROM outgoing_mentions WHERE source = $1 AND target = $2",
+        )
+        .bind(from)
+        .bind(to)
+        .fetch_one(db().await)
+        .await
+        .unwrap()
+    }
+
+    #[rocket::async_test]
+    async fn queued_webmentions_are_sent_or_backed_off() {
+        let from = "https://wolo.dev/queue-test".to_string();
+        // A target whose endpoint is itself, accepting the mention.
+        let accepting = serve_html(r#"<link rel="webmention" href="/endpoint">"#.to_string()).await;
+        let failing = serve("503 Service Unavailable", String::new()).await;
+        let now = unix_now();
+        send_webmention(from.clone(), accepting.clone(), 1, now).await;
+        send_webmention(from.clone(), failing.clone(), 1, now + 3600).await;
+        assert_eq!(queued(&from, &accepting).await, (0, Some(now), None));
+
+        send_due_webmentions().await;
+        assert_eq!(queued(&from, &accepting).await, (1, None, None));
+        // Not due yet, so not tried.
+        assert_eq!(queued(&from, &failing).await, (0, Some(now + 3600), None));
+
+        // Queueing the same revision again doesn't send it again.
+        send_webmention(from.clone(), accepting.clone(), 1, now).await;
+        assert_eq!(queued(&from, &accepting).await.1, None);
+
+        let delay = CONFIG.webmention_retry_delay;
+        for attempt in 1..=2 {
+            sqlx::query("UPDATE outgoing_mentions SET next_attempt = 0 WHERE target = $1")
+                .bind(&failing)
+                .execute(db().await)
+                .await
+                .unwrap();
+            // Both targets are on this host, so forget the last send rather than wait it out.
+            LAST_SENT.clear();
+            let before = unix_now();
+            send_due_webmentions().await;
+            let (attempts, next, error) = queued(&from, &failing).await;
+            assert_eq!(attempts, attempt);
+            let backoff = delay << (attempt - 1);
+            assert!(
+                (before + backoff..=unix_now() + backoff).contains(&next.unwrap()),
+                "{next:?}"
+            );
+            assert!(error.unwrap().contains("503"));
+        }
+    }
+}
@@ -0,0 +1,11 @@
+//! Templates and static files baked into the binary by the `embed-assets` feature.
+
+use include_dir::{include_dir, Dir, File};
+
+pub static TEMPLATES: Dir = include_dir!("$CARGO_MANIFEST_DIR/templates");
+pub static STATIC: Dir = include_dir!("$CARGO_MANIFEST_DIR/static");
+
+/// Every file in a directory and its subdirectories.
+pub fn files<'a>(dir: &'a Dir<'a>) -> Box<dyn Iterator<Item = &'a File<'a>> + 'a> {
+    Box::new(dir.files().chain(dir.dirs().flat_map(files)))
+}
@@ -0,0 +1,70 @@
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    io::Cursor,
+};
+
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::{Header, Method, Status},
+    Request, Response,
+};
+
+/// Tags rendered pages and API responses with an `ETag` and answers matching `If-None-Match`
+/// requests with `304 Not Modified`, so polling clients don't download unchanged bodies.
+///
+/// The tag is computed over the uncompressed body, so this must be attached before
+/// [`crate::compress::Compression`]. It's weak since the encoding may differ between responses.
+pub struct ETag;
+
+/// The `ETag` of a response body.
+fn etag(body: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    format!("W/\"{:016x}\"", hasher.finish())
+}
+
+/// Whether an `If-None-Match` header value matches `etag`, comparing weakly.
+fn matches(if_none_match: &str, etag: &str) -> bool {
+    let bare = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    if_none_match
+        .split(',')
+        .any(|tag| tag.trim() == "*" || bare(tag) == bare(etag))
+}
+
+#[rocket::async_trait]
+impl Fairing for ETag {
+    fn info(&self) -> Info {
+        Info {
+            name: "ETag conditional requests",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        if request.method() != Method::Get
+            || response.status() != Status::Ok
+            || response.headers().contains("ETag")
+            || !response
+                .content_type()
+                .is_some_and(|ct| ct.is_html() || ct.is_json() || ct.sub() == "atom+xml")
+        {
+            return;
+        }
+        let Ok(body) = response.body_mut().to_bytes().await else {
+            return;
+        };
+        let etag = etag(&body);
+        let fresh = request
+            .headers()
+            .get("If-None-Match")
+            .any(|header| matches(header, &etag));
+        response.set_header(Header::new("ETag", etag));
+        if fresh {
+            response.set_status(Status::NotModified);
+            response.remove_header("Content-Type");
+            response.set_sized_body(0, Cursor::new(vec![]));
+        } else {
+            response.set_sized_body(body.len(), Cursor::new(body));
+        }
+    }
+}
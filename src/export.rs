@@ -0,0 +1,312 @@
+//! Writing the site out as static files, for hosts that can't run the server.
+
+use std::path::{Path, PathBuf};
+
+use rocket::{
+    http::{ContentType, RawStr, Status},
+    local::asynchronous::Client,
+    tokio, Build, Rocket,
+};
+
+use crate::{
+    article::{self, Search},
+    config::CONFIG,
+    home, url,
+};
+
+/// The directory passed with `--export <dir>` or `--export=<dir>`, if any.
+pub fn requested() -> Option<PathBuf> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--export" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(dir) = arg.strip_prefix("--export=") {
+            return Some(PathBuf::from(dir));
+        }
+    }
+    None
+}
+
+/// Render every published article, the tag pages, feeds and sitemap into `out`, and copy the
+/// static files and assets alongside them.
+///
+/// Pages are fetched from `rocket` as a browser would, so they're rendered exactly as they'd be
+/// served. Each page is written as `index.html` in a directory named after its path, and links
+/// to tag pages and feeds, which the server tells apart by their query strings, are rewritten to
+/// the static files standing in for them. Search, print views and anything needing the server to
+/// respond to a visitor aren't exported.
+///
+/// Returns whether everything was exported.
+pub async fn run(rocket: Rocket<Build>, out: &Path) -> bool {
+    run_under(rocket, Path::new(""), out).await
+}
+
+/// Like [`run`], but only exports the articles under `root`, and the tags they use.
+async fn run_under(rocket: Rocket<Build>, root: &Path, out: &Path) -> bool {
+    let client = match Client::untracked(rocket).await {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Couldn't start the site for exporting: {e}");
+            return false;
+        }
+    };
+    let search = Search {
+        search_path: root.to_path_buf(),
+        include_hidden: true,
+        ..Default::default()
+    };
+    let articles = match article::search_all(&search).await {
+        Ok(articles) => articles,
+        Err(e) => {
+            eprintln!("Couldn't list the articles to export: {e:?}");
+            return false;
+        }
+    };
+    let mut tags: Vec<&str> = articles
+        .iter()
+        .filter(|(_, meta)| meta.is_listed())
+        .flat_map(|(_, meta)| meta.tags.iter().map(String::as_str))
+        .filter(|tag| !tag.is_empty() && !tag.contains('/') && !tag.starts_with('.'))
+        .collect();
+    tags.sort_unstable();
+    tags.dedup();
+
+    let mut export = Export {
+        client,
+        out,
+        rewrites: rewrites(&tags),
+        ok: true,
+    };
+    export.page("/", PathBuf::from("index.html")).await;
+    // The homepage's source is exported as the root page above.
+    for (path, meta) in articles.iter().filter(|(path, _)| !home::is_home(path)) {
+        let page = match &meta.slug {
+            Some(slug) => url::slug_path(path, slug),
+            None => format!("/{}", url::encode_path(&url::article_rel(path))),
+        };
+        let file = page_file(&page);
+        export.page(&page, file).await;
+    }
+    for tag in &tags {
+        let query = RawStr::new(tag).percent_encode();
+        let page = format!("/tags?tags={query}&limit={}", CONFIG.max_search_results);
+        export
+            .page(&page, Path::new("tags").join(tag).join("index.html"))
+            .await;
+        export
+            .page(
+                &format!("/feed?tags={query}"),
+                Path::new("feed").join(format!("{tag}.xml")),
+            )
+            .await;
+    }
+    export
+        .page("/tags/list", PathBuf::from("tags/list/index.html"))
+        .await;
+    export
+        .page("/feeds", PathBuf::from("feeds/index.html"))
+        .await;
+    export.page("/feed", PathBuf::from("feed.xml")).await;
+    export
+        .page("/sitemap.xml", PathBuf::from("sitemap.xml"))
+        .await;
+
+    // Copied last, so images resized while rendering are included.
+    export.ok &= copy_dir(Path::new("articles/assets"), &out.join("assets"));
+    export.ok &= copy_static(&out.join("static"));
+    println!("Exported {} articles to {out:?}", articles.len());
+    export.ok
+}
+
+/// Links to pages the server distinguishes by query string, and the static files replacing them.
+fn rewrites(tags: &[&str]) -> Vec<(String, String)> {
+    let base = url::base_path();
+    let mut rewrites = vec![(format!("\"{base}/feed\""), format!("\"{base}/feed.xml\""))];
+    // The primary feed is what `/feed` redirects to, which is exported as `feed.xml`.
+    if !url::primary_feed_is_default() {
+        rewrites.push((
+            format!("\"{}\"", url::primary_feed()),
+            format!("\"{base}/feed.xml\""),
+        ));
+    }
+    for tag in tags {
+        let encoded = RawStr::new(tag).percent_encode().to_string();
+        let mut forms = vec![tag.to_string()];
+        if encoded != *tag {
+            forms.push(encoded.clone());
+        }
+        for form in forms {
+            rewrites.push((
+                format!("\"{base}/tags?tags={form}\""),
+                format!("\"{base}/tags/{encoded}/\""),
+            ));
+            rewrites.push((
+                format!("\"{base}/feed?tags={form}\""),
+                format!("\"{base}/feed/{encoded}.xml\""),
+            ));
+        }
+    }
+    rewrites
+}
+
+/// Where the page at a site-relative path is written.
+fn page_file(page: &str) -> PathBuf {
+    let page = RawStr::new(page).percent_decode_lossy();
+    let page = page.trim_matches('/');
+    if page.is_empty() {
+        PathBuf::from("index.html")
+    } else {
+        Path::new(page).join("index.html")
+    }
+}
+
+struct Export<'a> {
+    client: Client,
+    out: &'a Path,
+    rewrites: Vec<(String, String)>,
+    ok: bool,
+}
+
+impl Export<'_> {
+    /// Fetch `uri`, following redirects, and write it to `file` in the output directory.
+    async fn page(&mut self, uri: &str, file: PathBuf) {
+        let mut uri = uri.to_string();
+        for _ in 0..5 {
+            let response = self.client.get(uri.clone()).dispatch().await;
+            if response.status().class().is_redirection() {
+                let Some(location) = response.headers().get_one("Location") else {
+                    break;
+                };
+                uri = location
+                    .strip_prefix(url::base_path())
+                    .unwrap_or(location)
+                    .to_string();
+                continue;
+            }
+            if response.status() != Status::Ok {
+                eprintln!("Exporting {uri} failed with {}", response.status());
+                break;
+            }
+            let html = response.content_type() == Some(ContentType::HTML);
+            let Some(mut body) = response.into_bytes().await else {
+                break;
+            };
+            if html {
+                let mut text = String::from_utf8_lossy(&body).into_owned();
+                for (from, to) in &self.rewrites {
+                    text = text.replace(from.as_str(), to);
+                }
+                body = text.into_bytes();
+            }
+            let file = self.out.join(file);
+            let written = async {
+                if let Some(dir) = file.parent() {
+                    tokio::fs::create_dir_all(dir).await?;
+                }
+                tokio::fs::write(&file, body).await
+            }
+            .await;
+            match written {
+                Ok(()) => return,
+                Err(e) => {
+                    eprintln!("Couldn't write {file:?}: {e}");
+                    break;
+                }
+            }
+        }
+        self.ok = false;
+    }
+}
+
+/// Copy a directory's files into `to`, returning whether they all copied.
+fn copy_dir(from: &Path, to: &Path) -> bool {
+    let mut ok = true;
+    for entry in walkdir::WalkDir::new(from)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+    {
+        let Ok(rel) = entry.path().strip_prefix(from) else {
+            continue;
+        };
+        let target = to.join(rel);
+        let copied = target
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::copy(entry.path(), &target));
+        if let Err(e) = copied {
+            eprintln!("Couldn't copy {:?} to {target:?}: {e}", entry.path());
+            ok = false;
+        }
+    }
+    ok
+}
+
+#[cfg(not(feature = "embed-assets"))]
+fn copy_static(to: &Path) -> bool {
+    copy_dir(Path::new("static"), to)
+}
+
+#[cfg(feature = "embed-assets")]
+fn copy_static(to: &Path) -> bool {
+    let mut ok = true;
+    for file in crate::embedded::files(&crate::embedded::STATIC) {
+        let target = to.join(file.path());
+        let written = target
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&target, file.contents()));
+        if let Err(e) = written {
+            eprintln!("Couldn't write {target:?}: {e}");
+            ok = false;
+        }
+    }
+    ok
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Fixture;
+
+    #[rocket::async_test]
+    async fn fixture_trees_export_to_static_files() {
+        let fixture = Fixture::new("export");
+        fixture
+            .article(
+                "post",
+                "---\ntitle: Exported\nstatus: published\ntags: [exported-tag]\n---\n\nWords on disk.\n",
+            )
+            .await;
+        fixture
+            .article(
+                "nested/deeper",
+                "---\ntitle: Deeper\nstatus: published\n---\n\nFurther down.\n",
+            )
+            .await;
+        let out = std::env::temp_dir().join(format!("wolog-export-{}", std::process::id()));
+        let ok = run_under(crate::rocket(), Path::new(&fixture.rel("")), &out).await;
+        let read = |rel: &str| std::fs::read_to_string(out.join(rel)).unwrap_or_default();
+
+        let post = read(&format!("{}/post/index.html", fixture.rel("")));
+        assert!(post.contains("Words on disk."), "{post}");
+        // Tag links point at the exported tag pages rather than query strings.
+        assert!(post.contains("/tags/exported-tag/\""), "{post}");
+        assert!(!post.contains("/tags?tags=exported-tag\""), "{post}");
+        let deeper = read(&format!("{}/nested/deeper/index.html", fixture.rel("")));
+        assert!(deeper.contains("Further down."), "{deeper}");
+        assert!(read("tags/exported-tag/index.html").contains("Exported"));
+        assert!(read("feed/exported-tag.xml").contains("<title>Exported</title>"));
+        for file in [
+            "index.html",
+            "feed.xml",
+            "sitemap.xml",
+            "tags/list/index.html",
+        ] {
+            assert!(out.join(file).is_file(), "{file}");
+        }
+        std::fs::remove_dir_all(&out).unwrap();
+        assert!(ok);
+    }
+}
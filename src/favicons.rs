@@ -0,0 +1,145 @@
+//! Icons of the sites articles link to, for articles with `link_favicons`.
+
+use std::{
+    path::Path,
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+use rocket::tokio;
+
+use crate::url;
+
+/// Where fetched icons are kept, inside the assets directory so they're served with it and
+/// readers' browsers never contact the linked sites.
+const ICON_DIR: &str = "_favicons";
+/// The largest icon kept, in bytes.
+const MAX_SIZE: usize = 64 * 1024;
+/// How long a site whose icon couldn't be fetched is left before trying again.
+const RETRY_AFTER: Duration = Duration::from_secs(60 * 60);
+/// Icon types and the extensions they're saved with. SVG is left out, since an SVG served from
+/// the site could run scripts on it.
+const TYPES: &[(&str, &str)] = &[
+    ("image/x-icon", "ico"),
+    ("image/vnd.microsoft.icon", "ico"),
+    ("image/png", "png"),
+    ("image/gif", "gif"),
+    ("image/jpeg", "jpg"),
+    ("image/webp", "webp"),
+];
+
+/// Icon URLs by host, or when fetching a host's icon last failed.
+static ICONS: LazyLock<DashMap<String, Result<String, Instant>>> = LazyLock::new(DashMap::new);
+
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .user_agent(concat!("wolog/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .expect("Couldn't build the favicon HTTP client")
+});
+
+/// The absolute URL of the icon of the site `link` is on, fetching it if it isn't on disk yet.
+///
+/// Returns `None` if the link isn't to a web page or the site has no usable `/favicon.ico`.
+pub async fn favicon(link: &str) -> Option<String> {
+    let host = url::parse_http_url(link)?.host_str()?.to_string();
+    match ICONS.get(&host).map(|icon| icon.clone()) {
+        Some(Ok(icon)) => return Some(icon),
+        Some(Err(failed)) if failed.elapsed() < RETRY_AFTER => return None,
+        _ => {}
+    }
+    let icon = match on_disk(&host) {
+        Some(rel) => Ok(rel),
+        None => fetch(&host).await.inspect_err(|e| {
+            eprintln!("Couldn't fetch the favicon of {host}: {e}");
+        }),
+    };
+    let icon = icon.map(|rel| url::absolute_url(&format!("assets/{ICON_DIR}/{rel}")));
+    ICONS.insert(host, icon.clone().map_err(|_| Instant::now()));
+    icon.ok()
+}
+
+/// The name of an icon fetched for `host` before, if there is one.
+fn on_disk(host: &str) -> Option<String> {
+    let dir = Path::new("articles/assets").join(ICON_DIR);
+    TYPES
+        .iter()
+        .map(|(_, ext)| format!("{host}.{ext}"))
+        .find(|name| dir.join(name).is_file())
+}
+
+/// Fetch `host`'s icon into the assets directory, returning its file name.
+async fn fetch(host: &str) -> Result<String, String> {
+    let response = CLIENT
+        .get(format!("https://{host}/favicon.ico"))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| e.to_string())?;
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|ct| ct.to_str().ok())
+        .and_then(|ct| ct.split(';').next())
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let Some((_, ext)) = TYPES.iter().find(|(ty, _)| *ty == content_type) else {
+        return Err(format!("it's {content_type:?}, not an image"));
+    };
+    let bytes = response.bytes().await.map_err(|e| e.to_string())?;
+    if bytes.is_empty() || bytes.len() > MAX_SIZE {
+        return Err(format!("it's {} bytes", bytes.len()));
+    }
+    let name = format!("{host}.{ext}");
+    let dir = Path::new("articles/assets").join(ICON_DIR);
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| e.to_string())?;
+    tokio::fs::write(dir.join(&name), bytes)
+        .await
+        .map_err(|e| e.to_string())?;
+    println!("Fetched the favicon of {host}");
+    Ok(name)
+}
+
+/// The image shown before a link to a site with an icon.
+pub fn img(icon: &str) -> String {
+    format!(
+        "<img class=\"favicon\" src=\"{icon}\" alt=\"\" width=\"16\" height=\"16\" loading=\"lazy\">"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{article::get_article, testing::Fixture};
+
+    use super::*;
+
+    #[rocket::async_test]
+    async fn external_links_get_icons_only_when_asked() {
+        // The icon's already on disk, so nothing's fetched.
+        let host = "favicon-test.example";
+        let dir = Path::new("articles/assets").join(ICON_DIR);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(format!("{host}.png")), b"png").unwrap();
+
+        let fixture = Fixture::new("favicons");
+        let article = |favicons: bool| {
+            format!("---\ntitle: Links\nstatus: published\nlink_favicons: {favicons}\n---\n\n[away](https://{host}/page) [near](/post)\n")
+        };
+        let with = fixture.article("with", &article(true)).await;
+        let without = fixture.article("without", &article(false)).await;
+
+        let icon = img(&url::absolute_url(&format!("assets/{ICON_DIR}/{host}.png")));
+        let content = get_article(&with).await.unwrap().content.clone();
+        assert_eq!(content.matches("class=\"favicon\"").count(), 1, "{content}");
+        assert!(content.contains(&format!("{icon}away</a>")), "{content}");
+        let content = get_article(&without).await.unwrap().content.clone();
+        assert!(!content.contains("class=\"favicon\""), "{content}");
+
+        let _ = std::fs::remove_file(dir.join(format!("{host}.png")));
+    }
+}
@@ -0,0 +1,86 @@
+//! Feeds as last built, so feed readers polling an unchanged feed don't have every article in it
+//! loaded and serialized again.
+
+use std::{
+    path::Path,
+    sync::{Arc, LazyLock},
+    time::SystemTime,
+};
+
+use chrono::{DateTime, FixedOffset};
+use dashmap::DashMap;
+
+use crate::{article::ArticleMeta, roots};
+
+const MAX_CACHED: usize = 256;
+
+/// A built feed.
+#[derive(Debug)]
+pub struct CachedFeed {
+    /// The Atom document.
+    pub xml: String,
+    /// When the newest article in it was last updated.
+    pub updated: DateTime<FixedOffset>,
+}
+
+/// What a feed was built from: the site-relative paths of the articles in it with when their
+/// files were last modified, and whether there was another page.
+#[derive(PartialEq, Eq, Debug)]
+pub struct Fingerprint {
+    articles: Vec<(Arc<Path>, Option<SystemTime>)>,
+    capped: bool,
+}
+
+impl Fingerprint {
+    pub fn new(articles: &[(Arc<Path>, Arc<ArticleMeta>)], capped: bool) -> Self {
+        let articles = articles
+            .iter()
+            .map(|(path, _)| {
+                let modified = std::fs::metadata(roots::disk_path(path))
+                    .and_then(|m| m.modified())
+                    .ok();
+                (path.clone(), modified)
+            })
+            .collect();
+        Self { articles, capped }
+    }
+}
+
+/// Feeds by the URI they were requested at.
+static FEEDS: LazyLock<DashMap<String, (Fingerprint, Arc<CachedFeed>)>> =
+    LazyLock::new(DashMap::new);
+
+/// The feed last built for `key`, if it was built from the same articles, none of which have
+/// changed since.
+pub fn get(key: &str, fingerprint: &Fingerprint) -> Option<Arc<CachedFeed>> {
+    FEEDS
+        .get(key)
+        .filter(|entry| entry.0 == *fingerprint)
+        .map(|entry| entry.1.clone())
+}
+
+/// How many times each key's feed was built, for tests to tell whether the cache was used.
+#[cfg(test)]
+static BUILDS: LazyLock<DashMap<String, usize>> = LazyLock::new(DashMap::new);
+
+/// How many times the feed at `uri` was built rather than taken from the cache.
+#[cfg(test)]
+pub fn builds(uri: &str) -> usize {
+    BUILDS
+        .iter()
+        .filter(|entry| entry.key().split(' ').next() == Some(uri))
+        .map(|entry| *entry.value())
+        .sum()
+}
+
+/// Remember the feed built for `key`.
+pub fn insert(key: String, fingerprint: Fingerprint, feed: Arc<CachedFeed>) {
+    #[cfg(test)]
+    {
+        *BUILDS.entry(key.clone()).or_default() += 1;
+    }
+    if FEEDS.len() >= MAX_CACHED {
+        FEEDS.clear();
+    }
+    FEEDS.insert(key, (fingerprint, feed));
+}
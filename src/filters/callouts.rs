@@ -0,0 +1,133 @@
+use pandoc_ast::{Block, Format, MutVisitor, Pandoc};
+use rocket_dyn_templates::tera::escape_html;
+use serde::Deserialize;
+
+use crate::config::CONFIG;
+
+/// A kind of callout, written as a fenced div with its class, like `::: warning`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Callout {
+    /// The div class that makes a callout of this kind.
+    pub class: String,
+    /// The title shown at its top, unless the div gives a `title` of its own.
+    pub label: String,
+    /// Shown before the title.
+    #[serde(default)]
+    pub icon: String,
+}
+
+impl Callout {
+    fn new(class: &str, label: &str, icon: &str) -> Self {
+        Self {
+            class: class.to_string(),
+            label: label.to_string(),
+            icon: icon.to_string(),
+        }
+    }
+
+    /// GitHub's kinds of alert.
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self::new("note", "Note", "ℹ️"),
+            Self::new("tip", "Tip", "💡"),
+            Self::new("important", "Important", "❗"),
+            Self::new("warning", "Warning", "⚠️"),
+            Self::new("caution", "Caution", "🛑"),
+        ]
+    }
+}
+
+/// Turn fenced divs of the configured callout classes into callouts: a `callout` div, also
+/// classed `callout-<class>`, headed by the callout's icon and title.
+///
+/// Divs of other classes are left alone.
+pub fn callouts(mut ast: Pandoc) -> Pandoc {
+    struct CalloutVisitor;
+    impl MutVisitor for CalloutVisitor {
+        fn visit_block(&mut self, block: &mut Block) {
+            self.walk_block(block);
+            let Block::Div((_, classes, attributes), contents) = block else {
+                return;
+            };
+            let Some(callout) = CONFIG
+                .callouts
+                .iter()
+                .find(|callout| classes.contains(&callout.class))
+            else {
+                return;
+            };
+            let title = match attributes.iter().position(|(key, _)| key == "title") {
+                Some(i) => attributes.remove(i).1,
+                None => callout.label.clone(),
+            };
+            let icon = if callout.icon.is_empty() {
+                String::new()
+            } else {
+                format!(
+                    "<span class=\"callout-icon\" aria-hidden=\"true\">{}</span> ",
+                    escape_html(&callout.icon)
+                )
+            };
+            let heading = format!(
+                "<p class=\"callout-title\">{icon}{}</p>",
+                escape_html(&title)
+            );
+            contents.insert(0, Block::RawBlock(Format("html".to_string()), heading));
+            classes.retain(|class| *class != callout.class);
+            classes.splice(
+                0..0,
+                ["callout".to_string(), format!("callout-{}", callout.class)],
+            );
+            attributes.push(("role".to_string(), "note".to_string()));
+        }
+    }
+    CalloutVisitor.walk_pandoc(&mut ast);
+    ast
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warning_divs_become_labelled_callouts() {
+        let ast = Pandoc::from_json(
+            r#"{"pandoc-api-version":[1,23,1],"meta":{},"blocks":[
+                {"t":"Div","c":[["",["warning","wide"],[]],[
+                    {"t":"Para","c":[{"t":"Str","c":"Careful"}]}
+                ]]},
+                {"t":"Div","c":[["",["tip"],[["title","Pro tip"]]],[]]},
+                {"t":"Div","c":[["",["aside"],[]],[]]}
+            ]}"#,
+        );
+        let ast = callouts(ast);
+
+        let Block::Div((_, classes, attributes), contents) = &ast.blocks[0] else {
+            panic!("The callout should still be a div");
+        };
+        assert_eq!(classes, &["callout", "callout-warning", "wide"]);
+        assert_eq!(attributes, &[("role".to_string(), "note".to_string())]);
+        let [Block::RawBlock(_, title), Block::Para(_)] = &contents[..] else {
+            panic!("The callout should be headed by its title: {contents:?}");
+        };
+        assert_eq!(
+            title,
+            r#"<p class="callout-title"><span class="callout-icon" aria-hidden="true">⚠️</span> Warning</p>"#
+        );
+
+        // A title of the div's own replaces the label.
+        let Block::Div((_, _, attributes), contents) = &ast.blocks[1] else {
+            panic!("The callout should still be a div");
+        };
+        assert!(!attributes.iter().any(|(key, _)| key == "title"));
+        assert!(
+            matches!(&contents[0], Block::RawBlock(_, title) if title.ends_with(" Pro tip</p>"))
+        );
+
+        let Block::Div((_, classes, attributes), contents) = &ast.blocks[2] else {
+            panic!("Other divs should be left alone");
+        };
+        assert_eq!(classes, &["aside"]);
+        assert!(attributes.is_empty() && contents.is_empty());
+    }
+}
@@ -0,0 +1,99 @@
+use std::sync::LazyLock;
+
+use pandoc_ast::{Block, Format, MutVisitor, Pandoc};
+use rocket_dyn_templates::tera::escape_html;
+use serde::Deserialize;
+use syntect::{
+    easy::HighlightLines,
+    highlighting::ThemeSet,
+    html::{styled_line_to_highlighted_html, IncludeBackground},
+    parsing::SyntaxSet,
+    util::LinesWithEndings,
+};
+
+use super::code_header;
+use crate::config::CONFIG;
+
+/// What highlights code blocks.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Highlighter {
+    /// Pandoc's own highlighting, when rendering with pandoc.
+    #[default]
+    Pandoc,
+    /// syntect, which gives the same results with either renderer.
+    Syntect,
+}
+
+/// Used if the configured theme doesn't exist.
+const FALLBACK_THEME: &str = "InspiredGitHub";
+
+static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+static THEMES: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
+
+/// Highlight `code` as `lang` with syntect, if it knows the language.
+///
+/// The result is wrapped like [`super::wrap_code_blocks`] wraps other blocks, and numbered if
+/// `line_numbers` is configured.
+pub fn highlight(lang: &str, code: &str) -> Option<String> {
+    let syntax = SYNTAXES.find_syntax_by_token(lang)?;
+    let theme = THEMES
+        .themes
+        .get(&CONFIG.highlight_theme)
+        .or_else(|| THEMES.themes.get(FALLBACK_THEME))?;
+    let mut highlighter = HighlightLines::new(syntax, theme);
+    let mut html = format!(
+        "<div class=\"code-block\" data-lang=\"{}\">{}<pre class=\"highlight\"><code>",
+        escape_html(lang),
+        code_header(lang)
+    );
+    for (number, line) in LinesWithEndings::from(code).enumerate() {
+        let regions = highlighter.highlight_line(line, &SYNTAXES).ok()?;
+        if CONFIG.line_numbers {
+            html += &format!("<span class=\"line-number\">{}</span>", number + 1);
+        }
+        html += &styled_line_to_highlighted_html(&regions, IncludeBackground::No).ok()?;
+    }
+    html += "</code></pre></div>";
+    Some(html)
+}
+
+/// Replace code blocks in languages syntect knows with highlighted HTML.
+pub fn highlight_code(mut ast: Pandoc) -> Pandoc {
+    struct HighlightVisitor;
+    impl MutVisitor for HighlightVisitor {
+        fn visit_block(&mut self, block: &mut Block) {
+            let Block::CodeBlock((_, classes, _), code) = block else {
+                return self.walk_block(block);
+            };
+            if let Some(html) = classes.first().and_then(|lang| highlight(lang, code)) {
+                *block = Block::RawBlock(Format("html".to_string()), html);
+            }
+        }
+    }
+    HighlightVisitor.walk_pandoc(&mut ast);
+    ast
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rust_blocks_get_highlighted_spans() {
+        let ast = Pandoc::from_json(
+            r#"{"pandoc-api-version":[1,23,1],"meta":{},"blocks":[
+                {"t":"CodeBlock","c":[["",["rust"],[]],"fn main() {\n    let x = 1;\n}\n"]},
+                {"t":"CodeBlock","c":[["",["no-such-language"],[]],"plain"]}
+            ]}"#,
+        );
+        let ast = highlight_code(ast);
+        let Block::RawBlock(_, html) = &ast.blocks[0] else {
+            panic!("The rust block should be highlighted");
+        };
+        assert!(html.contains(r#"data-lang="rust""#));
+        assert!(html.contains("<span style="));
+        assert!(html.contains(">main</span>"), "{html}");
+        assert!(matches!(ast.blocks[1], Block::CodeBlock(..)));
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::{hash_map::Entry, HashMap};
+
+use pandoc_ast::{Format, Inline, MetaValue, MutVisitor, Pandoc};
+use serde::Deserialize;
+
+use crate::{config::CONFIG, favicons, url::is_external};
+
+/// How links off the site are marked. They're always given the `external` class.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ExternalLinks {
+    /// Open them in a new tab, without giving the other site a handle on this one.
+    pub new_tab: bool,
+    /// Follow them with an icon.
+    pub icon: bool,
+}
+
+impl Default for ExternalLinks {
+    fn default() -> Self {
+        Self {
+            new_tab: true,
+            icon: false,
+        }
+    }
+}
+
+/// The icon after external links, if `icon` is configured.
+pub const EXTERNAL_ICON: &str =
+    "<span class=\"external-icon\" role=\"img\" aria-label=\"(external link)\">↗</span>";
+
+/// The attributes added to an external link, besides its class.
+pub fn external_attributes() -> Vec<(String, String)> {
+    if CONFIG.external_links.new_tab {
+        vec![
+            ("target".to_string(), "_blank".to_string()),
+            ("rel".to_string(), "noopener noreferrer".to_string()),
+        ]
+    } else {
+        vec![]
+    }
+}
+
+/// Mark links off the site as `external_links` asks, keeping their other classes and
+/// attributes.
+pub fn external_links(mut ast: Pandoc) -> Pandoc {
+    struct ExternalVisitor;
+    impl MutVisitor for ExternalVisitor {
+        fn visit_inline(&mut self, inline: &mut Inline) {
+            self.walk_inline(inline);
+            let Inline::Link((_, classes, attributes), contents, (target, _)) = inline else {
+                return;
+            };
+            if !is_external(target) {
+                return;
+            }
+            classes.push("external".to_string());
+            for (key, value) in external_attributes() {
+                match attributes.iter_mut().find(|(k, _)| *k == key) {
+                    // Keep whatever `rel` the author gave, like `me`, alongside.
+                    Some((_, existing)) if key == "rel" => {
+                        *existing = format!("{existing} {value}");
+                    }
+                    Some(_) => {}
+                    None => attributes.push((key, value)),
+                }
+            }
+            if CONFIG.external_links.icon {
+                contents.push(Inline::RawInline(
+                    Format("html".to_string()),
+                    EXTERNAL_ICON.to_string(),
+                ));
+            }
+        }
+    }
+    ExternalVisitor.walk_pandoc(&mut ast);
+    ast
+}
+
+/// Put the linked site's icon before each external link, if the article asks for
+/// `link_favicons`. Links to sites without a usable icon are left as they are.
+pub async fn link_favicons(mut ast: Pandoc) -> Pandoc {
+    if !matches!(
+        ast.meta.get("link_favicons"),
+        Some(MetaValue::MetaBool(true))
+    ) {
+        return ast;
+    }
+    struct TargetVisitor(Vec<String>);
+    impl MutVisitor for TargetVisitor {
+        fn visit_inline(&mut self, inline: &mut Inline) {
+            if let Inline::Link(_, _, (target, _)) = inline {
+                if is_external(target) {
+                    self.0.push(target.clone());
+                }
+            }
+            self.walk_inline(inline)
+        }
+    }
+    let mut targets = TargetVisitor(vec![]);
+    targets.walk_pandoc(&mut ast);
+    let mut icons = HashMap::new();
+    for target in targets.0 {
+        if let Entry::Vacant(entry) = icons.entry(target) {
+            let icon = favicons::favicon(entry.key()).await;
+            entry.insert(icon);
+        }
+    }
+
+    struct IconVisitor(HashMap<String, Option<String>>);
+    impl MutVisitor for IconVisitor {
+        fn visit_inline(&mut self, inline: &mut Inline) {
+            self.walk_inline(inline);
+            let Inline::Link(_, contents, (target, _)) = inline else {
+                return;
+            };
+            if let Some(Some(icon)) = self.0.get(target) {
+                contents.insert(
+                    0,
+                    Inline::RawInline(Format("html".to_string()), favicons::img(icon)),
+                );
+            }
+        }
+    }
+    IconVisitor(icons).walk_pandoc(&mut ast);
+    ast
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[rocket::async_test]
+    async fn only_links_off_the_site_are_marked_external() {
+        let own = format!("{}/elsewhere", CONFIG.url.trim_end_matches('/'));
+        let ast = Pandoc::from_json(&format!(
+            r#"{{"pandoc-api-version":[1,23,1],"meta":{{}},"blocks":[{{"t":"Para","c":[
+                {{"t":"Link","c":[["",[],[["rel","me"]]],[{{"t":"Str","c":"away"}}],["https://example.org/",""]]}},
+                {{"t":"Link","c":[["",[],[]],[{{"t":"Str","c":"home"}}],["{own}",""]]}},
+                {{"t":"Link","c":[["",[],[]],[{{"t":"Str","c":"near"}}],["/post",""]]}}
+            ]}}]}}"#
+        ));
+        let ast = external_links(ast);
+        let pandoc_ast::Block::Para(inlines) = &ast.blocks[0] else {
+            panic!("The paragraph should be kept");
+        };
+        let links: Vec<_> = inlines
+            .iter()
+            .map(|inline| match inline {
+                Inline::Link((_, classes, attributes), ..) => (classes.clone(), attributes.clone()),
+                inline => panic!("Only links were written: {inline:?}"),
+            })
+            .collect();
+        let pair = |key: &str, value: &str| (key.to_string(), value.to_string());
+        assert_eq!(
+            links[0],
+            (
+                vec!["external".to_string()],
+                vec![
+                    pair("rel", "me noopener noreferrer"),
+                    pair("target", "_blank")
+                ]
+            )
+        );
+        assert_eq!(links[1], (vec![], vec![]));
+        assert_eq!(links[2], (vec![], vec![]));
+
+        // Markdown rendered without pandoc marks them the same way.
+        let markdown = format!("[away](https://example.org/) [home]({own}) [near](/post)");
+        let html = crate::article::preview(&markdown).await.unwrap();
+        assert!(
+            html.contains(r#"<a href="https://example.org/" class="external" target="_blank" rel="noopener noreferrer">away</a>"#),
+            "{html}"
+        );
+        assert!(
+            html.contains(&format!(r#"<a href="{own}">home</a>"#)),
+            "{html}"
+        );
+        assert!(html.contains(r#"<a href="/post">near</a>"#), "{html}");
+    }
+}
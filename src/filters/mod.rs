@@ -1,80 +1,368 @@
 use std::{
-    ops::Bound,
-    path::{Path, PathBuf},
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
-    },
+    collections::HashMap,
+    path::Path,
+    str::FromStr,
+    sync::{Arc, LazyLock, Mutex, RwLock},
+    time::SystemTime,
 };
 
-use crate::article::Search;
-use chrono::{Local, NaiveDate};
-use pandoc_ast::{Attr, Block, Format, Inline, MetaValue, MutVisitor, Pandoc};
-use rocket::tokio::{
-    runtime::{Handle, Runtime},
-    task::spawn_blocking,
+use crate::{
+    article::{
+        error::ArticleError, in_rendering_chain, record_dependencies, rendering_chain, Query,
+        Search,
+    },
+    config::CONFIG,
+    related::{Related, RelatedBy},
+    timezone,
+};
+use chrono::NaiveDate;
+use pandoc_ast::{Block, Format, Inline, MetaValue, MutVisitor, Pandoc};
+use rocket::{
+    response::content::RawHtml,
+    tokio::{runtime::Handle, task::spawn_blocking},
 };
 use rocket_dyn_templates::{
     context,
-    tera::{Context, Tera},
-    Template,
+    tera::{self, Context, Tera, Value},
 };
-use serde::{de::Visitor, Deserialize, Serialize};
-
-lazy_static::lazy_static! {
-    static ref TERA: Tera = {
-        let mut tera = Tera::default();
-        let files = walkdir::WalkDir::new("./templates").into_iter().flatten().filter(|f| f.file_type().is_file()).map(|file| {
-            (file.path().to_path_buf(), Some(file.file_name().to_string_lossy().trim_end_matches(".html.tera").to_string()))
-        });
-        tera.add_template_files(files).unwrap();
-        tera
+use serde::Serialize;
+
+mod callouts;
+mod highlight;
+mod links;
+
+pub use callouts::Callout;
+pub use highlight::{highlight, Highlighter};
+pub use links::{external_attributes, ExternalLinks, EXTERNAL_ICON};
+
+static TERA: LazyLock<RwLock<Tera>> = LazyLock::new(|| {
+    *LAST_TEMPLATE_LOAD.lock().unwrap() = SystemTime::now();
+    RwLock::new(load_templates().unwrap())
+});
+static LAST_TEMPLATE_LOAD: Mutex<SystemTime> = Mutex::new(SystemTime::UNIX_EPOCH);
+
+fn template_files() -> impl Iterator<Item = walkdir::DirEntry> {
+    walkdir::WalkDir::new("./templates")
+        .into_iter()
+        .flatten()
+        .filter(|f| f.file_type().is_file())
+}
+
+#[cfg(feature = "embed-assets")]
+fn load_templates() -> tera::Result<Tera> {
+    let mut tera = Tera::default();
+    let files = crate::embedded::files(&crate::embedded::TEMPLATES).filter_map(|file| {
+        let name = file.path().file_name()?.to_string_lossy();
+        Some((
+            name.trim_end_matches(".html.tera").to_string(),
+            file.contents_utf8()?,
+        ))
+    });
+    tera.add_raw_templates(files)?;
+    register(&mut tera);
+    Ok(tera)
+}
+
+#[cfg(not(feature = "embed-assets"))]
+fn load_templates() -> tera::Result<Tera> {
+    let mut tera = Tera::default();
+    let files = template_files().map(|file| {
+        (
+            file.path().to_path_buf(),
+            Some(
+                file.file_name()
+                    .to_string_lossy()
+                    .trim_end_matches(".html.tera")
+                    .to_string(),
+            ),
+        )
+    });
+    tera.add_template_files(files)?;
+    register(&mut tera);
+    Ok(tera)
+}
+
+/// In dev mode, rebuild [`TERA`] if any template changed since it was last loaded.
+///
+/// A template that fails to parse is logged and the last good set of templates is kept.
+fn reload_templates() {
+    let newest = template_files()
+        .filter_map(|f| f.metadata().ok()?.modified().ok())
+        .max();
+    let mut last_load = LAST_TEMPLATE_LOAD.lock().unwrap();
+    if newest.is_none_or(|newest| newest <= *last_load) {
+        return;
+    }
+    *last_load = SystemTime::now();
+    match load_templates() {
+        Ok(tera) => {
+            println!("Reloaded templates");
+            *TERA.write().unwrap() = tera;
+        }
+        Err(e) => eprintln!("Failed to reload templates, keeping the old ones: {e:#?}"),
+    }
+}
+
+/// Render one of the templates loaded from `./templates`.
+pub fn render(name: &str, ctx: &Context) -> tera::Result<String> {
+    if CONFIG.dev && !cfg!(feature = "embed-assets") {
+        LazyLock::force(&TERA);
+        reload_templates();
+    }
+    TERA.read().unwrap().render(name, ctx)
+}
+
+/// Names of all templates currently loaded.
+pub fn template_names() -> Vec<String> {
+    TERA.read()
+        .unwrap()
+        .get_template_names()
+        .map(ToString::to_string)
+        .collect()
+}
+
+/// Render a whole page, turning template failures into an [`ArticleError`].
+pub fn render_page(name: &str, ctx: impl Serialize) -> Result<RawHtml<String>, ArticleError> {
+    let ctx = Context::from_serialize(ctx).map_err(ArticleError::TemplateFailed)?;
+    render(name, &ctx).map(RawHtml).map_err(|e| match &e.kind {
+        tera::ErrorKind::TemplateNotFound(name) => ArticleError::MissingTemplate(name.clone()),
+        _ => ArticleError::TemplateFailed(e),
+    })
+}
+
+/// Register the wolog's custom functions and filters on a Tera instance.
+pub fn register(tera: &mut Tera) {
+    tera.register_function("asset_url", asset_url);
+    tera.register_function("site_lang", site_lang);
+    tera.register_function("base_path", base_path);
+    tera.register_function("primary_feed", primary_feed);
+    tera.register_filter("humandate", humandate);
+    tera.register_filter("reldate", reldate);
+    tera.register_filter("markdown", markdown);
+    tera.register_filter("summary", summary);
+    tera.register_function("undated_label", undated_label);
+    tera.register_tester("undated", undated);
+}
+
+/// Shorten text by the site's [`summary`](crate::config::Config::summary) policy.
+fn summary(value: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
+    let text = value
+        .as_str()
+        .ok_or_else(|| tera::Error::msg("summary expects a string"))?;
+    Ok(Value::String(crate::summary::summarize(
+        text,
+        &CONFIG.summary,
+    )))
+}
+
+/// Render a markdown snippet to HTML without forking pandoc.
+///
+/// With `inline=true`, a lone paragraph is unwrapped so the result can sit inside other markup.
+fn markdown(value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let source = value
+        .as_str()
+        .ok_or_else(|| tera::Error::msg("markdown expects a string"))?;
+    let html = markdown_to_html(source);
+    let inline = args.get("inline").and_then(Value::as_bool).unwrap_or(false);
+    if inline {
+        let trimmed = html.trim();
+        if let Some(inner) = trimmed
+            .strip_prefix("<p>")
+            .and_then(|t| t.strip_suffix("</p>"))
+            .filter(|inner| !inner.contains("<p>"))
+        {
+            return Ok(Value::String(inner.to_string()));
+        }
+    }
+    Ok(Value::String(html))
+}
+
+/// Render a markdown snippet to HTML with pulldown-cmark.
+pub fn markdown_to_html(source: &str) -> String {
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, pulldown_cmark::Parser::new(source));
+    html
+}
+
+/// Parse a date from a template value, treating the default date as missing.
+fn template_date(value: &Value) -> tera::Result<Option<NaiveDate>> {
+    let date = value
+        .as_str()
+        .ok_or_else(|| tera::Error::msg("expected a date string"))?;
+    let date = NaiveDate::from_str(date).map_err(|e| tera::Error::msg(e.to_string()))?;
+    Ok((date != NaiveDate::default()).then_some(date))
+}
+
+/// Format a date like "November 21, 2024".
+fn humandate(value: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
+    Ok(Value::String(match template_date(value)? {
+        Some(date) => date.format("%B %-d, %Y").to_string(),
+        None => "unknown".to_string(),
+    }))
+}
+
+/// Format a date relative to today, like "3 days ago", falling back to an absolute date after a
+/// year.
+fn reldate(value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let Some(date) = template_date(value)? else {
+        return Ok(Value::String("unknown".to_string()));
     };
+    match days_ago((timezone::now().date_naive() - date).num_days()) {
+        Some(ago) => Ok(Value::String(ago)),
+        None => humandate(value, args),
+    }
+}
+
+/// Describe a date `days` days before today, or `None` if it's in the future or over a year ago.
+fn days_ago(days: i64) -> Option<String> {
+    Some(match days {
+        0 => "today".to_string(),
+        1 => "yesterday".to_string(),
+        2..30 => format!("{days} days ago"),
+        30..60 => "1 month ago".to_string(),
+        60..365 => format!("{} months ago", days / 30),
+        _ => return None,
+    })
+}
+
+fn asset_url(args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let path = args
+        .get("path")
+        .and_then(Value::as_str)
+        .ok_or_else(|| tera::Error::msg("asset_url requires a string `path` argument"))?;
+    Ok(Value::String(crate::assets::asset_url(path)))
+}
+
+fn site_lang(_: &HashMap<String, Value>) -> tera::Result<Value> {
+    Ok(Value::String(CONFIG.lang.clone()))
+}
+
+/// What's shown instead of the date of an article without one.
+fn undated_label(_: &HashMap<String, Value>) -> tera::Result<Value> {
+    Ok(Value::String(CONFIG.undated_label.clone()))
+}
+
+/// Whether a date is the placeholder of an article that has none, as in
+/// `{% if meta.created is undated %}`.
+fn undated(value: Option<&Value>, _: &[Value]) -> tera::Result<bool> {
+    Ok(value
+        .and_then(Value::as_str)
+        .and_then(|date| NaiveDate::from_str(date).ok())
+        .is_some_and(crate::article::is_undated))
+}
+
+/// The root-relative URL of the site's primary feed, for pages that don't list their own feeds.
+fn primary_feed(_: &HashMap<String, Value>) -> tera::Result<Value> {
+    Ok(Value::String(crate::url::primary_feed()))
+}
+
+/// The path the site is served under, like `/blog`, for prefixing root-relative links.
+fn base_path(_: &HashMap<String, Value>) -> tera::Result<Value> {
+    Ok(Value::String(crate::url::base_path().to_string()))
+}
+
+/// Render the results of a `search` or `related` code block embedded in the article at `my_path`.
+///
+/// Returns `None`, leaving the block as it was, if the block is malformed or the search fails.
+pub async fn embed_results(my_path: &Path, kind: &str, contents: &str) -> Option<String> {
+    let search = match kind {
+        "search" => {
+            let Ok(mut search): Result<Search, _> = serde_yml::from_str(contents) else {
+                eprintln!("Bad search block {contents}");
+                return None;
+            };
+            search.exclude_paths.push(my_path.to_path_buf());
+            let results = crate::article::search(&search).await;
+            if let Ok(results) = &results {
+                let shown = results.iter().map(|(p, _)| &**p);
+                record_dependencies(my_path, shown, Query::Search(search));
+            }
+            results
+        }
+        "related" => {
+            let Ok(related): Result<Related, _> = serde_yml::from_str(contents) else {
+                eprintln!("Bad related block {contents}");
+                return None;
+            };
+            let results = crate::article::related(my_path, &related).await;
+            if let Ok(results) = &results {
+                let query = match related.by {
+                    RelatedBy::Tags => {
+                        Query::SharedTags(crate::article::tags_of(my_path).unwrap_or_default())
+                    }
+                    RelatedBy::Content => Query::Any,
+                };
+                let shown = results.iter().map(|(p, _)| &**p);
+                record_dependencies(my_path, shown, query);
+            }
+            results
+        }
+        _ => return None,
+    };
+    let Ok(search) = search else {
+        eprintln!("Search failed: {search:#?}");
+        return None;
+    };
+
+    let ctx = context! {
+        articles: search
+    };
+    let ctx = Context::from_serialize(ctx).unwrap();
+
+    Some(render("frag-search-results", &ctx).unwrap_or_else(|e| {
+        eprintln!("Search template failure: {e:#?}");
+        if CONFIG.dev {
+            format!("<pre>{}</pre>", tera::escape_html(&format!("{e:#?}")))
+        } else {
+            String::new()
+        }
+    }))
 }
 
 pub async fn apply_filters(my_path: Arc<Path>, ast: Pandoc) -> Pandoc {
+    // Index before embedding results, so `related` blocks can compare against this article.
+    crate::related::index(&my_path, &plain_text(&ast));
     let ast = frag_search_results(my_path.clone(), ast).await;
-    let ast = find_links(ast);
-    ast
+    let ast = presentation_filters(ast);
+    links::link_favicons(ast).await
+}
+
+/// The filters that only depend on the document itself, which apply even to markdown that isn't
+/// an article.
+pub fn presentation_filters(ast: Pandoc) -> Pandoc {
+    let ast = footnotes(ast);
+    let ast = details(ast);
+    let ast = callouts::callouts(ast);
+    let ast = match CONFIG.highlighter {
+        Highlighter::Syntect => highlight::highlight_code(ast),
+        Highlighter::Pandoc => ast,
+    };
+    let ast = wrap_code_blocks(ast);
+    let ast = image_attributes(ast);
+    let ast = links::external_links(ast);
+    find_links(ast)
 }
 
 async fn frag_search_results(my_path: Arc<Path>, mut ast: Pandoc) -> Pandoc {
-    let has_any_searches = Arc::new(AtomicBool::new(false));
-    struct FragSearchVisitor(Handle, Arc<Path>, Arc<AtomicBool>);
+    struct FragSearchVisitor(Handle, Arc<Path>, Vec<Arc<Path>>);
     impl MutVisitor for FragSearchVisitor {
         fn visit_block(&mut self, block: &mut Block) {
             if let Block::CodeBlock((_, classes, _), contents) = block {
-                self.2.store(true, Ordering::Relaxed);
-                if !classes.iter().any(|c| c == "search") {
-                    return;
-                }
-
-                let Ok(mut search): Result<Search, _> = serde_yml::from_str(contents) else {
-                    eprintln!("Bad search block {contents}");
+                let Some(kind) = classes.iter().find(|c| *c == "search" || *c == "related") else {
                     return;
                 };
-                search.exclude_paths.push(self.1.to_path_buf());
-
-                let Ok(search) = self.0.block_on(crate::article::search(&search)) else {
-                    eprintln!("Search failed: {search:#?}");
-                    return;
-                };
-
-                let ctx = context! {
-                    articles: search
-                };
-                let ctx = Context::from_serialize(ctx).unwrap();
-
-                let html = TERA
-                    .render("frag-search-results", &ctx)
-                    .unwrap_or_else(|e| format!("Search template failure: {e:#?}"));
-                *block = Block::RawBlock(Format("html".to_string()), html);
+                // The search runs outside the rendering task, so it's told what's being rendered.
+                let search =
+                    in_rendering_chain(self.2.clone(), embed_results(&self.1, kind, contents));
+                if let Some(html) = self.0.block_on(search) {
+                    *block = Block::RawBlock(Format("html".to_string()), html);
+                }
             }
         }
     }
     let initial = ast.clone();
-    let mut visitor = FragSearchVisitor(Handle::current(), my_path, has_any_searches.clone());
-    let Ok(mut ast) = spawn_blocking(move || {
+    let mut visitor = FragSearchVisitor(Handle::current(), my_path, rendering_chain());
+    let Ok(ast) = spawn_blocking(move || {
         visitor.walk_pandoc(&mut ast);
         ast
     })
@@ -83,11 +371,235 @@ async fn frag_search_results(my_path: Arc<Path>, mut ast: Pandoc) -> Pandoc {
         eprintln!("Filter failed");
         return initial;
     };
-    if has_any_searches.load(Ordering::Relaxed) {
-        ast.meta.insert(
-            "always_rerender".to_string(),
-            pandoc_ast::MetaValue::MetaBool(true),
-        );
+    ast
+}
+
+/// The label and copy button shown above a code block.
+pub fn code_header(lang: &str) -> String {
+    let label = if lang.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "<span class=\"code-lang\">{}</span>",
+            tera::escape_html(lang)
+        )
+    };
+    format!("<div class=\"code-header\">{label}<button class=\"copy-code\" type=\"button\">Copy</button></div>")
+}
+
+/// Wrap code blocks in a `code-block` div headed by their language and a copy button, leaving
+/// highlighting to pandoc.
+fn wrap_code_blocks(mut ast: Pandoc) -> Pandoc {
+    struct CodeVisitor;
+    impl MutVisitor for CodeVisitor {
+        fn visit_block(&mut self, block: &mut Block) {
+            let Block::CodeBlock((_, classes, _), _) = block else {
+                return self.walk_block(block);
+            };
+            if classes.iter().any(|c| c == "search" || c == "related") {
+                return;
+            }
+            let lang = classes.first().cloned().unwrap_or_default();
+            let header = Block::RawBlock(Format("html".to_string()), code_header(&lang));
+            let code = std::mem::replace(block, Block::Null);
+            *block = Block::Div(
+                (
+                    String::new(),
+                    vec!["code-block".to_string()],
+                    vec![("data-lang".to_string(), lang)],
+                ),
+                vec![header, code],
+            );
+        }
+    }
+    CodeVisitor.walk_pandoc(&mut ast);
+    ast
+}
+
+/// The summary of a `details` div that doesn't give one.
+const DEFAULT_SUMMARY: &str = "Details";
+
+/// Turn `::: details` divs into disclosure widgets.
+///
+/// The summary is the div's `summary` div if it has one, or else its first block if that's a
+/// paragraph or heading. Other classes and attributes are kept on the `<details>` element, so
+/// `::: {.details open=""}` starts out open.
+fn details(mut ast: Pandoc) -> Pandoc {
+    struct DetailsVisitor;
+    impl MutVisitor for DetailsVisitor {
+        fn visit_vec_block(&mut self, blocks: &mut Vec<Block>) {
+            // Inner details are expanded first, so they end up inside the outer ones.
+            self.walk_vec_block(blocks);
+            let mut expanded = Vec::with_capacity(blocks.len());
+            for block in blocks.drain(..) {
+                match block {
+                    Block::Div((id, classes, attributes), contents)
+                        if classes.iter().any(|c| c == "details") =>
+                    {
+                        let classes: Vec<String> =
+                            classes.into_iter().filter(|c| c != "details").collect();
+                        expanded.extend(disclosure(&id, &classes, &attributes, contents));
+                    }
+                    block => expanded.push(block),
+                }
+            }
+            *blocks = expanded;
+        }
+    }
+    DetailsVisitor.walk_pandoc(&mut ast);
+    ast
+}
+
+/// The blocks of a `<details>` element with the given attributes, made from a div's contents.
+fn disclosure(
+    id: &str,
+    classes: &[String],
+    attributes: &[(String, String)],
+    mut contents: Vec<Block>,
+) -> Vec<Block> {
+    let raw = |html: String| Block::RawBlock(Format("html".to_string()), html);
+    let is_summary = |block: &Block| matches!(block, Block::Div((_, classes, _), _) if classes.iter().any(|c| c == "summary"));
+    let summary = match contents.iter().position(is_summary) {
+        Some(i) => match contents.remove(i) {
+            // Paragraphs would each be a line of their own in the summary.
+            Block::Div(_, summary) => summary
+                .into_iter()
+                .map(|block| match block {
+                    Block::Para(inlines) => Block::Plain(inlines),
+                    block => block,
+                })
+                .collect(),
+            _ => unreachable!(),
+        },
+        None => match contents.first() {
+            Some(Block::Para(inlines) | Block::Plain(inlines) | Block::Header(_, _, inlines)) => {
+                let inlines = inlines.clone();
+                contents.remove(0);
+                vec![Block::Plain(inlines)]
+            }
+            _ => vec![Block::Plain(vec![Inline::Str(DEFAULT_SUMMARY.to_string())])],
+        },
+    };
+
+    let mut open = "<details".to_string();
+    if !id.is_empty() {
+        open.push_str(&format!(" id=\"{}\"", tera::escape_html(id)));
+    }
+    if !classes.is_empty() {
+        open.push_str(&format!(
+            " class=\"{}\"",
+            tera::escape_html(&classes.join(" "))
+        ));
+    }
+    for (key, value) in attributes {
+        open.push_str(&format!(
+            " {}=\"{}\"",
+            tera::escape_html(key),
+            tera::escape_html(value)
+        ));
+    }
+    open.push('>');
+
+    let mut blocks = vec![raw(open), raw("<summary>".to_string())];
+    blocks.extend(summary);
+    blocks.push(raw("</summary>".to_string()));
+    blocks.extend(contents);
+    blocks.push(raw("</details>".to_string()));
+    blocks
+}
+
+/// Make images load lazily, and give local ones their dimensions.
+fn image_attributes(mut ast: Pandoc) -> Pandoc {
+    struct ImageVisitor;
+    impl MutVisitor for ImageVisitor {
+        fn visit_inline(&mut self, inline: &mut Inline) {
+            let Inline::Image((_, _, attributes), _, (src, _)) = inline else {
+                return self.walk_inline(inline);
+            };
+            let added = crate::images::attributes(src, attributes);
+            attributes.extend(added);
+        }
+    }
+    ImageVisitor.walk_pandoc(&mut ast);
+    ast
+}
+
+/// Extract the readable text of an article body, with paragraphs separated by blank lines.
+///
+/// Raw HTML (including embedded search results) and footnotes are left out.
+pub fn plain_text(ast: &Pandoc) -> String {
+    blocks_text(&ast.blocks)
+}
+
+fn blocks_text(blocks: &[Block]) -> String {
+    struct TextVisitor(String);
+    impl MutVisitor for TextVisitor {
+        fn visit_block(&mut self, block: &mut Block) {
+            match block {
+                Block::CodeBlock(_, code) => self.0.push_str(code),
+                Block::RawBlock(..) | Block::HorizontalRule | Block::Null => return,
+                _ => self.walk_block(block),
+            }
+            if !self.0.is_empty() && !self.0.ends_with("\n\n") {
+                self.0.push_str("\n\n");
+            }
+        }
+        fn visit_inline(&mut self, inline: &mut Inline) {
+            match inline {
+                Inline::Str(s) | Inline::Code(_, s) | Inline::Math(_, s) => self.0.push_str(s),
+                Inline::Space | Inline::SoftBreak => self.0.push(' '),
+                Inline::LineBreak => self.0.push('\n'),
+                Inline::RawInline(..) | Inline::Note(_) => {}
+                _ => self.walk_inline(inline),
+            }
+        }
+    }
+    let mut visitor = TextVisitor(String::new());
+    visitor.visit_vec_block(&mut blocks.to_vec());
+    visitor.0.trim_end().to_string()
+}
+
+/// Wrap each footnote reference in a span carrying the note's text, so it can be shown as a
+/// popover, and list the footnotes in the article's metadata.
+///
+/// Pandoc numbers footnotes in document order, giving them ids `fnN` and `fnrefN`; this visits
+/// notes in the same order so the listed ids match the rendered ones.
+fn footnotes(mut ast: Pandoc) -> Pandoc {
+    struct FootnoteVisitor(Vec<MetaValue>);
+    impl MutVisitor for FootnoteVisitor {
+        fn visit_inline(&mut self, inline: &mut Inline) {
+            let Inline::Note(blocks) = inline else {
+                return self.walk_inline(inline);
+            };
+            let number = self.0.len() + 1;
+            let text = blocks_text(blocks);
+            self.0.push(MetaValue::MetaMap(
+                [
+                    ("id", format!("fn{number}")),
+                    ("ref_id", format!("fnref{number}")),
+                    ("text", text.clone()),
+                ]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), Box::new(MetaValue::MetaString(v))))
+                .collect(),
+            ));
+            let note = std::mem::take(blocks);
+            *inline = Inline::Span(
+                (
+                    String::new(),
+                    vec!["footnote".to_string()],
+                    vec![("title".to_string(), text)],
+                ),
+                vec![Inline::Note(note)],
+            );
+        }
+    }
+    let mut visitor = FootnoteVisitor(vec![]);
+    visitor.visit_vec_block(&mut ast.blocks);
+    let FootnoteVisitor(footnotes) = visitor;
+    if !footnotes.is_empty() {
+        ast.meta
+            .insert("footnotes".to_string(), MetaValue::MetaList(footnotes));
     }
     ast
 }
@@ -116,3 +628,145 @@ fn find_links(mut ast: Pandoc) -> Pandoc {
     );
     ast
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_dates_change_at_each_boundary() {
+        let cases = [
+            (-1, None),
+            (0, Some("today")),
+            (1, Some("yesterday")),
+            (2, Some("2 days ago")),
+            (29, Some("29 days ago")),
+            (30, Some("1 month ago")),
+            (59, Some("1 month ago")),
+            (60, Some("2 months ago")),
+            (364, Some("12 months ago")),
+            (365, None),
+        ];
+        for (days, expected) in cases {
+            assert_eq!(days_ago(days).as_deref(), expected, "{days} days");
+        }
+    }
+
+    #[test]
+    fn old_dates_are_shown_absolutely() {
+        let date = Value::String("2001-02-03".to_string());
+        let shown = reldate(&date, &HashMap::new()).unwrap();
+        assert_eq!(shown, Value::String("February 3, 2001".to_string()));
+    }
+
+    #[test]
+    fn templates_can_tell_placeholder_dates_apart() {
+        let placeholder = serde_json::to_value(NaiveDate::default()).unwrap();
+        assert!(undated(Some(&placeholder), &[]).unwrap());
+        assert!(!undated(Some(&Value::String("2001-02-03".to_string())), &[]).unwrap());
+        assert!(!undated(None, &[]).unwrap());
+    }
+
+    #[test]
+    fn markdown_snippets_render_inline() {
+        let snippet = Value::String("Some **bold** words".to_string());
+        let inline = HashMap::from([("inline".to_string(), Value::Bool(true))]);
+        assert_eq!(
+            markdown(&snippet, &inline).unwrap(),
+            Value::String("Some <strong>bold</strong> words".to_string())
+        );
+        assert_eq!(
+            markdown(&snippet, &HashMap::new()).unwrap(),
+            Value::String("<p>Some <strong>bold</strong> words</p>\n".to_string())
+        );
+    }
+
+    #[test]
+    fn plain_text_leaves_out_markup_raw_html_and_notes() {
+        let ast = Pandoc::from_json(
+            r#"{"pandoc-api-version":[1,23,1],"meta":{},"blocks":[
+                {"t":"Header","c":[2,["intro",[],[]],[{"t":"Str","c":"Intro"}]]},
+                {"t":"Para","c":[
+                    {"t":"Str","c":"Hello"},{"t":"Space"},
+                    {"t":"Emph","c":[{"t":"Str","c":"world"}]},
+                    {"t":"Note","c":[{"t":"Para","c":[{"t":"Str","c":"aside"}]}]}
+                ]},
+                {"t":"RawBlock","c":["html","<div>search results</div>"]},
+                {"t":"CodeBlock","c":[["",[],[]],"let x = 1;"]}
+            ]}"#,
+        );
+        assert_eq!(plain_text(&ast), "Intro\n\nHello world\n\nlet x = 1;");
+    }
+
+    #[test]
+    fn code_blocks_are_wrapped_with_a_label_and_copy_button() {
+        let ast = Pandoc::from_json(
+            r#"{"pandoc-api-version":[1,23,1],"meta":{},"blocks":[
+                {"t":"CodeBlock","c":[["",["python"],[]],"print('hi')"]},
+                {"t":"CodeBlock","c":[["",["search"],[]],"tags: [x]"]}
+            ]}"#,
+        );
+        let ast = wrap_code_blocks(ast);
+        let Block::Div((_, classes, attrs), contents) = &ast.blocks[0] else {
+            panic!("The code block should be wrapped");
+        };
+        assert_eq!(classes, &["code-block"]);
+        assert_eq!(attrs, &[("data-lang".to_string(), "python".to_string())]);
+        let [Block::RawBlock(_, header), Block::CodeBlock(_, code)] = &contents[..] else {
+            panic!("The wrapper should hold a header and the code");
+        };
+        assert!(header.contains(r#"<span class="code-lang">python</span>"#));
+        assert!(header.contains(r#"<button class="copy-code""#));
+        assert_eq!(code, "print('hi')");
+        // Embedded searches are replaced later, so they're left alone.
+        assert!(matches!(ast.blocks[1], Block::CodeBlock(..)));
+    }
+
+    #[test]
+    fn details_divs_become_disclosures_with_a_summary() {
+        let ast = Pandoc::from_json(
+            r#"{"pandoc-api-version":[1,23,1],"meta":{},"blocks":[
+                {"t":"Div","c":[["spoiler",["details","wide"],[["open",""]]],[
+                    {"t":"Para","c":[{"t":"Str","c":"Spoilers"}]},
+                    {"t":"Para","c":[{"t":"Str","c":"Hidden"}]}
+                ]]},
+                {"t":"Div","c":[["",["details"],[]],[
+                    {"t":"Para","c":[{"t":"Str","c":"Body"}]},
+                    {"t":"Div","c":[["",["summary"],[]],[
+                        {"t":"Para","c":[{"t":"Str","c":"Given"}]}
+                    ]]}
+                ]]},
+                {"t":"Div","c":[["",["details"],[]],[
+                    {"t":"CodeBlock","c":[["",[],[]],"code"]}
+                ]]}
+            ]}"#,
+        );
+        let ast = details(ast);
+        let raw = |html: &str| Block::RawBlock(Format("html".to_string()), html.to_string());
+        let plain = |text: &str| Block::Plain(vec![Inline::Str(text.to_string())]);
+        let para = |text: &str| Block::Para(vec![Inline::Str(text.to_string())]);
+        assert_eq!(
+            ast.blocks,
+            [
+                raw(r#"<details id="spoiler" class="wide" open="">"#),
+                raw("<summary>"),
+                plain("Spoilers"),
+                raw("</summary>"),
+                para("Hidden"),
+                raw("</details>"),
+                raw("<details>"),
+                raw("<summary>"),
+                plain("Given"),
+                raw("</summary>"),
+                para("Body"),
+                raw("</details>"),
+                raw("<details>"),
+                raw("<summary>"),
+                plain(DEFAULT_SUMMARY),
+                raw("</summary>"),
+                Block::CodeBlock(Default::default(), "code".to_string()),
+                raw("</details>"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! What the site serves at `/`.
+
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::{config::CONFIG, roots};
+
+/// What the homepage shows.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct HomePage {
+    pub mode: HomeMode,
+    /// The site-relative path of the article shown, if it's one.
+    pub article: PathBuf,
+    /// How many of the newest articles a listing shows.
+    pub limit: usize,
+}
+
+impl Default for HomePage {
+    fn default() -> Self {
+        Self {
+            mode: HomeMode::Auto,
+            article: PathBuf::from("index.md"),
+            limit: 10,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HomeMode {
+    /// The article, if it exists, and otherwise a listing.
+    Auto,
+    /// Always the article.
+    Article,
+    /// Always a listing of the newest articles, through the `home` template.
+    Listing,
+}
+
+/// Where the source of the article shown at `/` is, or `None` to show a listing instead.
+pub fn article() -> Option<PathBuf> {
+    article_for(&CONFIG.home)
+}
+
+fn article_for(home: &HomePage) -> Option<PathBuf> {
+    let path = roots::disk_path(&home.article);
+    match home.mode {
+        HomeMode::Article => Some(path),
+        HomeMode::Auto => path.is_file().then_some(path),
+        HomeMode::Listing => None,
+    }
+}
+
+/// Whether `path`, relative to the site, is the article shown at `/`.
+pub fn is_home(path: &Path) -> bool {
+    CONFIG.home.mode != HomeMode::Listing && path == CONFIG.home.article
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::Status;
+
+    use super::*;
+    use crate::testing::{self, Fixture};
+
+    fn home(mode: HomeMode, fixture: &Fixture) -> HomePage {
+        HomePage {
+            mode,
+            article: fixture.rel("index.md").into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn the_mode_picks_the_article_or_a_listing() {
+        let fixture = Fixture::new("home-modes");
+        let index = fixture.dir().join("index.md");
+
+        // Without the article, only the article mode insists on it.
+        assert_eq!(article_for(&home(HomeMode::Auto, &fixture)), None);
+        assert_eq!(
+            article_for(&home(HomeMode::Article, &fixture)),
+            Some(index.clone())
+        );
+        assert_eq!(article_for(&home(HomeMode::Listing, &fixture)), None);
+
+        fixture.write("index", "---\ntitle: Home\n---\nWelcome");
+        assert_eq!(
+            article_for(&home(HomeMode::Auto, &fixture)),
+            Some(index.clone())
+        );
+        assert_eq!(article_for(&home(HomeMode::Article, &fixture)), Some(index));
+        assert_eq!(article_for(&home(HomeMode::Listing, &fixture)), None);
+    }
+
+    #[rocket::async_test]
+    async fn without_an_index_article_the_newest_posts_are_listed() {
+        let fixture = Fixture::new("home-listing");
+        // Newer than anything other tests write, so it stays within the listing's limit.
+        fixture
+            .article(
+                "post",
+                "---\ntitle: Listed on the homepage\nstatus: published\ncreated: 2999-01-01\n---\nHello",
+            )
+            .await;
+        assert!(!roots::disk_path(&CONFIG.home.article).exists());
+
+        let client = testing::client().await;
+        let response = client.get("/").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let html = response.into_string().await.unwrap();
+        assert!(html.contains("Latest posts"));
+        assert!(html.contains(&format!("/{}\"", fixture.rel("post"))));
+    }
+}
@@ -0,0 +1,230 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::LazyLock,
+    time::SystemTime,
+};
+
+use dashmap::DashMap;
+use image::imageops::FilterType;
+use rocket::http::RawStr;
+
+use crate::{config::CONFIG, url};
+
+/// Where resized images are written, inside the assets directory so they're served with it.
+const VARIANT_DIR: &str = "_resized";
+
+/// An image's width and height in pixels.
+type Dimensions = (u32, u32);
+
+/// Image dimensions by file, with the modification time they were read at.
+static DIMENSIONS: LazyLock<DashMap<PathBuf, (SystemTime, Option<Dimensions>)>> =
+    LazyLock::new(DashMap::new);
+
+/// The file behind an image URL, if it's served from the assets directory.
+pub fn local_file(src: &str) -> Option<PathBuf> {
+    let src = src.split(['?', '#']).next()?;
+    let src = src.strip_prefix(url::base_path()).unwrap_or(src);
+    let rel = RawStr::new(src.strip_prefix("/assets/")?)
+        .percent_decode()
+        .ok()?;
+    let path = Path::new("articles/assets").join(&*rel);
+    // Keep `..` from escaping the assets directory.
+    path.components()
+        .all(|c| matches!(c, std::path::Component::Normal(_)))
+        .then_some(path)
+}
+
+/// The width and height of a local image, read from its header.
+fn dimensions(path: &Path) -> Option<Dimensions> {
+    let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    if let Some(cached) = DIMENSIONS.get(path).filter(|c| c.0 == modified) {
+        return cached.1;
+    }
+    let dimensions = image::image_dimensions(path)
+        .inspect_err(|e| eprintln!("Couldn't read the size of {path:?}: {e}"))
+        .ok();
+    DIMENSIONS.insert(path.to_path_buf(), (modified, dimensions));
+    dimensions
+}
+
+/// Attributes to add to an image, so it loads lazily and local images don't shift the layout
+/// as they load.
+///
+/// Attributes in `existing` aren't overridden.
+pub fn attributes(src: &str, existing: &[(String, String)]) -> Vec<(String, String)> {
+    let has = |key: &str| existing.iter().any(|(k, _)| k == key);
+    let mut attributes = vec![];
+    if !has("loading") {
+        attributes.push(("loading".to_string(), "lazy".to_string()));
+    }
+    if has("width") || has("height") {
+        return attributes;
+    }
+    let Some(path) = local_file(src) else {
+        return attributes;
+    };
+    let Some((width, height)) = dimensions(&path) else {
+        return attributes;
+    };
+    attributes.push(("width".to_string(), width.to_string()));
+    attributes.push(("height".to_string(), height.to_string()));
+    let variants = variants(&path, width);
+    if !variants.is_empty() && !has("srcset") {
+        let srcset = variants
+            .iter()
+            .map(|(url, width)| format!("{url} {width}w"))
+            .chain([format!("{src} {width}w")])
+            .collect::<Vec<_>>()
+            .join(", ");
+        attributes.push(("srcset".to_string(), srcset));
+    }
+    attributes
+}
+
+/// Smaller copies of the image at `path`, which is `width` pixels wide, at each configured width,
+/// resizing it where the copies are missing or older than the original.
+///
+/// Vector and animated formats are left alone.
+fn variants(path: &Path, width: u32) -> Vec<(String, u32)> {
+    let resizable = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            matches!(
+                ext.to_ascii_lowercase().as_str(),
+                "png" | "jpg" | "jpeg" | "webp"
+            )
+        });
+    let Some(rel) = path
+        .strip_prefix("articles/assets")
+        .ok()
+        .filter(|_| resizable)
+    else {
+        return vec![];
+    };
+    let Ok(modified) = std::fs::metadata(path).and_then(|m| m.modified()) else {
+        return vec![];
+    };
+    let mut source = None;
+    let mut variants = vec![];
+    for &variant_width in CONFIG.image_widths.iter().filter(|&&w| w < width) {
+        let mut name = rel.with_extension("").into_os_string();
+        name.push(format!("-{variant_width}."));
+        name.push(rel.extension().unwrap_or_default());
+        let variant_rel = Path::new(VARIANT_DIR).join(name);
+        let variant = Path::new("articles/assets").join(&variant_rel);
+        let fresh = std::fs::metadata(&variant)
+            .and_then(|m| m.modified())
+            .is_ok_and(|variant_modified| variant_modified >= modified);
+        if !fresh {
+            let source = match &source {
+                Some(source) => source,
+                None => match image::open(path) {
+                    Ok(image) => source.insert(image),
+                    Err(e) => {
+                        eprintln!("Couldn't open {path:?} to resize it: {e}");
+                        return vec![];
+                    }
+                },
+            };
+            println!("Resizing {path:?} to {variant_width}px wide");
+            let resized = source.resize(variant_width, u32::MAX, FilterType::Lanczos3);
+            // Write to a temporary file first, so a request never sees half an image.
+            let mut partial = variant.clone().into_os_string();
+            partial.push(".partial");
+            let partial = PathBuf::from(partial);
+            let result = std::fs::create_dir_all(variant.parent().unwrap_or(Path::new(".")))
+                .map_err(image::ImageError::IoError)
+                .and_then(|_| {
+                    resized.save_with_format(&partial, image::ImageFormat::from_path(&variant)?)
+                })
+                .and_then(|_| {
+                    std::fs::rename(&partial, &variant).map_err(image::ImageError::IoError)
+                });
+            if let Err(e) = result {
+                eprintln!("Couldn't write {variant:?}: {e}");
+                continue;
+            }
+        }
+        let url = url::site_path(&format!(
+            "/assets/{}",
+            url::encode_path(&variant_rel.to_string_lossy())
+        ));
+        variants.push((url, variant_width));
+    }
+    variants
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory of test images in the assets directory, removed with their resized copies
+    /// when it's dropped.
+    struct Images(&'static str);
+
+    impl Images {
+        fn add(&self, name: &str, width: u32, height: u32) -> String {
+            let dir = Path::new("articles/assets").join(self.0);
+            std::fs::create_dir_all(&dir).unwrap();
+            image::RgbImage::new(width, height)
+                .save(dir.join(name))
+                .unwrap();
+            url::site_path(&format!("/assets/{}/{name}", self.0))
+        }
+    }
+
+    impl Drop for Images {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(Path::new("articles/assets").join(self.0));
+            let _ = std::fs::remove_dir_all(
+                Path::new("articles/assets").join(VARIANT_DIR).join(self.0),
+            );
+            // Only removed if no other resized images are left in it.
+            let _ = std::fs::remove_dir(Path::new("articles/assets").join(VARIANT_DIR));
+        }
+    }
+
+    fn get<'a>(attributes: &'a [(String, String)], key: &str) -> Option<&'a str> {
+        attributes
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn local_images_gain_their_dimensions() {
+        let images = Images("test-dimensions");
+        let src = images.add("small.png", 120, 80);
+        let attributes = attributes(&src, &[]);
+        assert_eq!(get(&attributes, "loading"), Some("lazy"));
+        assert_eq!(get(&attributes, "width"), Some("120"));
+        assert_eq!(get(&attributes, "height"), Some("80"));
+        assert_eq!(get(&attributes, "srcset"), None);
+
+        let remote = super::attributes("https://example.com/a.png", &[]);
+        assert_eq!(remote, [("loading".to_string(), "lazy".to_string())]);
+    }
+
+    #[test]
+    fn large_images_get_resized_copies_in_a_srcset() {
+        let images = Images("test-srcset");
+        let src = images.add("large.png", 1000, 50);
+        let attributes = attributes(&src, &[]);
+        let srcset = get(&attributes, "srcset").expect("A large image should get a srcset");
+        let widths: Vec<_> = CONFIG.image_widths.iter().filter(|&&w| w < 1000).collect();
+        assert!(!widths.is_empty());
+        for width in widths {
+            let file = Path::new("articles/assets")
+                .join(VARIANT_DIR)
+                .join(images.0)
+                .join(format!("large-{width}.png"));
+            assert_eq!(image::image_dimensions(&file).unwrap().0, *width);
+            assert!(
+                srcset.contains(&format!("large-{width}.png {width}w")),
+                "{srcset}"
+            );
+        }
+        assert!(srcset.ends_with(&format!("{src} 1000w")));
+    }
+}
@@ -0,0 +1,300 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+    sync::{Arc, LazyLock},
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+use rocket::tokio::{runtime::Handle, sync::Semaphore, task::JoinSet};
+use scraper::{Html, Selector};
+use serde::Serialize;
+
+use crate::{
+    article::{self, error::ArticleError, Search},
+    config::CONFIG,
+    roots, url,
+};
+
+/// What the link checker found wrong with the site's links.
+#[derive(Serialize, Debug, Default)]
+pub struct Report {
+    /// How many articles were checked.
+    pub articles: usize,
+    /// Whether links off the site were fetched, or only internal links checked.
+    pub external_checked: bool,
+    pub broken: Vec<BrokenLink>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BrokenLink {
+    /// The site-relative path of the article with the link.
+    pub article: String,
+    /// Where the link points, resolved against the article's URL.
+    pub target: String,
+    pub kind: LinkKind,
+    pub problem: String,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    /// A link to another page on the site.
+    Internal,
+    /// A link off the site.
+    External,
+    /// A link marked as a webmention.
+    Mention,
+}
+
+/// Site paths served by routes rather than articles.
+const ROUTES: &[&str] = &[
+    "search",
+    "tags",
+    "feed",
+    "feeds",
+    "sitemap.xml",
+    "webmention",
+];
+/// Routes showing the article named by the rest of their path.
+const ARTICLE_ROUTES: &[&str] = &["print", "pdf", "epub", "toc"];
+
+/// Check the links in every published article.
+///
+/// Internal links are checked against the articles and files on disk. If `external` is set,
+/// links off the site are fetched too, at most
+/// [`link_check_rate`](crate::config::Config::link_check_rate) a second, with results
+/// remembered for [`link_check_cache`](crate::config::Config::link_check_cache) seconds.
+pub async fn check(external: bool) -> Result<Report, ArticleError> {
+    let search = Search {
+        include_hidden: true,
+        ..Default::default()
+    };
+    let articles = article::search_all(&search).await?;
+    let mut report = Report {
+        articles: articles.len(),
+        external_checked: external,
+        ..Default::default()
+    };
+    // Each external target is only fetched once, however many articles link to it.
+    let mut outgoing: BTreeMap<String, BTreeSet<(String, LinkKind)>> = BTreeMap::new();
+    for (path, _) in &articles {
+        let source: Arc<Path> = roots::disk_path(path).into();
+        let Ok(article) = article::get_article(&source).await else {
+            continue;
+        };
+        let page = url::article_rel(path);
+        for (target, kind) in links(path, &article.content) {
+            match kind {
+                LinkKind::Internal => {
+                    if !internal_exists(&target) {
+                        report.broken.push(BrokenLink {
+                            article: page.clone(),
+                            target: url::absolute_url(&target),
+                            kind,
+                            problem: "No such page".to_string(),
+                        });
+                    }
+                }
+                LinkKind::External | LinkKind::Mention if external => {
+                    outgoing
+                        .entry(target)
+                        .or_default()
+                        .insert((page.clone(), kind));
+                }
+                LinkKind::External | LinkKind::Mention => {}
+            }
+        }
+    }
+
+    let mut checks = JoinSet::new();
+    for target in outgoing.keys().cloned() {
+        checks.spawn(async move {
+            let result = check_external(&target).await;
+            (target, result)
+        });
+    }
+    while let Some(checked) = checks.join_next().await {
+        let Ok((target, Err(problem))) = checked else {
+            continue;
+        };
+        for (page, kind) in &outgoing[&target] {
+            report.broken.push(BrokenLink {
+                article: page.clone(),
+                target: target.clone(),
+                kind: *kind,
+                problem: problem.clone(),
+            });
+        }
+    }
+    report
+        .broken
+        .sort_by(|a, b| (&a.article, &a.target).cmp(&(&b.article, &b.target)));
+    Ok(report)
+}
+
+/// The links in an article's HTML, resolved against its URL.
+///
+/// Internal links are returned as raw site-relative paths, without their query or fragment.
+fn links(path: &Path, html: &str) -> Vec<(String, LinkKind)> {
+    static ANCHORS: LazyLock<Selector> = LazyLock::new(|| Selector::parse("a[href]").unwrap());
+    let Ok(base) = reqwest::Url::parse(&url::article_url(path)) else {
+        return vec![];
+    };
+    let html = Html::parse_fragment(html);
+    html.select(&ANCHORS)
+        .filter_map(|anchor| {
+            let href = anchor.value().attr("href")?;
+            if href.starts_with('#') {
+                return None;
+            }
+            let mut target = base.join(href).ok()?;
+            if !matches!(target.scheme(), "http" | "https") {
+                return None;
+            }
+            target.set_fragment(None);
+            let mut page = target.clone();
+            page.set_query(None);
+            if let Some(rel) = url::strip_base(page.as_str()) {
+                return Some((url::decode_path(rel), LinkKind::Internal));
+            }
+            let mention = anchor.value().classes().any(|class| class == "mention");
+            let kind = if mention {
+                LinkKind::Mention
+            } else {
+                LinkKind::External
+            };
+            Some((target.to_string(), kind))
+        })
+        .collect()
+}
+
+/// Whether a site-relative path leads somewhere.
+fn internal_exists(rel: &str) -> bool {
+    let rel = rel.trim_matches('/');
+    let (first, rest) = rel.split_once('/').unwrap_or((rel, ""));
+    if rel.is_empty() || ROUTES.contains(&first) {
+        return true;
+    }
+    if ARTICLE_ROUTES.contains(&first) {
+        return article::resolve(Path::new(rest)).is_some();
+    }
+    match first {
+        "assets" => Path::new("articles").join(rel).is_file(),
+        "static" => static_exists(rest),
+        _ => article::resolve(Path::new(rel)).is_some(),
+    }
+}
+
+#[cfg(feature = "embed-assets")]
+fn static_exists(rel: &str) -> bool {
+    crate::embedded::STATIC.get_file(rel).is_some()
+}
+
+#[cfg(not(feature = "embed-assets"))]
+fn static_exists(rel: &str) -> bool {
+    Path::new("static").join(rel).is_file()
+}
+
+/// Whether an external link answered, or what went wrong.
+type Checked = Result<(), String>;
+
+/// Recent results of fetching external links, and when they were fetched.
+static CHECKED: LazyLock<DashMap<String, (Instant, Checked)>> = LazyLock::new(DashMap::new);
+
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(15))
+        .user_agent(concat!("wolog link checker/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .expect("Couldn't build the link checker's HTTP client")
+});
+
+/// Refilled at [`link_check_rate`](crate::config::Config::link_check_rate) permits a second.
+static BUCKET: LazyLock<Arc<Semaphore>> = LazyLock::new(|| {
+    let rate = CONFIG.link_check_rate.max(1);
+    let semaphore = Arc::new(Semaphore::new(rate));
+    Handle::current().spawn({
+        let semaphore = semaphore.clone();
+        async move {
+            let mut clock = rocket::tokio::time::interval(Duration::from_secs(1));
+            loop {
+                if semaphore.available_permits() < rate {
+                    semaphore.add_permits(1);
+                }
+                clock.tick().await;
+            }
+        }
+    });
+    semaphore
+});
+
+/// Fetch an external link, succeeding if it (eventually) answers with a 2xx status.
+async fn check_external(target: &str) -> Checked {
+    let cache_for = Duration::from_secs(CONFIG.link_check_cache);
+    if let Some(cached) = CHECKED.get(target) {
+        if cached.0.elapsed() < cache_for {
+            return cached.1.clone();
+        }
+    }
+    BUCKET.acquire().await.unwrap().forget();
+    let mut response = CLIENT.head(target).send().await;
+    // Plenty of servers don't bother supporting HEAD.
+    if response.as_ref().is_ok_and(|r| {
+        matches!(
+            r.status(),
+            reqwest::StatusCode::METHOD_NOT_ALLOWED | reqwest::StatusCode::NOT_IMPLEMENTED
+        )
+    }) {
+        response = CLIENT.get(target).send().await;
+    }
+    let result = match response {
+        Ok(response) if response.status().is_success() => Ok(()),
+        Ok(response) => Err(response.status().to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    CHECKED.insert(target.to_string(), (Instant::now(), result.clone()));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Fixture;
+
+    #[rocket::async_test]
+    async fn broken_internal_links_are_reported() {
+        let fixture = Fixture::new("link-check");
+        let dir = fixture.rel("");
+        fixture
+            .article(
+                "other",
+                "---\ntitle: Other\nstatus: published\n---\n\nHi.\n",
+            )
+            .await;
+        fixture
+            .article(
+                "post",
+                &format!(
+                    "---\ntitle: Links\nstatus: published\n---\n\n[Fine](/{dir}/other), [gone](/{dir}/missing#part), [feed](/feed) and [away](https://example.invalid/).\n"
+                ),
+            )
+            .await;
+        let report = check(false).await.unwrap();
+        assert!(!report.external_checked);
+        let broken: Vec<_> = report
+            .broken
+            .iter()
+            .filter(|link| link.article.starts_with(&format!("{dir}/")))
+            .map(|link| (link.article.as_str(), link.target.as_str(), link.kind))
+            .collect();
+        assert_eq!(
+            broken,
+            [(
+                &*format!("{dir}/post"),
+                &*url::absolute_url(&format!("/{dir}/missing")),
+                LinkKind::Internal
+            )]
+        );
+    }
+}
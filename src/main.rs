@@ -1,82 +1,544 @@
-use article::{error::ArticleError, ArticlePath};
-use article::{Article, ArticleMeta, Bounds, Search, SortType};
-use atom_syndication::{Category, Content, Entry, Generator, Link, Person, Text};
-use chrono::{
-    Date, DateTime, Days, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime,
-    TimeZone, Utc,
+use article::error::{ArticleError, JsonError};
+use article::{
+    Article, ArticleMeta, ArticlePath, ArticleStatus, ExportFormat, FeedContent, InFeed, Search,
+    SearchBuilder, SortType,
 };
-use pandoc_ast::Map;
+use atom_syndication::{Category, Content, Entry, Generator, Link, Person, Text};
+use chrono::{DateTime, NaiveDate, Utc};
+use config::CONFIG;
+use protect::Unlocks;
+use rocket::data::Capped;
 use rocket::form::{Form, FromFormField, ValueField};
-use rocket::http::hyper::Request;
-use rocket::http::{ContentType, HeaderMap, Status};
-use rocket::request::{FromParam, FromRequest, FromSegments, Outcome};
-use rocket::response::Responder;
+use rocket::http::{uri::Origin, Accept, ContentType, Header, Status};
+use rocket::request::{FromParam, FromRequest, Outcome};
+use rocket::response::content::RawHtml;
+use rocket::response::{Redirect, Responder};
 use rocket::serde::json::Json;
-use rocket::tokio::runtime::{Handle, Runtime};
-use rocket::{fs::FileServer, Rocket};
-use rocket::{tokio, State};
-use rocket_dyn_templates::{context, Template};
+use rocket::tokio;
+use rocket::{fs::FileServer, Build, Rocket};
+use rocket_dyn_templates::{context, tera::escape_html};
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashSet};
-use std::default;
-use std::ops::{Bound, Deref, RangeBounds};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::ops::Deref;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::sync::{Arc, LazyLock, RwLock};
+use std::sync::Arc;
+use std::time::Duration;
+use url::FeedLink;
 
+mod admin;
 mod article;
+mod assets;
+mod base_path;
+mod cache_control;
+mod compress;
+mod config;
 mod db;
+#[cfg(feature = "embed-assets")]
+mod embedded;
+mod etag;
+mod export;
+mod favicons;
+mod feed_cache;
 mod filters;
-
-static WOLOG_URL: LazyLock<String> = LazyLock::new(|| {
-    dbg!(std::env::var("WOLOG_URL").unwrap_or_else(|_| "https://wolo.dev/".to_string()))
-});
+mod home;
+mod images;
+mod link_check;
+mod mention_policy;
+mod minify;
+mod og_image;
+mod protect;
+mod read_next;
+mod related;
+mod roots;
+mod sanitize;
+mod security;
+mod shutdown;
+mod summary;
+mod tag_rename;
+#[cfg(test)]
+mod testing;
+mod timezone;
+mod trailing_slash;
+mod url;
 
 #[macro_use]
 extern crate rocket;
 
 #[rocket::main]
 async fn main() {
-    Rocket::build()
-        .attach(Template::fairing())
+    article::check_filters();
+    roots::check();
+    if CONFIG.persist_cache {
+        article::load_cache().await;
+    }
+    if let Some(out) = export::requested() {
+        if !export::run(rocket(), &out).await {
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Err(e) = db::init().await {
+        eprintln!("Couldn't open the database: {e}");
+        std::process::exit(1);
+    }
+    if let Some(interval) = CONFIG.rerender_interval {
+        tokio::spawn(article::rerender_periodically(Duration::from_secs(
+            interval.max(1),
+        )));
+    }
+    tokio::spawn(article::publish_scheduled(Duration::from_secs(
+        CONFIG.publish_check_interval.max(1),
+    )));
+    if CONFIG.send_webmentions {
+        tokio::spawn(db::send_queued_webmentions(Duration::from_secs(
+            CONFIG.webmention_queue_interval.max(1),
+        )));
+    }
+    // Render every article up front, so slugs resolve and related articles are found from the
+    // first request.
+    tokio::spawn(async {
+        if let Err(e) = article::search(&Search::default()).await {
+            eprintln!("Warming the article cache failed: {e:?}");
+        }
+    });
+    rocket().launch().await.expect("Rocket failed");
+}
+
+fn rocket() -> Rocket<Build> {
+    // The webmention endpoint is the only form, and the preview endpoint the only string body, so
+    // Rocket's limits for those are effectively theirs.
+    let figment = rocket::Config::figment()
+        .merge(("limits.form", CONFIG.webmention_limit))
+        .merge(("limits.string", CONFIG.preview_limit));
+    let rocket = Rocket::custom(figment)
+        .attach(rocket::shield::Shield::new())
+        .attach(security::SecurityHeaders)
+        .attach(base_path::StripBasePath)
+        .attach(trailing_slash::TrailingSlashRedirect)
+        .attach(minify::Minify)
+        .attach(etag::ETag)
+        .attach(compress::Compression)
+        .attach(cache_control::CacheControl)
+        .attach(shutdown::FlushTasks)
         // .manage(Arc::new(ArticleManager::default()))
         .mount(
             "/",
             routes![
                 show_article,
+                unlock_article,
+                print_article,
+                article_toc,
+                article_mentions,
+                missing_json,
+                pdf_article,
+                epub_article,
                 render_homepage,
                 search,
                 tags,
                 tags_list,
+                drafts,
+                render_preview,
+                link_report,
+                rename_tag,
                 gen_feed,
+                named_feed,
+                legacy_rss,
+                legacy_rss_xml,
+                legacy_atom_xml,
+                legacy_feed_xml,
+                legacy_index_xml,
+                feeds,
+                sitemap,
+                redirect_trailing_slash,
+                ready,
                 mention
             ],
         )
-        .mount("/assets", FileServer::from("./articles/assets"))
-        .mount("/static", FileServer::from("./static"))
-        .launch()
-        .await
-        .expect("Rocket failed");
+        .register("/admin", catchers![admin::unauthorized])
+        .mount("/assets", FileServer::from("./articles/assets"));
+    #[cfg(feature = "embed-assets")]
+    let rocket = rocket.mount("/static", routes![embedded_static]);
+    #[cfg(not(feature = "embed-assets"))]
+    let rocket = rocket.mount("/static", FileServer::from("./static"));
+    rocket
 }
 
 #[get("/")]
-async fn render_homepage() -> Result<Template, ArticleError> {
-    show_article(ArticlePath("articles/index.md".into())).await
+async fn render_homepage(
+    accept: Option<&Accept>,
+    unlocks: Unlocks<'_>,
+) -> Result<ArticleResponse, ArticleError> {
+    let Some(path) = home::article() else {
+        return home_listing(accept).await.map(ArticleResponse::Listing);
+    };
+    let path: Arc<Path> = path.into();
+    let article = article::get_article(&path).await?;
+    if !unlocks.allows(&path, article.password_hash.as_deref()) {
+        return locked_view(&article, false).map(ArticleResponse::Locked);
+    }
+    article_view(&path, article, accept, vec![]).map(ArticleResponse::Page)
+}
+
+#[derive(Responder)]
+enum NegotiatedBody<T: Serialize> {
+    Html(RawHtml<String>),
+    Json(Json<T>),
+}
+
+/// A page rendered as HTML or serialized as JSON, depending on what the client accepts.
+#[derive(Responder)]
+struct Negotiated<T: Serialize> {
+    body: NegotiatedBody<T>,
+    vary: Header<'static>,
+}
+
+impl<T: Serialize> Negotiated<T> {
+    /// Serialize `data` if the client prefers JSON, otherwise render it with `html`.
+    fn new(
+        accept: Option<&Accept>,
+        data: T,
+        html: impl FnOnce(T) -> Result<RawHtml<String>, ArticleError>,
+    ) -> Result<Self, ArticleError> {
+        let body = if accept.is_some_and(|a| a.preferred().is_json()) {
+            NegotiatedBody::Json(Json(data))
+        } else {
+            NegotiatedBody::Html(html(data)?)
+        };
+        Ok(Negotiated {
+            body,
+            vary: Header::new("Vary", "Accept"),
+        })
+    }
+}
+
+/// A request guard for clients that prefer JSON to HTML.
+struct PrefersJson;
+
+#[async_trait]
+impl<'r> FromRequest<'r> for PrefersJson {
+    type Error = ();
+
+    async fn from_request(request: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.accept() {
+            Some(accept) if accept.preferred().is_json() => Outcome::Success(PrefersJson),
+            _ => Outcome::Forward(Status::NotFound),
+        }
+    }
+}
+
+/// Answers JSON clients asking for something that doesn't exist with a [`JsonError`], rather
+/// than the HTML error page they'd get otherwise.
+#[get("/<_..>", rank = 20)]
+fn missing_json(_json: PrefersJson) -> JsonError {
+    JsonError(ArticleError::NoArticle)
+}
+
+/// An article, with the `Content-Security-Policy` it asks for.
+struct ArticlePage {
+    page: Negotiated<Arc<Article>>,
+    csp: Header<'static>,
+    compressed: Arc<compress::Bodies>,
+}
+
+impl<'r> Responder<'r, 'static> for ArticlePage {
+    fn respond_to(self, request: &'r rocket::Request<'_>) -> rocket::response::Result<'static> {
+        compress::keep_in(request, self.compressed);
+        let mut response = self.page.respond_to(request)?;
+        response.set_header(self.csp);
+        Ok(response)
+    }
+}
+
+#[derive(Responder)]
+enum ArticleResponse {
+    Page(ArticlePage),
+    Listing(Negotiated<Vec<(Arc<Path>, Arc<ArticleMeta>)>>),
+    Locked((Status, RawHtml<String>)),
+    Moved(Redirect),
 }
 
 #[get("/<article..>")]
-async fn show_article(article: ArticlePath) -> Result<Template, ArticleError> {
-    let article = article::get_article(&article.0.into()).await?;
-    Ok((&*article).into())
+async fn show_article(
+    article: ArticlePath,
+    uri: &Origin<'_>,
+    accept: Option<&Accept>,
+    unlocks: Unlocks<'_>,
+) -> Result<ArticleResponse, ArticleError> {
+    let path: Arc<Path> = article.0.into();
+    let article = article::get_article(&path).await?;
+    if let Some(slug) = &article.meta.slug {
+        let canonical = url::slug_path(&path, slug);
+        if uri.path().as_str() != canonical {
+            return Ok(ArticleResponse::Moved(Redirect::permanent(url::site_path(
+                &canonical,
+            ))));
+        }
+    } else if CONFIG.case_insensitive_paths {
+        // Only the case is corrected, leaving other spellings that reach the same file alone.
+        let canonical = format!("/{}", url::encode_path(&url::article_rel(&path)));
+        let requested = uri.path().as_str();
+        if requested != canonical && requested.to_lowercase() == canonical.to_lowercase() {
+            return Ok(ArticleResponse::Moved(Redirect::permanent(url::site_path(
+                &canonical,
+            ))));
+        }
+    }
+    if !unlocks.allows(&path, article.password_hash.as_deref()) {
+        return locked_view(&article, false).map(ArticleResponse::Locked);
+    }
+    let read_next = read_next::suggestions(&path, &article.meta).await?;
+    article_view(&path, article, accept, read_next).map(ArticleResponse::Page)
+}
+
+/// The newest articles, shown at `/` when there's no homepage article.
+async fn home_listing(
+    accept: Option<&Accept>,
+) -> Result<Negotiated<Vec<(Arc<Path>, Arc<ArticleMeta>)>>, ArticleError> {
+    let articles = article::search(&Search {
+        sort_type: SortType::CreateDesc,
+        limit: Some(CONFIG.home.limit),
+        ..Default::default()
+    })
+    .await?;
+    let feeds = [FeedLink::main()];
+    Negotiated::new(accept, articles, |articles| {
+        filters::render_page("home", context! { articles, feeds })
+    })
+}
+
+#[derive(FromForm)]
+struct Unlock {
+    password: String,
+}
+
+/// Check the password for a protected article, and send the visitor back to it if it's right.
+#[post("/<article..>", data = "<form>")]
+async fn unlock_article(
+    article: ArticlePath,
+    form: Form<Unlock>,
+    uri: &Origin<'_>,
+    unlocks: Unlocks<'_>,
+) -> Result<ArticleResponse, ArticleError> {
+    let path: Arc<Path> = article.0.into();
+    let article = article::get_article(&path).await?;
+    let back = Redirect::to(url::site_path(uri.path().as_str()));
+    let Some(hash) = article.password_hash.clone() else {
+        return Ok(ArticleResponse::Moved(back));
+    };
+    let password = form.into_inner().password;
+    let correct = {
+        let hash = hash.clone();
+        tokio::task::spawn_blocking(move || protect::verify(&hash, &password)).await?
+    };
+    if correct {
+        unlocks.unlock(&path, &hash);
+        Ok(ArticleResponse::Moved(back))
+    } else {
+        // Still mark the response private, since it's about a protected article.
+        unlocks.allows(&path, Some(&hash));
+        locked_view(&article, true).map(ArticleResponse::Locked)
+    }
+}
+
+/// The password form shown in place of a protected article.
+fn locked_view(article: &Article, failed: bool) -> Result<(Status, RawHtml<String>), ArticleError> {
+    let status = if failed {
+        Status::Forbidden
+    } else {
+        Status::Ok
+    };
+    let page = filters::render_page(
+        "locked",
+        context! {
+            title: &article.meta.title,
+            failed,
+        },
+    )?;
+    Ok((status, page))
+}
+
+/// Refuse a password-protected article to visitors who haven't unlocked it.
+async fn check_unlocked(path: &Arc<Path>, unlocks: &Unlocks<'_>) -> Result<(), ArticleError> {
+    let hash = article::password_hash(path).await?;
+    if unlocks.allows(path, hash.as_deref()) {
+        Ok(())
+    } else {
+        Err(ArticleError::Locked)
+    }
+}
+
+fn article_view(
+    path: &Path,
+    article: Arc<Article>,
+    accept: Option<&Accept>,
+    read_next: Vec<read_next::Suggestion>,
+) -> Result<ArticlePage, ArticleError> {
+    let csp = security::csp(article.meta.csp.as_deref());
+    let compressed = article.compressed.clone();
+    let page = Negotiated::new(accept, article, |article| {
+        let edit_url = if article.meta.no_edit {
+            None
+        } else {
+            url::edit_url(path)
+        };
+        let root = roots::for_file(path).map(|(root, _)| &root.name);
+        article.render(context! { edit_url, root, read_next: &read_next })
+    })?;
+    Ok(ArticlePage {
+        page,
+        csp,
+        compressed,
+    })
 }
 
-pub struct Feed(pub atom_syndication::Feed);
+#[derive(Responder)]
+enum PrintView {
+    Html(RawHtml<String>),
+    Pdf(Download),
+}
+
+/// An article's table of contents alone, for clients that navigate without reloading pages.
+#[get("/toc/<article..>")]
+async fn article_toc(
+    article: Result<ArticlePath, ArticleError>,
+    unlocks: Unlocks<'_>,
+) -> Result<Json<Vec<article::Toc>>, JsonError> {
+    let path: Arc<Path> = article?.0.into();
+    check_unlocked(&path, &unlocks).await?;
+    let article = article::get_article(&path).await?;
+    Ok(Json(article.meta.toc.clone()))
+}
+
+/// The webmentions an article has received, as `/mentions/<article>.json`, newest first.
+///
+/// `limit` and `offset` page through them.
+#[get("/mentions/<article..>?<limit>&<offset>")]
+async fn article_mentions(
+    article: PathBuf,
+    limit: Option<u32>,
+    offset: Option<u32>,
+    unlocks: Unlocks<'_>,
+) -> Result<Json<Vec<db::MentionDetail>>, JsonError> {
+    let rel = article
+        .to_str()
+        .and_then(|rel| rel.strip_suffix(".json"))
+        .ok_or(ArticleError::NoArticle)?;
+    let path: Arc<Path> = article::resolve(Path::new(rel))
+        .ok_or(ArticleError::NoArticle)?
+        .into();
+    check_unlocked(&path, &unlocks).await?;
+    let canonical = url::article_rel(&path);
+    // Mentions are kept under the URL they were sent to, which may be the article's slug.
+    let slug = article::get_article(&path)
+        .await?
+        .meta
+        .slug
+        .as_deref()
+        .map(|slug| {
+            let dir = Path::new(&canonical).parent().unwrap_or(Path::new(""));
+            dir.join(slug).to_string_lossy().into_owned()
+        });
+    let mut targets = vec![canonical.as_str(), rel];
+    targets.extend(slug.as_deref());
+    let mentions = db::mentions_of(&targets, limit, offset.unwrap_or_default()).await;
+    Ok(Json(mentions))
+}
+
+#[get("/print/<article..>?<pdf>")]
+async fn print_article(
+    article: ArticlePath,
+    pdf: bool,
+    unlocks: Unlocks<'_>,
+) -> Result<PrintView, ArticleError> {
+    let path: Arc<Path> = article.0.into();
+    check_unlocked(&path, &unlocks).await?;
+    if pdf {
+        return Ok(PrintView::Pdf(
+            Download::export(&path, ExportFormat::Pdf).await?,
+        ));
+    }
+    let article = article::get_article(&path).await?;
+    Ok(PrintView::Html(filters::render_page(
+        "print",
+        context! {
+            meta: &article.meta,
+            content: &article.content,
+        },
+    )?))
+}
+
+#[get("/pdf/<article..>")]
+async fn pdf_article(article: ArticlePath, unlocks: Unlocks<'_>) -> Result<Download, ArticleError> {
+    let path: Arc<Path> = article.0.into();
+    check_unlocked(&path, &unlocks).await?;
+    Download::export(&path, ExportFormat::Pdf).await
+}
+
+#[get("/epub/<article..>")]
+async fn epub_article(
+    article: ArticlePath,
+    unlocks: Unlocks<'_>,
+) -> Result<Download, ArticleError> {
+    let path: Arc<Path> = article.0.into();
+    check_unlocked(&path, &unlocks).await?;
+    Download::export(&path, ExportFormat::Epub).await
+}
+
+/// A binary file served as an attachment.
+pub struct Download {
+    pub content_type: ContentType,
+    pub filename: String,
+    pub body: Arc<Vec<u8>>,
+}
+
+impl Download {
+    async fn export(path: &Arc<Path>, format: ExportFormat) -> Result<Self, ArticleError> {
+        let (meta, body) = article::export(path, format).await?;
+        Ok(Self {
+            content_type: format.content_type(),
+            filename: download_filename(&meta.title, format.extension()),
+            body,
+        })
+    }
+}
+
+impl<'r, 'o: 'r> Responder<'r, 'o> for Download {
+    fn respond_to(self, request: &'r rocket::Request<'_>) -> rocket::response::Result<'o> {
+        let mut response = (self.content_type, self.body.to_vec()).respond_to(request)?;
+        response.set_raw_header(
+            "Content-Disposition",
+            format!("attachment; filename=\"{}\"", self.filename),
+        );
+        Ok(response)
+    }
+}
+
+/// Turn an article title into a filesystem- and header-safe filename.
+fn download_filename(title: &str, extension: &str) -> String {
+    let stem = title
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    let stem = if stem.is_empty() { "article" } else { &stem };
+    format!("{stem}.{extension}")
+}
+
+pub struct Feed(pub Arc<feed_cache::CachedFeed>);
 
 impl<'r, 'o: 'r> Responder<'r, 'o> for Feed {
     fn respond_to(self, request: &'r rocket::Request<'_>) -> rocket::response::Result<'o> {
-        let response = self.0.to_string();
-        let mut response = response.respond_to(request)?;
+        let mut response = self.0.xml.clone().respond_to(request)?;
         response.set_header(ContentType::new("application", "atom+xml"));
+        response.set_header(Header::new(
+            "Last-Modified",
+            self.0
+                .updated
+                .with_timezone(&Utc)
+                .format("%a, %d %b %Y %H:%M:%S GMT")
+                .to_string(),
+        ));
         Ok(response)
     }
 }
@@ -97,37 +559,133 @@ impl<'r> FromRequest<'r> for ModifiedSince {
     }
 }
 
-#[get("/feed/<path..>")]
+// Only one is built per request, so the size of the redirect doesn't matter.
+#[allow(clippy::large_enum_variant)]
+#[derive(Responder)]
+enum FeedResponse {
+    Feed(Feed),
+    Moved(Redirect),
+    NotModified(rocket::response::status::Custom<()>),
+}
+
+#[get("/feed/<path..>?<tags>&<content>&<page>", rank = 2)]
 async fn gen_feed(
     path: PathBuf,
+    tags: Vec<String>,
+    content: Option<FeedContent>,
+    page: Option<usize>,
     modified_since: Option<ModifiedSince>,
-) -> Result<Feed, ArticleError> {
-    fn naive_date_to_time(date: NaiveDate) -> DateTime<FixedOffset> {
-        FixedOffset::east_opt(0)
-            .unwrap()
-            .from_local_datetime(&NaiveDateTime::new(date, NaiveTime::default()))
-            .unwrap()
+    uri: &Origin<'_>,
+) -> Result<FeedResponse, ArticleError> {
+    // Subscribers to the bare `/feed` follow it to the primary feed if that's another one.
+    if path.as_os_str().is_empty()
+        && uri.query().is_none()
+        && modified_since.is_none()
+        && !url::primary_feed_is_default()
+    {
+        return Ok(FeedResponse::Moved(primary_feed_redirect()));
+    }
+    let search = Search::builder()
+        .path(path.clone())
+        .tags(tags.clone())
+        .in_feed(InFeed::Main);
+    let title = FeedLink::new(&path.to_string_lossy(), &tags).title;
+    let id = url::absolute_url(&path.to_string_lossy());
+    build_feed(search, title, id, content, page, modified_since, uri).await
+}
+
+/// The name of a feed some article routes itself into with `feeds`. Other names are forwarded to
+/// [`gen_feed`], so a directory called `named` keeps its feeds.
+struct FeedName<'r>(&'r str);
+
+impl<'r> FromParam<'r> for FeedName<'r> {
+    type Error = &'r str;
+
+    fn from_param(name: &'r str) -> Result<Self, Self::Error> {
+        if article::names_feed(name) {
+            Ok(Self(name))
+        } else {
+            Err(name)
+        }
     }
+}
+
+/// The feed of articles that name it in their `feeds`, which are left out of the main feeds.
+#[get("/feed/named/<name>?<content>&<page>", rank = 1)]
+async fn named_feed(
+    name: FeedName<'_>,
+    content: Option<FeedContent>,
+    page: Option<usize>,
+    modified_since: Option<ModifiedSince>,
+    uri: &Origin<'_>,
+) -> Result<FeedResponse, ArticleError> {
+    let name = name.0;
+    let search = Search::builder().in_feed(InFeed::Named(name.to_string()));
+    let link = FeedLink::named(name);
+    let id = url::absolute_url(&format!("feed/named/{name}"));
+    build_feed(search, link.title, id, content, page, modified_since, uri).await
+}
+
+/// Build a feed of the articles `search` finds, newest first, or reuse the one built for the
+/// same request if none of its articles have changed.
+///
+/// Clients asking for articles since a date when none are newer are told the feed is unchanged.
+async fn build_feed(
+    search: SearchBuilder,
+    title: String,
+    id: String,
+    content: Option<FeedContent>,
+    page: Option<usize>,
+    modified_since: Option<ModifiedSince>,
+    uri: &Origin<'_>,
+) -> Result<FeedResponse, ArticleError> {
+    // Only the newest entries are in the feed itself, with older ones on later pages, unless the
+    // client asked for everything since a date.
+    let limit = modified_since.is_none().then_some(CONFIG.feed_limit.max(1));
+    let page = page.unwrap_or(0);
     let search = Search {
-        created: (
-            match modified_since {
-                Some(t) => Bound::Included(t.0.date_naive()),
-                None => Bound::Unbounded,
-            },
-            Bound::Unbounded,
-        ),
-        search_path: path.clone(),
-        ..Default::default()
+        // Feeds stay chronological.
+        ignore_pins: true,
+        ..search
+            .created_since(
+                modified_since
+                    .as_ref()
+                    .map(|t| timezone::in_zone(t.0).date_naive()),
+            )
+            .sort(SortType::PublishDesc)
+            .limit(limit)
+            .page(page)
+            .build()
     };
-    let mut search = article::search(&search).await?;
-    dbg!(search.len());
-    search.retain(|(_, a)| !a.exclude_from_rss);
-    let mut rt = Handle::current();
+    let (search, capped) = article::search_page(&search).await?;
+    let updated = timezone::start_of(
+        search
+            .iter()
+            .map(|(_, a)| a.updated)
+            .max()
+            .unwrap_or_default(),
+    );
+    if let Some(since) = &modified_since {
+        if updated <= since.0 {
+            return Ok(FeedResponse::NotModified(rocket::response::status::Custom(
+                Status::NotModified,
+                (),
+            )));
+        }
+    }
+    let key = format!(
+        "{uri} {}",
+        modified_since.map(|t| t.0.to_rfc3339()).unwrap_or_default()
+    );
+    let fingerprint = feed_cache::Fingerprint::new(&search, capped);
+    if let Some(feed) = feed_cache::get(&key, &fingerprint) {
+        return Ok(FeedResponse::Feed(Feed(feed)));
+    }
+    println!("Building the feed at {uri}");
     let search = {
         let mut new = vec![];
-        for (path, meta) in search {
-            let Ok(article) = article::get_article(&Path::new("articles").join(&path).into()).await
-            else {
+        for (path, _) in search {
+            let Ok(article) = article::get_article(&roots::disk_path(&path).into()).await else {
                 continue;
             };
             new.push((path.clone(), article));
@@ -135,20 +693,15 @@ async fn gen_feed(
         new
     };
     let feed = atom_syndication::Feed {
-        title: "Willow's blog".into(),
-        id: format!("https://wolo.dev/{}", path.to_string_lossy()),
-        base: Some("https://wolo.dev/".to_string()),
-        updated: naive_date_to_time(
-            search
-                .iter()
-                .map(|(_, a)| a.meta.updated)
-                .max()
-                .unwrap_or_default(),
-        ),
+        title: title.into(),
+        id,
+        base: Some(url::absolute_url("")),
+        lang: Some(CONFIG.lang.clone()),
+        updated,
         authors: vec![Person {
-            name: "Willow".into(),
-            email: Some("public@w.wolo.dev".into()),
-            uri: Some("https://wolo.dev".into()),
+            name: CONFIG.author.clone(),
+            email: feed_email(CONFIG.author_email.as_ref()),
+            uri: Some(url::author_url()),
         }],
         categories: search
             .iter()
@@ -164,56 +717,167 @@ async fn gen_feed(
             value: "Wolog".into(),
             ..Default::default()
         }),
-        links: vec![Link {
-            href: "https://wolo.dev".to_string(),
+        links: std::iter::once(Link {
+            href: url::absolute_url(""),
             rel: "alternate".to_string(),
             mime_type: Some("text/html".to_string()),
             ..Default::default()
-        }],
+        })
+        .chain(capped.then(|| feed_page_link("next", uri, page + 1)))
+        .chain((page > 0).then(|| feed_page_link("previous", uri, page - 1)))
+        .collect(),
         rights: Some("https://creativecommons.org/licenses/by-nc/4.0/".into()),
         entries: search
             .iter()
-            .map(|(p, a)| Entry {
-                title: a.meta.title.clone().into(),
-                id: p.to_string_lossy().to_string(),
-                updated: naive_date_to_time(a.meta.updated),
-                categories: a
+            .map(|(p, a)| {
+                let mode = a
                     .meta
-                    .tags
-                    .as_slice()
-                    .iter()
-                    .map(|t| Category {
-                        term: t.to_string(),
+                    .feed_content
+                    .or(content)
+                    .unwrap_or(CONFIG.feed_content);
+                let summary = match mode {
+                    FeedContent::Full => a
+                        .meta
+                        .excerpt
+                        .as_deref()
+                        .map(filters::markdown_to_html)
+                        .unwrap_or_else(|| a.content.clone()),
+                    FeedContent::Summary | FeedContent::Truncated => feed_summary(a),
+                };
+                let body = match mode {
+                    FeedContent::Full => Some(a.content.clone()),
+                    FeedContent::Summary => None,
+                    FeedContent::Truncated => Some(format!(
+                        "{summary}<p><a href=\"{}\">Read more…</a></p>",
+                        url::article_url(p)
+                    )),
+                };
+                Entry {
+                    title: a.meta.title.clone().into(),
+                    id: url::article_url(p),
+                    updated: timezone::start_of(a.meta.updated),
+                    categories: a
+                        .meta
+                        .tags
+                        .as_slice()
+                        .iter()
+                        .map(|t| Category {
+                            term: t.to_string(),
+                            ..Default::default()
+                        })
+                        .collect(),
+                    authors: a
+                        .meta
+                        .authors
+                        .iter()
+                        .map(|author| Person {
+                            name: author.name.clone(),
+                            email: feed_email(author.email.as_ref()),
+                            uri: author.uri.clone(),
+                        })
+                        .collect(),
+                    links: std::iter::once(Link {
+                        href: url::article_url(p),
+                        rel: "alternate".to_string(),
+                        mime_type: Some("text/html".to_string()),
+                        hreflang: Some(a.meta.lang().to_string()),
                         ..Default::default()
                     })
+                    .chain(a.meta.translations.iter().map(|t| Link {
+                        href: url::absolute_url(&t.path),
+                        rel: "alternate".to_string(),
+                        mime_type: Some("text/html".to_string()),
+                        hreflang: Some(t.lang.clone()),
+                        ..Default::default()
+                    }))
                     .collect(),
-                contributors: vec![],
-                links: vec![Link {
-                    href: format!("https://wolo.dev/{}", p.to_string_lossy()),
-                    rel: "alternate".to_string(),
-                    mime_type: Some("text/html".to_string()),
-                    ..Default::default()
-                }],
-                published: Some(naive_date_to_time(a.meta.created)),
-                summary: Some(Text {
-                    base: Some(format!("https://wolo.dev/{}", p.to_string_lossy())),
-                    value: a.content.clone(),
-                    r#type: atom_syndication::TextType::Html,
+                    published: Some(timezone::start_of(a.meta.published_date())),
+                    summary: Some(Text {
+                        base: Some(url::article_url(p)),
+                        value: summary,
+                        lang: Some(a.meta.lang().to_string()),
+                        r#type: atom_syndication::TextType::Html,
+                    }),
+                    content: body.map(|body| Content {
+                        base: Some(url::article_url(p)),
+                        value: Some(body),
+                        lang: Some(a.meta.lang().to_string()),
+                        src: Some(url::article_url(p)),
+                        content_type: Some("text/html".into()),
+                    }),
                     ..Default::default()
-                }),
-                content: Some(Content {
-                    base: Some(format!("https://wolo.dev/{}", p.to_string_lossy())),
-                    value: Some(a.content.clone()),
-                    src: Some(format!("https://wolo.dev/{}", p.to_string_lossy())),
-                    content_type: Some("text/html".into()),
-                    ..Default::default()
-                }),
-                ..Default::default()
+                }
             })
             .collect(),
         ..Default::default()
     };
-    Ok(Feed(feed))
+    let feed = Arc::new(feed_cache::CachedFeed {
+        xml: feed.to_string(),
+        updated,
+    });
+    feed_cache::insert(key, fingerprint, feed.clone());
+    Ok(FeedResponse::Feed(Feed(feed)))
+}
+
+fn primary_feed_redirect() -> Redirect {
+    Redirect::moved(url::primary_feed())
+}
+
+// Where other blogging tools serve their feeds, so subscribers carried over from them keep
+// getting posts.
+
+#[get("/rss")]
+fn legacy_rss() -> Redirect {
+    primary_feed_redirect()
+}
+
+#[get("/rss.xml")]
+fn legacy_rss_xml() -> Redirect {
+    primary_feed_redirect()
+}
+
+#[get("/atom.xml")]
+fn legacy_atom_xml() -> Redirect {
+    primary_feed_redirect()
+}
+
+#[get("/feed.xml")]
+fn legacy_feed_xml() -> Redirect {
+    primary_feed_redirect()
+}
+
+#[get("/index.xml")]
+fn legacy_index_xml() -> Redirect {
+    primary_feed_redirect()
+}
+
+/// A link to another page of a paged feed, as in RFC 5005.
+fn feed_page_link(rel: &str, uri: &Origin<'_>, page: usize) -> Link {
+    Link {
+        href: format!(
+            "{}{}",
+            CONFIG.url.trim_end_matches('/'),
+            page_url(uri, page)
+        ),
+        rel: rel.to_string(),
+        mime_type: Some("application/atom+xml".to_string()),
+        ..Default::default()
+    }
+}
+
+/// An author's email address, if feeds may show it.
+fn feed_email(email: Option<&String>) -> Option<String> {
+    email.filter(|_| CONFIG.feed_include_email).cloned()
+}
+
+/// An article's excerpt, or failing that its text shortened by the
+/// [`summary`](config::Config::summary) policy, as HTML.
+fn feed_summary(article: &Article) -> String {
+    if let Some(excerpt) = &article.meta.excerpt {
+        return filters::markdown_to_html(excerpt);
+    }
+    let summary = summary::summarize(&article.plain_text, &CONFIG.summary);
+    format!("<p>{}</p>", escape_html(&summary))
 }
 
 #[derive(Debug, Clone, Copy, Deserialize, Serialize)]
@@ -241,8 +905,18 @@ impl<'r> FromFormField<'r> for DateField {
     }
 }
 
+/// How `/search` renders its results as HTML.
+#[derive(FromFormField, Default, Clone, Copy, PartialEq, Eq)]
+enum SearchFormat {
+    /// A whole page, with the search form.
+    #[default]
+    Full,
+    /// Just the results and a link to the next page, for embedding with an iframe or `fetch`.
+    Minimal,
+}
+
 #[allow(clippy::too_many_arguments)]
-#[get("/search/<search_path..>?<created_since>&<created_before>&<updated_since>&<updated_before>&<tags>&<title_filter>&<sort_type>")]
+#[get("/search/<search_path..>?<created_since>&<created_before>&<updated_since>&<updated_before>&<tags>&<title_filter>&<sort_type>&<limit>&<page>&<format>&<since>")]
 async fn search(
     search_path: PathBuf,
     tags: Vec<String>,
@@ -252,60 +926,62 @@ async fn search(
     updated_before: Option<DateField>,
     title_filter: Option<String>,
     sort_type: Option<SortType>,
-) -> Result<Template, ArticleError> {
-    let created = (
-        created_since
-            .as_deref()
-            .cloned()
-            .map(Bound::Included)
-            .unwrap_or(Bound::Unbounded),
-        created_before
-            .as_deref()
-            .cloned()
-            .map(Bound::Included)
-            .unwrap_or(Bound::Unbounded),
-    );
-    let updated = (
-        updated_since
-            .as_deref()
-            .cloned()
-            .map(Bound::Included)
-            .unwrap_or(Bound::Unbounded),
-        updated_before
-            .as_deref()
-            .cloned()
-            .map(Bound::Included)
-            .unwrap_or(Bound::Unbounded),
-    );
+    limit: Option<usize>,
+    page: Option<usize>,
+    format: Option<SearchFormat>,
+    since: Option<DateField>,
+    uri: &Origin<'_>,
+    accept: Option<&Accept>,
+) -> Result<Negotiated<Vec<(Arc<Path>, Arc<ArticleMeta>)>>, ArticleError> {
     let sort_type = sort_type.unwrap_or_default();
-    let search = Search {
-        search_path: search_path.clone(),
-        title_filter: title_filter.clone(),
-        tags: tags.clone(),
-        sort_type,
-        created,
-        updated,
-        ..Default::default()
-    };
-    let articles = article::search(&search).await?;
-    Ok(Template::render(
-        "page-list",
-        context! {
-            search_path,
-            sort_type,
-            title_filter,
-            tags,
-            created_since,
-            created_before,
-            updated_since,
-            updated_before,
-            articles
-        },
-    ))
+    let search = Search::builder()
+        .path(search_path.clone())
+        .title_filter(title_filter.clone())
+        .tags(tags.clone())
+        .sort(sort_type)
+        .created_since(created_since.map(|d| d.0))
+        .created_before(created_before.map(|d| d.0))
+        .updated_since(updated_since.map(|d| d.0))
+        .updated_before(updated_before.map(|d| d.0))
+        .limit(limit)
+        .page(page)
+        .build();
+    let (articles, capped) = article::search_page(&search).await?;
+    let next_page = capped.then(|| page_url(uri, page.unwrap_or(0) + 1));
+    let feeds = [FeedLink::new(&search_path.to_string_lossy(), &tags)];
+    Negotiated::new(accept, articles, |articles| {
+        let articles = mark_new_since(articles, since);
+        if format.unwrap_or_default() == SearchFormat::Minimal {
+            return filters::render_page(
+                "frag-search-results",
+                context! {
+                    articles,
+                    next_page
+                },
+            );
+        }
+        filters::render_page(
+            "page-list",
+            context! {
+                search_path,
+                sort_type,
+                title_filter,
+                tags,
+                created_since,
+                created_before,
+                updated_since,
+                updated_before,
+                articles,
+                capped,
+                next_page,
+                feeds
+            },
+        )
+    })
 }
 
 #[get("/tags/list")]
-async fn tags_list() -> Result<Template, ArticleError> {
+async fn tags_list() -> Result<RawHtml<String>, ArticleError> {
     let articles = article::search(&Search::default()).await?;
     let tags: BTreeMap<&str, usize> = articles
         .iter()
@@ -314,36 +990,268 @@ async fn tags_list() -> Result<Template, ArticleError> {
             *acc.entry(el).or_insert(0) += 1;
             acc
         });
-    Ok(Template::render(
+    filters::render_page(
         "tag-directory",
         context! {
             tags
         },
-    ))
+    )
 }
 
-#[get("/tags/<search_path..>?<sort_type>&<tags..>")]
+#[get("/tags/<search_path..>?<sort_type>&<limit>&<page>&<since>&<tags..>")]
 async fn tags(
     search_path: PathBuf,
     tags: Vec<String>,
     sort_type: Option<SortType>,
-) -> Result<Template, ArticleError> {
+    limit: Option<usize>,
+    page: Option<usize>,
+    since: Option<DateField>,
+    uri: &Origin<'_>,
+) -> Result<RawHtml<String>, ArticleError> {
     let sort_type = sort_type.unwrap_or_default();
-    let articles = article::search(&Search {
-        search_path: search_path.clone(),
-        tags: tags.clone(),
-        sort_type,
-        ..Default::default()
-    })
+    let limit = tag_page_limit(limit);
+    let (articles, capped) = article::search_page(
+        &Search::builder()
+            .path(search_path.clone())
+            .tags(tags.clone())
+            .sort(sort_type)
+            .limit(limit)
+            .page(page)
+            .build(),
+    )
     .await?;
-    Ok(Template::render(
+    let next_page = capped.then(|| page_url(uri, page.unwrap_or(0) + 1));
+    let feeds = [FeedLink::new(&search_path.to_string_lossy(), &tags)];
+    let related_tags = related_tags(
+        &article::search_all(&Search {
+            search_path: search_path.clone(),
+            tags: tags.clone(),
+            ..Default::default()
+        })
+        .await?,
+        &tags,
+    );
+    let articles = mark_new_since(articles, since);
+    filters::render_page(
         "tag-list",
         context! {
             search_path,
             tags,
-            articles
+            articles,
+            capped,
+            next_page,
+            feeds,
+            related_tags
+        },
+    )
+}
+
+/// How many tags a tag page suggests as often appearing with its own.
+const RELATED_TAGS: usize = 8;
+
+/// The tags that appear most often alongside `tags` on `articles`, with how many articles they
+/// share, most first and alphabetically among equals.
+fn related_tags(
+    articles: &[(Arc<Path>, Arc<ArticleMeta>)],
+    tags: &[String],
+) -> Vec<(String, usize)> {
+    let mut related: Vec<(String, usize)> = articles
+        .iter()
+        .flat_map(|(_, meta)| meta.tags.iter())
+        .filter(|tag| !tags.contains(tag))
+        .fold(BTreeMap::new(), |mut acc, el| {
+            *acc.entry(el.clone()).or_insert(0) += 1;
+            acc
+        })
+        .into_iter()
+        .collect();
+    related.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    related.truncate(RELATED_TAGS);
+    related
+}
+
+/// Listed articles, each with whether it's been updated since `since`, such as a returning
+/// reader's last visit.
+fn mark_new_since(
+    articles: Vec<(Arc<Path>, Arc<ArticleMeta>)>,
+    since: Option<DateField>,
+) -> Vec<(Arc<Path>, Arc<ArticleMeta>, bool)> {
+    articles
+        .into_iter()
+        .map(|(path, meta)| {
+            let is_new_since = since.is_some_and(|since| meta.updated > *since);
+            (path, meta, is_new_since)
+        })
+        .collect()
+}
+
+/// The URL of another page of a paginated listing, keeping the rest of its query.
+fn page_url(uri: &Origin<'_>, page: usize) -> String {
+    let query = uri
+        .query()
+        .map(|q| q.as_str())
+        .unwrap_or("")
+        .split('&')
+        .filter(|p| !p.is_empty() && !p.starts_with("page="))
+        .chain([format!("page={page}").as_str()])
+        .collect::<Vec<_>>()
+        .join("&");
+    format!("{}?{query}", url::site_path(uri.path().as_str()))
+}
+
+/// How many articles a tag page shows, given the `limit` asked for.
+///
+/// Searches return no more than `max_search_results`, so larger limits are cut to that before
+/// they're used to find where a page starts.
+fn tag_page_limit(limit: Option<usize>) -> usize {
+    limit
+        .unwrap_or(CONFIG.tag_page_size)
+        .min(CONFIG.max_search_results)
+        .max(1)
+}
+
+/// How many articles a tag needs before the feed index lists its feed.
+const NOTABLE_TAG: usize = 2;
+
+#[get("/feeds")]
+async fn feeds() -> Result<RawHtml<String>, ArticleError> {
+    let articles = article::search(&Search::default()).await?;
+    let mut tags: Vec<(&str, usize)> = articles
+        .iter()
+        .flat_map(|(_, meta)| meta.tags.iter().map(|s| s.as_str()))
+        .fold(BTreeMap::new(), |mut acc, el| {
+            *acc.entry(el).or_insert(0) += 1;
+            acc
+        })
+        .into_iter()
+        .filter(|(_, count)| *count >= NOTABLE_TAG)
+        .collect();
+    tags.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    let tag_feeds: Vec<_> = tags
+        .into_iter()
+        .map(|(tag, count)| (FeedLink::new("", &[tag.to_string()]), count))
+        .collect();
+    let named_feeds: Vec<_> = articles
+        .iter()
+        .filter(|(_, meta)| meta.in_feeds())
+        .flat_map(|(_, meta)| meta.feeds.iter().flatten())
+        .filter(|name| *name != article::MAIN_FEED)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|name| FeedLink::named(name))
+        .collect();
+    filters::render_page(
+        "feeds",
+        context! {
+            main_feed: FeedLink::main(),
+            tag_feeds,
+            named_feeds
         },
-    ))
+    )
+}
+
+/// Where [`trailing_slash::TrailingSlashRedirect`] sends requests for non-canonical paths.
+#[get("/.trailing-slash")]
+fn redirect_trailing_slash(target: trailing_slash::Canonical) -> Redirect {
+    Redirect::moved(target.0)
+}
+
+/// Whether the wolog can serve requests, for load balancers and orchestrators to poll. It's
+/// dotted so it can't shadow an article.
+#[get("/.ready")]
+async fn ready() -> (Status, &'static str) {
+    match db::ping().await {
+        Ok(()) => (Status::Ok, "ready"),
+        Err(e) => {
+            eprintln!("Readiness check failed; the database can't be queried. {e}");
+            (Status::ServiceUnavailable, "database unavailable")
+        }
+    }
+}
+
+#[get("/sitemap.xml")]
+async fn sitemap() -> Result<(ContentType, String), ArticleError> {
+    let articles = article::search(&Search::default()).await?;
+    let mut xml = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+"#,
+    );
+    for (path, meta) in articles
+        .iter()
+        .filter(|(_, meta)| !meta.noindex && !meta.sitemap_exclude)
+    {
+        let loc = match &meta.slug {
+            Some(slug) => url::absolute_url(&url::slug_rel(path, slug)),
+            None => url::article_url(path),
+        };
+        let lastmod = if article::is_undated(meta.updated) {
+            String::new()
+        } else {
+            format!("<lastmod>{}</lastmod>", meta.updated)
+        };
+        xml += &format!(
+            "  <url><loc>{}</loc>{lastmod}</url>\n",
+            loc.replace('&', "&amp;").replace('<', "&lt;"),
+        );
+    }
+    xml += "</urlset>\n";
+    Ok((ContentType::XML, xml))
+}
+
+#[get("/admin/drafts")]
+async fn drafts(_admin: admin::Admin) -> Result<RawHtml<String>, ArticleError> {
+    let mut articles = article::search(&Search {
+        include_unready: true,
+        sort_type: SortType::UpdateDesc,
+        ..Default::default()
+    })
+    .await?;
+    articles.retain(|(_, meta)| meta.status() == ArticleStatus::Draft || meta.is_scheduled());
+    filters::render_page("drafts", context! { articles })
+}
+
+/// Render a markdown body to an HTML fragment, for editors to show a live preview.
+#[post("/admin/render", data = "<markdown>")]
+async fn render_preview(
+    _admin: admin::Admin,
+    markdown: Capped<String>,
+) -> Result<RawHtml<String>, ArticleError> {
+    if !markdown.is_complete() {
+        return Err(ArticleError::TooLarge);
+    }
+    article::preview(&markdown).await.map(RawHtml)
+}
+
+/// Report links in published articles that lead nowhere, fetching external ones too if
+/// `external` is set.
+#[get("/admin/links?<external>")]
+async fn link_report(
+    _admin: admin::Admin,
+    external: bool,
+) -> Result<Json<link_check::Report>, JsonError> {
+    Ok(Json(link_check::check(external).await?))
+}
+
+/// Rename a tag in the front matter of every article that has it.
+#[post("/admin/tags/rename?<from>&<to>")]
+async fn rename_tag(
+    _admin: admin::Admin,
+    from: &str,
+    to: &str,
+) -> Result<Json<tag_rename::Report>, JsonError> {
+    Ok(Json(tag_rename::rename(from, to).await?))
+}
+
+#[cfg(feature = "embed-assets")]
+#[get("/<path..>", rank = 10)]
+fn embedded_static(path: PathBuf) -> Option<(ContentType, &'static [u8])> {
+    let file = embedded::STATIC.get_file(&path)?;
+    let content_type = path
+        .extension()
+        .and_then(|ext| ContentType::from_extension(&ext.to_string_lossy()))
+        .unwrap_or(ContentType::Binary);
+    Some((content_type, file.contents()))
 }
 
 #[derive(FromForm)]
@@ -353,14 +1261,1025 @@ struct WebMention {
 }
 
 #[post("/webmention", data = "<webmention>")]
-async fn mention(webmention: Form<WebMention>) -> Status {
-    let Some(target) = webmention.target.strip_prefix(&*WOLOG_URL) else {
+async fn mention(webmention: Result<Form<WebMention>, rocket::form::Errors<'_>>) -> Status {
+    let webmention = match webmention {
+        Ok(webmention) => webmention,
+        Err(errors) if errors.status() == Status::PayloadTooLarge => {
+            return Status::PayloadTooLarge
+        }
+        Err(_) => return Status::BadRequest,
+    };
+    let (Some(source), Some(target)) = (
+        url::parse_http_url(&webmention.source),
+        url::parse_http_url(&webmention.target),
+    ) else {
+        return Status::BadRequest;
+    };
+    if source == target {
+        return Status::BadRequest;
+    }
+    let Some(target) = url::strip_base(&webmention.target) else {
         return Status::BadRequest;
     };
-    let target = target.trim_start_matches("/");
-    tokio::spawn(db::received_webmention(
+    shutdown::spawn(db::received_webmention(
         webmention.source.clone(),
-        target.to_string(),
+        url::decode_path(target),
     ));
     Status::Accepted
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, str::FromStr, sync::Arc};
+
+    use rocket::{
+        http::{Accept, ContentType, Header, Status},
+        local::asynchronous::Client,
+    };
+
+    use super::{
+        mark_new_since, related_tags, tag_page_limit, ArticleMeta, DateField, NaiveDate,
+        RELATED_TAGS,
+    };
+    use crate::{
+        article::{self, Renderer},
+        config::CONFIG,
+        feed_cache,
+        testing::{client, config, pandoc_installed, run_with, Fixture, ADMIN_TOKEN},
+        url,
+    };
+
+    async fn feed(client: &Client, uri: &str) -> atom_syndication::Feed {
+        let response = client.get(uri).dispatch().await;
+        assert_eq!(response.status(), Status::Ok, "{uri}");
+        atom_syndication::Feed::from_str(&response.into_string().await.unwrap()).unwrap()
+    }
+
+    #[rocket::async_test]
+    async fn cached_articles_are_served_compressed() {
+        let fixture = Fixture::new("compressed");
+        let body = "A paragraph long enough to be worth compressing. ".repeat(40);
+        fixture
+            .article(
+                "post",
+                &format!("---\ntitle: Compressed\nstatus: published\n---\n\n{body}\n"),
+            )
+            .await;
+        let client = client().await;
+        let uri = format!("/{}", fixture.rel("post"));
+        // The first request compresses the page and the second is served what was kept.
+        for _ in 0..2 {
+            let response = client
+                .get(&uri)
+                .header(Header::new("Accept-Encoding", "br"))
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::Ok);
+            assert_eq!(response.headers().get_one("Content-Encoding"), Some("br"));
+        }
+    }
+
+    #[rocket::async_test]
+    async fn feed_entries_list_every_author() {
+        let fixture = Fixture::new("authors");
+        fixture
+            .article(
+                "post",
+                "---\ntitle: Together\nstatus: published\nauthors:\n  - Ada\n  - name: Grace\n    uri: https://grace.example/\n---\n\nHi.\n",
+            )
+            .await;
+        let feed = feed(&client().await, &format!("/feed/{}", fixture.rel(""))).await;
+        let [entry] = feed.entries() else {
+            panic!("The feed should have one entry");
+        };
+        let authors: Vec<_> = entry
+            .authors()
+            .iter()
+            .map(|a| (a.name(), a.uri()))
+            .collect();
+        assert_eq!(
+            authors,
+            [("Ada", None), ("Grace", Some("https://grace.example/"))]
+        );
+    }
+
+    #[rocket::async_test]
+    async fn pages_carry_their_articles_language() {
+        check_pages_carry_their_articles_language("lang").await;
+    }
+
+    #[test]
+    fn pandoc_rendered_pages_carry_their_articles_language() {
+        // Pandoc isn't always installed where tests run.
+        if !pandoc_installed() {
+            return;
+        }
+        run_with(
+            config(|c| c.renderer = Renderer::Pandoc),
+            check_pages_carry_their_articles_language("lang-pandoc"),
+        );
+    }
+
+    /// Check that pages in the fixture `name` are marked with their articles' languages.
+    async fn check_pages_carry_their_articles_language(name: &str) {
+        let fixture = Fixture::new(name);
+        fixture
+            .article(
+                "fr",
+                "---\ntitle: Bonjour\nstatus: published\nlang: fr\n---\n\nSalut.\n",
+            )
+            .await;
+        fixture
+            .article(
+                "default",
+                "---\ntitle: Hello\nstatus: published\n---\n\nHi.\n",
+            )
+            .await;
+        let client = client().await;
+        let page = |rel: &str| {
+            let uri = format!("/{}", fixture.rel(rel));
+            let client = &client;
+            async move {
+                client
+                    .get(uri)
+                    .dispatch()
+                    .await
+                    .into_string()
+                    .await
+                    .unwrap()
+            }
+        };
+        assert!(page("fr").await.contains(r#"<html lang="fr">"#));
+        assert!(page("default")
+            .await
+            .contains(&format!(r#"<html lang="{}">"#, CONFIG.lang)));
+    }
+
+    #[rocket::async_test]
+    async fn translations_link_to_each_other() {
+        let fixture = Fixture::new("translations");
+        let english = format!(
+            "---\ntitle: Hello\nstatus: published\ntranslations:\n  - lang: fr\n    path: /{}\n  - lang: de\n    path: /{}\n---\n\nHi.\n",
+            fixture.rel("post.fr"),
+            fixture.rel("beitrag")
+        );
+        let french = format!(
+            "---\ntitle: Bonjour\nstatus: published\nlang: fr\ntranslations:\n  - lang: en\n    path: /{}\n---\n\nSalut.\n",
+            fixture.rel("post")
+        );
+        // Each links to the other, so both are written before either is rendered.
+        fixture.write("post.fr", &french);
+        fixture.write("post", &english);
+        let client = client().await;
+
+        let page = client
+            .get(format!("/{}", fixture.rel("post")))
+            .dispatch()
+            .await
+            .into_string()
+            .await
+            .unwrap();
+        assert!(page.contains(&format!(
+            r#"href="{}""#,
+            url::site_path(&format!("/{}", fixture.rel("post.fr")))
+        )));
+        assert!(page.contains(r#"hreflang="fr""#));
+        // The German translation doesn't exist, so it isn't linked.
+        assert!(!page.contains(r#"hreflang="de""#));
+
+        let page = client
+            .get(format!("/{}", fixture.rel("post.fr")))
+            .dispatch()
+            .await
+            .into_string()
+            .await
+            .unwrap();
+        assert!(page.contains(r#"<html lang="fr">"#));
+        assert!(page.contains(r#"hreflang="en""#));
+    }
+
+    #[rocket::async_test]
+    async fn articles_are_served_as_json_when_preferred() {
+        let fixture = Fixture::new("negotiated");
+        fixture
+            .article(
+                "post",
+                "---\ntitle: Negotiable\nstatus: published\n---\n\nHi.\n",
+            )
+            .await;
+        let client = client().await;
+        let uri = format!("/{}", fixture.rel("post"));
+
+        let response = client.get(&uri).header(Accept::JSON).dispatch().await;
+        assert_eq!(response.content_type(), Some(ContentType::JSON));
+        let json: serde_json::Value = response.into_json().await.unwrap();
+        assert_eq!(json["meta"]["title"], "Negotiable");
+        assert!(json["content"].as_str().unwrap().contains("Hi."));
+
+        let response = client.get(&uri).dispatch().await;
+        assert_eq!(response.content_type(), Some(ContentType::HTML));
+        assert!(response.into_string().await.unwrap().contains("<title>"));
+    }
+
+    #[rocket::async_test]
+    async fn the_homepage_links_to_its_feed() {
+        let client = client().await;
+        let home = client
+            .get("/")
+            .dispatch()
+            .await
+            .into_string()
+            .await
+            .unwrap();
+        let home = scraper::Html::parse_document(&home);
+        let feed_links =
+            scraper::Selector::parse(r#"head link[rel="alternate"][type="application/atom+xml"]"#)
+                .unwrap();
+        let hrefs: Vec<_> = home
+            .select(&feed_links)
+            .filter_map(|link| link.attr("href"))
+            .collect();
+        assert!(hrefs.contains(&&*url::primary_feed()), "{hrefs:?}");
+
+        let feeds = client.get("/feeds").dispatch().await;
+        assert_eq!(feeds.status(), Status::Ok);
+        assert!(feeds
+            .into_string()
+            .await
+            .unwrap()
+            .contains(&format!("href=\"{}\"", url::primary_feed())));
+    }
+
+    #[rocket::async_test]
+    async fn slugs_route_and_filenames_redirect_to_them() {
+        let fixture = Fixture::new("slugs");
+        fixture
+            .article(
+                "2024-01-01-draft-name",
+                "---\ntitle: Slugged\nstatus: published\nslug: better-name\n---\n\nHi.\n",
+            )
+            .await;
+        let client = client().await;
+
+        let response = client
+            .get(format!("/{}", fixture.rel("better-name")))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        assert!(response.into_string().await.unwrap().contains("Slugged"));
+
+        let response = client
+            .get(format!("/{}", fixture.rel("2024-01-01-draft-name")))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::PermanentRedirect);
+        assert_eq!(
+            response.headers().get_one("Location"),
+            Some(&*url::site_path(&format!(
+                "/{}",
+                fixture.rel("better-name")
+            )))
+        );
+    }
+
+    #[rocket::async_test]
+    async fn noindex_and_sitemap_exclude_act_alone() {
+        let fixture = Fixture::new("indexing");
+        for (name, flag) in [
+            ("plain", ""),
+            ("noindex", "noindex: true\n"),
+            ("unmapped", "sitemap_exclude: true\n"),
+        ] {
+            fixture
+                .article(
+                    name,
+                    &format!("---\ntitle: {name}\nstatus: published\n{flag}---\n\nHi.\n"),
+                )
+                .await;
+        }
+        let client = client().await;
+        let sitemap = client
+            .get("/sitemap.xml")
+            .dispatch()
+            .await
+            .into_string()
+            .await
+            .unwrap();
+        let robots = r#"<meta name="robots""#;
+        for (name, in_sitemap, noindex) in [
+            ("plain", true, false),
+            ("noindex", false, true),
+            ("unmapped", false, false),
+        ] {
+            let loc = format!("<loc>{}</loc>", url::absolute_url(&fixture.rel(name)));
+            assert_eq!(sitemap.contains(&loc), in_sitemap, "{name} in the sitemap");
+            let page = client
+                .get(format!("/{}", fixture.rel(name)))
+                .dispatch()
+                .await
+                .into_string()
+                .await
+                .unwrap();
+            assert_eq!(page.contains(robots), noindex, "{name} marked noindex");
+        }
+    }
+
+    #[rocket::async_test]
+    async fn tables_of_contents_are_served_nested() {
+        let fixture = Fixture::new("toc");
+        fixture
+            .article(
+                "post",
+                "---\ntitle: Structured\nstatus: published\ntoc:\n  - Heading:\n      label: Setup\n      anchor: setup\n      subheadings:\n        - Heading:\n            label: Tools\n            anchor: tools\n            subheadings: []\n  - Heading:\n      label: Results\n      anchor: results\n      subheadings: []\n---\n\n# Setup\n\n## Tools\n\n# Results\n",
+            )
+            .await;
+        let client = client().await;
+        let response = client
+            .get(format!("/toc/{}", fixture.rel("post")))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let toc: serde_json::Value = response.into_json().await.unwrap();
+        assert_eq!(
+            toc,
+            serde_json::json!([
+                {"Heading": {"label": "Setup", "anchor": "setup", "subheadings": [
+                    {"Heading": {"label": "Tools", "anchor": "tools", "subheadings": []}}
+                ]}},
+                {"Heading": {"label": "Results", "anchor": "results", "subheadings": []}}
+            ])
+        );
+
+        let missing = client
+            .get(format!("/toc/{}", fixture.rel("missing")))
+            .dispatch()
+            .await;
+        assert_eq!(missing.status(), Status::NotFound);
+    }
+
+    #[rocket::async_test]
+    async fn bad_webmentions_are_refused() {
+        let client = client().await;
+        let target = url::absolute_url("post");
+        let oversized = format!(
+            "source=https://example.com/{}&target={target}",
+            "a".repeat(CONFIG.webmention_limit as usize)
+        );
+        let cases = [
+            (oversized, Status::PayloadTooLarge),
+            (format!("target={target}"), Status::BadRequest),
+            (
+                format!("source=not a url&target={target}"),
+                Status::BadRequest,
+            ),
+            (
+                format!("source=ftp://example.com/&target={target}"),
+                Status::BadRequest,
+            ),
+            (
+                format!("source={target}&target={target}"),
+                Status::BadRequest,
+            ),
+            (
+                "source=https://example.com/&target=https://elsewhere.example/".to_string(),
+                Status::BadRequest,
+            ),
+        ];
+        for (body, expected) in cases {
+            let response = client
+                .post("/webmention")
+                .header(ContentType::Form)
+                .body(&body)
+                .dispatch()
+                .await;
+            assert_eq!(
+                response.status(),
+                expected,
+                "{}",
+                &body[..body.len().min(80)]
+            );
+        }
+    }
+
+    #[rocket::async_test]
+    async fn tag_pages_paginate_and_link_their_feed() {
+        let fixture = Fixture::new("tag-pages");
+        for n in 1..=5 {
+            fixture
+                .article(
+                    &format!("post-{n}"),
+                    &format!(
+                        "---\ntitle: Tagged post {n}\nstatus: published\ncreated: 2024-01-0{n}\ntags: [paged]\n---\n\nHi.\n"
+                    ),
+                )
+                .await;
+        }
+        let client = client().await;
+        let base = format!("/tags/{}?tags=paged&limit=2", fixture.rel(""));
+        let mut seen = vec![];
+        for (page, has_next) in [(0, true), (1, true), (2, false)] {
+            let uri = format!("{base}&page={page}");
+            let html = client
+                .get(&uri)
+                .dispatch()
+                .await
+                .into_string()
+                .await
+                .unwrap();
+            let shown: Vec<_> = (1..=5)
+                .filter(|n| html.contains(&format!("/{}/post-{n}\"", fixture.rel(""))))
+                .collect();
+            assert_eq!(shown.len(), if has_next { 2 } else { 1 }, "{uri}");
+            seen.extend(shown);
+            let next = format!("page={}", page + 1);
+            assert_eq!(html.contains(&next), has_next, "{uri}");
+            let feed = url::FeedLink::new(&fixture.rel(""), &["paged".to_string()]).href;
+            assert!(html.contains(&format!(r#"href="{feed}""#)), "{uri}");
+        }
+        seen.sort();
+        assert_eq!(seen, [1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn tag_page_limits_stay_within_the_search_cap() {
+        assert_eq!(
+            tag_page_limit(None),
+            CONFIG.tag_page_size.clamp(1, CONFIG.max_search_results)
+        );
+        assert_eq!(tag_page_limit(Some(0)), 1);
+        assert_eq!(tag_page_limit(Some(usize::MAX)), CONFIG.max_search_results);
+    }
+
+    #[rocket::async_test]
+    async fn posted_markdown_is_previewed_for_admins_only() {
+        let client = client().await;
+        let snippet = "Some *emphasis* and `code`.";
+        let response = client.post("/admin/render").body(snippet).dispatch().await;
+        assert_eq!(response.status(), Status::Unauthorized);
+
+        let auth = Header::new("Authorization", format!("Bearer {ADMIN_TOKEN}"));
+        let response = client
+            .post("/admin/render")
+            .header(auth.clone())
+            .body(snippet)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::HTML));
+        let html = response.into_string().await.unwrap();
+        assert!(html.contains("<em>emphasis</em>"), "{html}");
+        assert!(html.contains("<code>code</code>"), "{html}");
+
+        let oversized = "a".repeat(CONFIG.preview_limit as usize + 1);
+        let response = client
+            .post("/admin/render")
+            .header(auth)
+            .body(oversized)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::PayloadTooLarge);
+    }
+
+    #[rocket::async_test]
+    async fn feed_content_modes_choose_content_and_summary() {
+        let fixture = Fixture::new("feed-content");
+        fixture
+            .article(
+                "post",
+                "---\ntitle: Teased\nstatus: published\n---\n\nA short teaser.\n\n<!--more-->\n\nThe whole body.\n",
+            )
+            .await;
+        let client = client().await;
+        let entry = |content: &'static str| {
+            let uri = format!("/feed/{}?content={content}", fixture.rel(""));
+            let client = &client;
+            async move {
+                let feed = feed(client, &uri).await;
+                let [entry] = feed.entries() else {
+                    panic!("The feed should have one entry");
+                };
+                let summary = entry.summary().map(|s| s.value.clone()).unwrap_or_default();
+                let content = entry.content().and_then(|c| c.value().map(str::to_string));
+                (summary, content)
+            }
+        };
+
+        let (summary, content) = entry("full").await;
+        assert!(summary.contains("A short teaser."), "{summary}");
+        assert!(content.unwrap().contains("The whole body."));
+
+        let (summary, content) = entry("summary").await;
+        assert!(summary.contains("A short teaser."), "{summary}");
+        assert_eq!(content, None);
+
+        let (summary, content) = entry("truncated").await;
+        assert!(summary.contains("A short teaser."), "{summary}");
+        let content = content.unwrap();
+        assert!(content.contains("A short teaser."), "{content}");
+        assert!(content.contains("Read more"), "{content}");
+        assert!(!content.contains("The whole body."), "{content}");
+
+        // An article's own choice wins over the feed's.
+        fixture
+            .article(
+                "post",
+                "---\ntitle: Teased\nstatus: published\nfeed_content: summary\n---\n\nA short teaser.\n\n<!--more-->\n\nThe whole body.\n",
+            )
+            .await;
+        assert_eq!(entry("full").await.1, None);
+    }
+
+    #[rocket::async_test]
+    async fn feeds_leave_out_emails_by_default() {
+        assert!(!CONFIG.feed_include_email);
+        let fixture = Fixture::new("feed-email");
+        fixture
+            .article(
+                "post",
+                "---\ntitle: Private\nstatus: published\nauthors:\n  - name: Ada\n    email: ada@example.com\n---\n\nHi.\n",
+            )
+            .await;
+        let uri = format!("/feed/{}", fixture.rel(""));
+        let xml = client()
+            .await
+            .get(&uri)
+            .dispatch()
+            .await
+            .into_string()
+            .await
+            .unwrap();
+        assert!(xml.contains("<name>Ada</name>"), "{xml}");
+        assert!(!xml.contains("<email>"), "{xml}");
+        assert!(!xml.contains("ada@example.com"), "{xml}");
+    }
+
+    #[rocket::async_test]
+    async fn feeds_carry_at_most_the_newest_entries() {
+        let fixture = Fixture::new("feed-limit");
+        let count = CONFIG.feed_limit + 1;
+        for n in 0..count {
+            fixture
+                .article(
+                    &format!("post-{n}"),
+                    &format!(
+                        "---\ntitle: Entry {n}\nstatus: published\ncreated: 2020-01-01\npublished: {}\n---\n\nHi.\n",
+                        NaiveDate::from_ymd_opt(2020, 1, 1).unwrap() + chrono::Days::new(n as u64)
+                    ),
+                )
+                .await;
+        }
+        let client = client().await;
+        let uri = format!("/feed/{}", fixture.rel(""));
+        let first = feed(&client, &uri).await;
+        assert_eq!(first.entries().len(), CONFIG.feed_limit);
+        assert_eq!(
+            first.entries()[0].title().as_str(),
+            format!("Entry {}", count - 1)
+        );
+        assert!(first.links().iter().any(|link| link.rel() == "next"));
+
+        let second = feed(&client, &format!("{uri}?page=1")).await;
+        let titles: Vec<_> = second
+            .entries()
+            .iter()
+            .map(|e| e.title().as_str())
+            .collect();
+        assert_eq!(titles, ["Entry 0"]);
+        assert!(!second.links().iter().any(|link| link.rel() == "next"));
+    }
+
+    #[rocket::async_test]
+    async fn received_mentions_are_listed_as_json() {
+        let fixture = Fixture::new("mentions-json");
+        fixture
+            .article(
+                "post",
+                "---\ntitle: Mentioned\nstatus: published\nslug: mentioned-post\n---\n\nHi.\n",
+            )
+            .await;
+        let source = "https://elsewhere.example/reply";
+        crate::db::store_mention(
+            source,
+            &fixture.rel("post"),
+            Some("<p>Nice.</p>"),
+            1_700_000_000,
+        )
+        .await;
+        // Mentions sent to the slug count too.
+        crate::db::store_mention(
+            "https://elsewhere.example/slugged",
+            &fixture.rel("mentioned-post"),
+            None,
+            1_700_000_100,
+        )
+        .await;
+
+        let client = client().await;
+        for uri in [
+            format!("/mentions/{}.json", fixture.rel("post")),
+            format!("/mentions/{}.json", fixture.rel("mentioned-post")),
+        ] {
+            let response = client.get(&uri).dispatch().await;
+            assert_eq!(response.status(), Status::Ok, "{uri}");
+            assert_eq!(response.content_type(), Some(ContentType::JSON));
+            let mentions: serde_json::Value = response.into_json().await.unwrap();
+            let sources: Vec<_> = mentions
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|m| m["source"].as_str().unwrap())
+                .collect();
+            assert!(sources.contains(&source), "{uri}: {mentions}");
+            assert!(
+                sources.contains(&"https://elsewhere.example/slugged"),
+                "{uri}: {mentions}"
+            );
+            let reply = mentions
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|m| m["source"] == source)
+                .unwrap();
+            assert_eq!(reply["excerpt"], "<p>Nice.</p>");
+            assert_eq!(reply["received_at"], 1_700_000_000);
+        }
+
+        let response = client
+            .get(format!("/mentions/{}.json", fixture.rel("missing")))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn related_tags_rank_by_how_often_they_appear_together() {
+        let articles: Vec<_> = [
+            &["rust", "web"][..],
+            &["rust", "cli"],
+            &["rust", "web", "async"],
+            &["rust", "cli", "zebra"],
+            &["rust", "web", "apple"],
+        ]
+        .iter()
+        .enumerate()
+        .map(|(n, tags)| {
+            let meta = ArticleMeta {
+                tags: tags.iter().map(ToString::to_string).collect(),
+                ..Default::default()
+            };
+            (Path::new(&format!("{n}.md")).into(), Arc::new(meta))
+        })
+        .collect();
+        let related = related_tags(&articles, &["rust".to_string()]);
+        let related: Vec<_> = related.iter().map(|(t, n)| (t.as_str(), *n)).collect();
+        // Equally common tags are alphabetical.
+        assert_eq!(
+            related,
+            [
+                ("web", 3),
+                ("cli", 2),
+                ("apple", 1),
+                ("async", 1),
+                ("zebra", 1)
+            ]
+        );
+
+        let many: Vec<_> = (0..RELATED_TAGS + 2)
+            .map(|n| {
+                let meta = ArticleMeta {
+                    tags: vec![format!("tag-{n:02}")],
+                    ..Default::default()
+                };
+                (Path::new(&format!("{n}.md")).into(), Arc::new(meta))
+            })
+            .collect();
+        assert_eq!(related_tags(&many, &[]).len(), RELATED_TAGS);
+    }
+
+    #[rocket::async_test]
+    async fn statuses_decide_where_articles_show_up() {
+        let fixture = Fixture::new("statuses");
+        let statuses = ["draft", "published", "unlisted", "archived"];
+        for status in statuses {
+            fixture
+                .article(
+                    status,
+                    &format!("---\ntitle: The {status} post\nstatus: {status}\n---\n\nHi.\n"),
+                )
+                .await;
+        }
+        let client = client().await;
+        let shown_in = |html: &str, end: char| -> Vec<&str> {
+            statuses
+                .into_iter()
+                .filter(|status| html.contains(&format!("/{}{end}", fixture.rel(status))))
+                .collect()
+        };
+
+        for status in statuses {
+            let response = client
+                .get(format!("/{}", fixture.rel(status)))
+                .dispatch()
+                .await;
+            let expected = if status == "draft" {
+                Status::NotFound
+            } else {
+                Status::Ok
+            };
+            assert_eq!(response.status(), expected, "{status}");
+        }
+
+        let uri = format!("/search/{}", fixture.rel(""));
+        let search = client
+            .get(uri)
+            .dispatch()
+            .await
+            .into_string()
+            .await
+            .unwrap();
+        assert_eq!(shown_in(&search, '"'), ["published", "archived"]);
+
+        let feed = feed(&client, &format!("/feed/{}", fixture.rel(""))).await;
+        let titles: Vec<_> = feed.entries().iter().map(|e| e.title().as_str()).collect();
+        assert_eq!(titles, ["The published post"]);
+
+        let sitemap = client.get("/sitemap.xml").dispatch().await;
+        let sitemap = sitemap.into_string().await.unwrap();
+        assert_eq!(shown_in(&sitemap, '<'), ["published", "archived"]);
+
+        let drafts = client
+            .get("/admin/drafts")
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {ADMIN_TOKEN}"),
+            ))
+            .dispatch()
+            .await;
+        let drafts = drafts.into_string().await.unwrap();
+        let listed: Vec<_> = statuses
+            .into_iter()
+            .filter(|status| drafts.contains(&format!("The {status} post")))
+            .collect();
+        assert_eq!(listed, ["draft"]);
+    }
+
+    #[test]
+    fn legacy_feed_paths_move_to_the_primary_feed() {
+        run_with(
+            config(|c| c.primary_feed = "/feed/posts".to_string()),
+            async {
+                let client = client().await;
+                assert_eq!(url::primary_feed(), "/feed/posts");
+                for uri in ["/feed", "/feed.xml", "/atom.xml", "/index.xml", "/rss"] {
+                    let response = client.get(uri).dispatch().await;
+                    assert_eq!(response.status(), Status::MovedPermanently, "{uri}");
+                    assert_eq!(
+                        response.headers().get_one("Location"),
+                        Some("/feed/posts"),
+                        "{uri}"
+                    );
+                }
+                // Asking the whole site's feed for something is still answered.
+                let response = client.get("/feed?tags=anything").dispatch().await;
+                assert_eq!(response.status(), Status::Ok);
+            },
+        )
+    }
+
+    #[rocket::async_test]
+    async fn legacy_feed_paths_move_to_the_feed_of_everything_by_default() {
+        let client = client().await;
+        assert_eq!(url::primary_feed(), "/feed");
+        let response = client.get("/feed").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        for uri in ["/feed.xml", "/atom.xml", "/index.xml", "/rss"] {
+            let response = client.get(uri).dispatch().await;
+            assert_eq!(response.status(), Status::MovedPermanently, "{uri}");
+            assert_eq!(
+                response.headers().get_one("Location"),
+                Some("/feed"),
+                "{uri}"
+            );
+        }
+    }
+
+    #[rocket::async_test]
+    async fn articles_choose_the_feeds_they_are_in() {
+        let fixture = Fixture::new("named-feeds");
+        for (name, feeds) in [
+            ("announcement", "[announcements]"),
+            ("both", "[main, announcements]"),
+            ("post", "null"),
+        ] {
+            fixture
+                .article(
+                    name,
+                    &format!(
+                        "---\ntitle: Feeds {name}\nstatus: published\nfeeds: {feeds}\n---\n\nHi.\n"
+                    ),
+                )
+                .await;
+        }
+        let client = client().await;
+        let titles = |feed: atom_syndication::Feed| -> Vec<String> {
+            let mut titles: Vec<_> = feed
+                .entries()
+                .iter()
+                .map(|entry| entry.title().to_string())
+                .filter(|title| title.starts_with("Feeds "))
+                .collect();
+            titles.sort();
+            titles
+        };
+
+        let main = feed(&client, &format!("/feed/{}", fixture.rel(""))).await;
+        assert_eq!(titles(main), ["Feeds both", "Feeds post"]);
+        let announcements = feed(&client, "/feed/named/announcements").await;
+        assert_eq!(titles(announcements), ["Feeds announcement", "Feeds both"]);
+
+        // A directory called `named` still has feeds for what's in it.
+        let dir = CONFIG.roots[0].dir.join("named/fixture-named-dir");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path: Arc<Path> = dir.join("post.md").into();
+        std::fs::write(
+            &path,
+            "---\ntitle: Feeds in a directory\nstatus: published\n---\n\nHi.\n",
+        )
+        .unwrap();
+        article::refresh(&path).await;
+        let in_dir = feed(&client, "/feed/named/fixture-named-dir").await;
+        assert_eq!(titles(in_dir), ["Feeds in a directory"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+        article::forget(&dir);
+    }
+
+    #[rocket::async_test]
+    async fn unchanged_feeds_are_served_from_the_cache() {
+        let fixture = Fixture::new("feed-cache");
+        let post = |body: &str| format!("---\ntitle: Cached\nstatus: published\n---\n\n{body}\n");
+        fixture.article("post", &post("First")).await;
+        let client = client().await;
+        let uri = format!("/feed/{}", fixture.rel(""));
+
+        let first = client.get(&uri).dispatch().await.into_string().await;
+        let second = client.get(&uri).dispatch().await.into_string().await;
+        assert_eq!(first, second);
+        assert_eq!(feed_cache::builds(&uri), 1);
+
+        // Editing an article in it builds it again.
+        fixture.article("post", &post("Second")).await;
+        let third = client
+            .get(&uri)
+            .dispatch()
+            .await
+            .into_string()
+            .await
+            .unwrap();
+        assert!(third.contains("Second"), "{third}");
+        assert_eq!(feed_cache::builds(&uri), 2);
+    }
+
+    #[rocket::async_test]
+    async fn minimal_search_results_are_a_bare_fragment() {
+        let fixture = Fixture::new("minimal-search");
+        for n in 0..3 {
+            fixture
+                .article(
+                    &format!("post-{n}"),
+                    &format!("---\ntitle: Result {n}\nstatus: published\n---\n\nHi.\n"),
+                )
+                .await;
+        }
+        let client = client().await;
+        let uri = format!("/search/{}?limit=2&format=minimal", fixture.rel(""));
+        let response = client.get(&uri).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::HTML));
+        let html = response.into_string().await.unwrap();
+        assert!(!html.contains("<html"), "{html}");
+        assert!(!html.contains("<form"), "{html}");
+        assert!(html.contains(r#"class="cards hscroll""#), "{html}");
+        let shown = (0..3)
+            .filter(|n| html.contains(&format!("/{}/post-{n}\"", fixture.rel(""))))
+            .count();
+        assert_eq!(shown, 2);
+        assert!(html.contains(r#"rel="next""#), "{html}");
+        assert!(html.contains("format=minimal"), "{html}");
+
+        let full = client
+            .get(format!("/search/{}", fixture.rel("")))
+            .dispatch()
+            .await
+            .into_string()
+            .await
+            .unwrap();
+        assert!(full.contains("<html"));
+    }
+
+    #[test]
+    fn articles_updated_after_since_are_flagged() {
+        let article = |name: &str, updated: &str| {
+            let meta = ArticleMeta {
+                updated: NaiveDate::parse_from_str(updated, "%Y-%m-%d").unwrap(),
+                ..Default::default()
+            };
+            (Path::new(name).into(), Arc::new(meta))
+        };
+        let articles = vec![
+            article("older.md", "2024-01-01"),
+            article("same-day.md", "2024-02-01"),
+            article("newer.md", "2024-02-02"),
+        ];
+        let since = DateField(NaiveDate::from_ymd_opt(2024, 2, 1).unwrap());
+        let flags: Vec<_> = mark_new_since(articles.clone(), Some(since))
+            .into_iter()
+            .map(|(path, _, new)| (path.to_str().unwrap().to_string(), new))
+            .collect();
+        assert_eq!(
+            flags,
+            [
+                ("older.md".to_string(), false),
+                ("same-day.md".to_string(), false),
+                ("newer.md".to_string(), true)
+            ]
+        );
+        // Without a last visit, nothing is new.
+        assert!(mark_new_since(articles, None)
+            .iter()
+            .all(|(_, _, new)| !new));
+    }
+
+    #[test]
+    fn miscased_paths_redirect_to_the_files_spelling() {
+        run_with(config(|c| c.case_insensitive_paths = true), async {
+            assert!(CONFIG.case_insensitive_paths);
+            let fixture = Fixture::new("Case");
+            fixture
+                .article(
+                    "Dir/My-Post",
+                    "---\ntitle: Cased\nstatus: published\n---\n\nHi.\n",
+                )
+                .await;
+            fixture
+                .article(
+                    "exact",
+                    "---\ntitle: Lower\nstatus: published\n---\n\nlower\n",
+                )
+                .await;
+            fixture
+                .article(
+                    "EXACT",
+                    "---\ntitle: Upper\nstatus: published\n---\n\nUPPER\n",
+                )
+                .await;
+            let client = client().await;
+            let canonical = format!("/{}", fixture.rel("Dir/My-Post"));
+            for miscased in [
+                canonical.to_lowercase(),
+                canonical.to_uppercase(),
+                format!("/{}", fixture.rel("dir/My-Post")),
+            ] {
+                let response = client.get(&miscased).dispatch().await;
+                assert_eq!(response.status(), Status::PermanentRedirect, "{miscased}");
+                assert_eq!(
+                    response.headers().get_one("Location"),
+                    Some(url::site_path(&canonical).as_str()),
+                    "{miscased}"
+                );
+            }
+            let response = client.get(&canonical).dispatch().await;
+            assert_eq!(response.status(), Status::Ok);
+
+            // Names differing only in case are each found exactly.
+            for (name, text) in [("exact", "lower"), ("EXACT", "UPPER")] {
+                let html = client
+                    .get(format!("/{}", fixture.rel(name)))
+                    .dispatch()
+                    .await
+                    .into_string()
+                    .await
+                    .unwrap();
+                assert!(html.contains(text), "{name}");
+            }
+        })
+    }
+
+    #[rocket::async_test]
+    async fn paths_keep_their_case_by_default() {
+        let fixture = Fixture::new("case-default");
+        fixture
+            .article(
+                "My-Post",
+                "---\ntitle: Cased\nstatus: published\n---\n\nHi.\n",
+            )
+            .await;
+        let client = client().await;
+        let canonical = format!("/{}", fixture.rel("My-Post"));
+        let response = client.get(&canonical).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        // A case-insensitive file system may still find the file, but it's never redirected.
+        let response = client.get(canonical.to_lowercase()).dispatch().await;
+        assert_ne!(response.status(), Status::PermanentRedirect);
+        assert_eq!(response.headers().get_one("Location"), None);
+    }
+}
@@ -0,0 +1,141 @@
+use std::{
+    path::Path,
+    sync::{Arc, LazyLock, RwLock},
+    time::SystemTime,
+};
+
+use rocket::figment::{
+    providers::{Format, Toml},
+    Figment,
+};
+use serde::Deserialize;
+
+use crate::config::CONFIG;
+
+/// Which incoming webmentions are worth processing, read from the file at
+/// [`Config::webmention_policy`](crate::config::Config::webmention_policy).
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct MentionPolicy {
+    /// If not empty, only mentions from these domains (or their subdomains) are accepted.
+    pub allow: Vec<String>,
+    /// Mentions from these domains (or their subdomains) are dropped.
+    pub block: Vec<String>,
+    /// Mentions of site paths matching these patterns are dropped. `*` matches anything.
+    pub block_targets: Vec<String>,
+}
+
+/// The policy as last read, with the modification time of the file it was read from.
+static POLICY: LazyLock<RwLock<(Option<SystemTime>, Arc<MentionPolicy>)>> =
+    LazyLock::new(Default::default);
+
+/// The current policy, re-reading the file if it's changed.
+fn policy() -> Arc<MentionPolicy> {
+    let path = Path::new(&CONFIG.webmention_policy);
+    let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    {
+        let cached = POLICY.read().unwrap();
+        if cached.0 == modified {
+            return cached.1.clone();
+        }
+    }
+    let policy = match modified {
+        Some(_) => Figment::from(Toml::file(path))
+            .extract()
+            .unwrap_or_else(|e| {
+                eprintln!("Bad webmention policy in {path:?}, accepting everything: {e}");
+                MentionPolicy::default()
+            }),
+        None => MentionPolicy::default(),
+    };
+    let policy = Arc::new(policy);
+    *POLICY.write().unwrap() = (modified, policy.clone());
+    policy
+}
+
+/// Whether `host` is `domain` or one of its subdomains.
+fn in_domain(host: &str, domain: &str) -> bool {
+    let domain = domain.trim_start_matches("*.").trim_end_matches('.');
+    host.eq_ignore_ascii_case(domain)
+        || host
+            .to_ascii_lowercase()
+            .ends_with(&format!(".{}", domain.to_ascii_lowercase()))
+}
+
+/// Whether `text` matches `pattern`, where `*` matches any run of characters.
+fn glob(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let Some(first) = parts.next() else {
+        return text.is_empty();
+    };
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts = parts.peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            return rest.len() >= part.len() && rest.ends_with(part);
+        }
+        let Some(found) = rest.find(part) else {
+            return false;
+        };
+        rest = &rest[found + part.len()..];
+    }
+    rest.is_empty()
+}
+
+/// Why a mention from `source_host` of the site-relative `target` should be dropped, if it
+/// should be.
+pub fn rejection(source_host: &str, target: &str) -> Option<&'static str> {
+    policy().rejection(source_host, target)
+}
+
+impl MentionPolicy {
+    fn rejection(&self, source_host: &str, target: &str) -> Option<&'static str> {
+        let target = format!("/{}", target.trim_start_matches('/'));
+        if self.block.iter().any(|d| in_domain(source_host, d)) {
+            Some("source domain is blocked")
+        } else if !self.allow.is_empty() && !self.allow.iter().any(|d| in_domain(source_host, d)) {
+            Some("source domain isn't allowed")
+        } else if self.block_targets.iter().any(|p| glob(p, &target)) {
+            Some("target is blocked")
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(toml: &str) -> MentionPolicy {
+        Figment::from(Toml::string(toml)).extract().unwrap()
+    }
+
+    #[test]
+    fn blocked_domains_and_their_subdomains_are_dropped() {
+        let policy = policy(r#"block = ["spam.example"]"#);
+        assert!(policy.rejection("spam.example", "post").is_some());
+        assert!(policy.rejection("www.SPAM.example", "post").is_some());
+        assert_eq!(policy.rejection("notspam.example", "post"), None);
+    }
+
+    #[test]
+    fn only_allowed_domains_get_through_an_allow_list() {
+        let policy = policy(r#"allow = ["friend.example"]"#);
+        assert_eq!(policy.rejection("friend.example", "post"), None);
+        assert_eq!(policy.rejection("blog.friend.example", "post"), None);
+        assert!(policy.rejection("stranger.example", "post").is_some());
+    }
+
+    #[test]
+    fn blocked_targets_match_their_patterns() {
+        let policy = policy(r#"block_targets = ["/drafts/*", "*/private"]"#);
+        assert!(policy.rejection("friend.example", "drafts/idea").is_some());
+        assert!(policy
+            .rejection("friend.example", "/notes/private")
+            .is_some());
+        assert_eq!(policy.rejection("friend.example", "notes/public"), None);
+    }
+}
@@ -0,0 +1,192 @@
+//! Taking the whitespace and other slack out of the HTML wolog serves.
+
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    io::Cursor,
+    sync::LazyLock,
+};
+
+use dashmap::DashMap;
+use minify_html::Cfg;
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    tokio::task::spawn_blocking,
+    Request, Response,
+};
+use serde::Deserialize;
+
+use crate::config::CONFIG;
+
+/// Which HTML is minified.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MinifyHtml {
+    /// None of it.
+    #[default]
+    Off,
+    /// Only articles' rendered content, as it comes out of the renderer.
+    Content,
+    /// Whole pages, after their templates are rendered.
+    Pages,
+}
+
+/// What minification keeps. Closing tags and quoting are left valid for stricter parsers like
+/// feed readers'.
+static CFG: LazyLock<Cfg> = LazyLock::new(|| Cfg {
+    keep_closing_tags: true,
+    keep_html_and_head_opening_tags: true,
+    minify_css: true,
+    ..Cfg::spec_compliant()
+});
+
+/// Elements kept exactly as they're written. minify-html keeps the whitespace in them, but still
+/// rewrites their character references, so they're taken out while the rest is minified.
+const KEPT: &[&str] = &["pre", "textarea"];
+
+/// Marks where a kept element was, around its index. It's in a private use area, so it won't
+/// turn up in pages.
+const MARK: char = '\u{E000}';
+
+/// Minify HTML, leaving `pre` and `textarea` elements byte for byte as they were.
+pub fn minify(html: &[u8]) -> Vec<u8> {
+    let Ok(html) = std::str::from_utf8(html) else {
+        return minify_html::minify(html, &CFG);
+    };
+    let mut kept = vec![];
+    let mut rest = String::with_capacity(html.len());
+    let mut from = 0;
+    while let Some((start, end)) = next_kept(html, from) {
+        rest.push_str(&html[from..start]);
+        rest.extend([MARK.to_string(), kept.len().to_string(), MARK.to_string()]);
+        kept.push(&html[start..end]);
+        from = end;
+    }
+    rest.push_str(&html[from..]);
+    let minified = minify_html::minify(rest.as_bytes(), &CFG);
+    if kept.is_empty() {
+        return minified;
+    }
+    let minified = String::from_utf8_lossy(&minified);
+    let mut restored = String::with_capacity(html.len());
+    for (i, part) in minified.split(MARK).enumerate() {
+        if i % 2 == 0 {
+            restored.push_str(part);
+        } else {
+            restored.push_str(
+                part.parse()
+                    .ok()
+                    .and_then(|n: usize| kept.get(n))
+                    .unwrap_or(&part),
+            );
+        }
+    }
+    restored.into_bytes()
+}
+
+/// Where the first element in [`KEPT`] at or after `from` starts and ends, if any.
+fn next_kept(html: &str, from: usize) -> Option<(usize, usize)> {
+    KEPT.iter()
+        .filter_map(|tag| {
+            let mut search = from;
+            let start = loop {
+                let start = search + html[search..].find(&format!("<{tag}"))?;
+                let after = html[start + tag.len() + 1..].chars().next()?;
+                if after == '>' || after.is_ascii_whitespace() {
+                    break start;
+                }
+                search = start + 1;
+            };
+            let close = format!("</{tag}>");
+            let end = start + html[start..].find(&close)? + close.len();
+            Some((start, end))
+        })
+        .min()
+}
+
+/// Minify an article's rendered content, if `minify_html` is `content`.
+pub fn content(html: String) -> String {
+    if CONFIG.minify_html != MinifyHtml::Content {
+        return html;
+    }
+    String::from_utf8(minify(html.as_bytes())).unwrap_or(html)
+}
+
+const MAX_CACHED: usize = 256;
+
+/// Minified pages keyed on a hash of the page, so unchanged pages are only minified once.
+static MINIFIED: LazyLock<DashMap<u64, Vec<u8>>> = LazyLock::new(DashMap::new);
+
+/// Minifies HTML pages when `minify_html` is `pages`.
+///
+/// This must be attached before [`crate::etag::ETag`] and [`crate::compress::Compression`], so
+/// they see the minified body.
+pub struct Minify;
+
+#[rocket::async_trait]
+impl Fairing for Minify {
+    fn info(&self) -> Info {
+        Info {
+            name: "HTML minification",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let path = request.uri().path();
+        if CONFIG.minify_html != MinifyHtml::Pages
+            || path.starts_with("/static")
+            || path.starts_with("/assets")
+            || response.headers().contains("Content-Encoding")
+            || !response.content_type().is_some_and(|ct| ct.is_html())
+        {
+            return;
+        }
+        let Ok(body) = response.body_mut().to_bytes().await else {
+            return;
+        };
+
+        let mut hasher = DefaultHasher::new();
+        body.hash(&mut hasher);
+        let key = hasher.finish();
+        let minified = match MINIFIED.get(&key).map(|m| m.clone()) {
+            Some(minified) => minified,
+            None => {
+                let minified = spawn_blocking({
+                    let body = body.clone();
+                    move || minify(&body)
+                })
+                .await
+                .unwrap_or(body);
+                if MINIFIED.len() >= MAX_CACHED {
+                    MINIFIED.clear();
+                }
+                MINIFIED.insert(key, minified.clone());
+                minified
+            }
+        };
+        response.set_sized_body(minified.len(), Cursor::new(minified));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whitespace_goes_except_in_preformatted_text() {
+        let pre = "<pre><code>fn main() {\n\t  println!(\"&lt;hi&gt;\");  \n\n}\n</code></pre>";
+        let page = format!(
+            "<!DOCTYPE html>\n<html>\n  <head>\n    <title>  Page  </title>\n  </head>\n  <body>\n    <p>\n      Some   text\n    </p>\n    {pre}\n    <textarea>  kept\n  as is</textarea>\n  </body>\n</html>\n"
+        );
+        let minified = String::from_utf8(minify(page.as_bytes())).unwrap();
+        assert!(minified.len() < page.len(), "{minified}");
+        assert!(!minified.contains("\n    <p>"), "{minified}");
+        assert!(minified.contains(pre), "{minified}");
+        assert!(
+            minified.contains("<textarea>  kept\n  as is</textarea>"),
+            "{minified}"
+        );
+        // Tags stay closed for feed readers.
+        assert!(minified.contains("</p>"), "{minified}");
+    }
+}
@@ -0,0 +1,280 @@
+//! Social preview images for articles that don't set their own `image`.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use ab_glyph::{point, Font, FontVec, PxScaleFont, ScaleFont};
+use image::{ImageFormat, Rgb, RgbImage};
+use rocket::tokio::task::spawn_blocking;
+use sha2::{Digest, Sha256};
+
+use crate::{config::CONFIG, url};
+
+/// Where generated images are written, inside the assets directory so they're served with it.
+const IMAGE_DIR: &str = "_og";
+/// The size Open Graph consumers expect, in pixels.
+const WIDTH: u32 = 1200;
+const HEIGHT: u32 = 630;
+const MARGIN: f32 = 80.0;
+/// The most lines of title drawn, shrinking the text to fit before cutting it short.
+const MAX_LINES: usize = 4;
+
+/// The configured `og_image_font`, if it's set and loads.
+static FONT: LazyLock<Option<FontVec>> = LazyLock::new(|| {
+    let path = CONFIG.og_image_font.as_ref()?;
+    std::fs::read(path)
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| FontVec::try_from_vec(bytes).map_err(|e| e.to_string()))
+        .inspect_err(|e| eprintln!("Couldn't load the preview image font {path:?}: {e}"))
+        .ok()
+});
+
+/// The absolute URL of a preview image for the article at `path`, drawing it if it doesn't
+/// exist yet.
+///
+/// Images are named after a hash of everything drawn on them, so changing the title draws a new
+/// one under a new URL, which social sites that cache previews will fetch again. Old images are
+/// left for anyone still linking to them.
+///
+/// Returns `None` if no `og_image_font` is configured, or drawing fails.
+pub async fn generated(path: &Path, title: &str) -> Option<String> {
+    let font = FONT.as_ref()?;
+    generated_with(font, CONFIG.og_image_font.as_ref()?, path, title).await
+}
+
+/// Like [`generated`], drawing with `font`, which was loaded from `font_path`.
+async fn generated_with(
+    font: &'static FontVec,
+    font_path: &Path,
+    path: &Path,
+    title: &str,
+) -> Option<String> {
+    let mut digest = Sha256::new();
+    for part in [
+        title,
+        &CONFIG.author,
+        &CONFIG.og_image_background,
+        &CONFIG.og_image_foreground,
+        &font_path.to_string_lossy(),
+    ] {
+        digest.update(part.as_bytes());
+        digest.update([0]);
+    }
+    let hash: String = digest.finalize()[..6]
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    let rel = Path::new(IMAGE_DIR).join(format!("{}-{hash}.png", url::article_rel(path)));
+    let file = Path::new("articles/assets").join(&rel);
+    if !file.is_file() {
+        let title = title.to_string();
+        let target = file.clone();
+        let drawn = spawn_blocking(move || {
+            println!("Drawing a preview image for {title:?}");
+            write(&draw(font, &title)?, &target)
+        })
+        .await;
+        match drawn {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                eprintln!("Couldn't draw the preview image {file:?}: {e}");
+                return None;
+            }
+            Err(e) => {
+                eprintln!("Drawing the preview image {file:?} panicked: {e}");
+                return None;
+            }
+        }
+    }
+    Some(url::absolute_url(&format!(
+        "assets/{}",
+        rel.to_string_lossy()
+    )))
+}
+
+/// Draw an article's title, and the site's author beneath it.
+fn draw(font: &FontVec, title: &str) -> Result<RgbImage, String> {
+    let background = color(&CONFIG.og_image_background)?;
+    let foreground = color(&CONFIG.og_image_foreground)?;
+    let mut image = RgbImage::from_pixel(WIDTH, HEIGHT, background);
+    let max_width = WIDTH as f32 - 2.0 * MARGIN;
+
+    let mut size = 88.0;
+    let (scaled, mut lines) = loop {
+        let scaled = font.as_scaled(size);
+        let lines = wrap(&scaled, title, max_width);
+        if lines.len() <= MAX_LINES || size <= 48.0 {
+            break (scaled, lines);
+        }
+        size -= 8.0;
+    };
+    if lines.len() > MAX_LINES {
+        lines.truncate(MAX_LINES);
+        let last = &mut lines[MAX_LINES - 1];
+        while width(&scaled, &format!("{last}…")) > max_width {
+            match last.rfind(' ') {
+                Some(space) => last.truncate(space),
+                None => break,
+            }
+        }
+        last.push('…');
+    }
+    let mut baseline = MARGIN + scaled.ascent();
+    for line in &lines {
+        draw_text(&mut image, &scaled, line, baseline, foreground);
+        baseline += scaled.height() + scaled.line_gap();
+    }
+
+    let byline = font.as_scaled(40.0);
+    let baseline = HEIGHT as f32 - MARGIN + byline.descent();
+    draw_text(&mut image, &byline, &CONFIG.author, baseline, foreground);
+    Ok(image)
+}
+
+/// Parse a colour like `#1d2021`.
+fn color(hex: &str) -> Result<Rgb<u8>, String> {
+    let bad = || format!("{hex:?} isn't a colour like #1d2021");
+    let digits = hex
+        .strip_prefix('#')
+        .filter(|d| d.len() == 6)
+        .ok_or_else(bad)?;
+    let value = u32::from_str_radix(digits, 16).map_err(|_| bad())?;
+    let [_, r, g, b] = value.to_be_bytes();
+    Ok(Rgb([r, g, b]))
+}
+
+/// How wide `text` is set in `font`, in pixels.
+fn width(font: &PxScaleFont<&FontVec>, text: &str) -> f32 {
+    let mut width = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let glyph = font.glyph_id(c);
+        if let Some(previous) = previous {
+            width += font.kern(previous, glyph);
+        }
+        width += font.h_advance(glyph);
+        previous = Some(glyph);
+    }
+    width
+}
+
+/// Break `text` into lines no wider than `max_width`, except for words that are wider on their
+/// own.
+fn wrap(font: &PxScaleFont<&FontVec>, text: &str, max_width: f32) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if line.is_empty() {
+            line = word.to_string();
+            continue;
+        }
+        let longer = format!("{line} {word}");
+        if width(font, &longer) > max_width {
+            lines.push(std::mem::replace(&mut line, word.to_string()));
+        } else {
+            line = longer;
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Draw a line of text from the left margin, blending it over what's already there.
+fn draw_text(
+    image: &mut RgbImage,
+    font: &PxScaleFont<&FontVec>,
+    text: &str,
+    baseline: f32,
+    color: Rgb<u8>,
+) {
+    let mut caret = MARGIN;
+    let mut previous = None;
+    for c in text.chars() {
+        let id = font.glyph_id(c);
+        if let Some(previous) = previous {
+            caret += font.kern(previous, id);
+        }
+        let glyph = id.with_scale_and_position(font.scale(), point(caret, baseline));
+        caret += font.h_advance(id);
+        previous = Some(id);
+        let Some(outline) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outline.px_bounds();
+        outline.draw(|x, y, coverage| {
+            let x = bounds.min.x as i64 + x as i64;
+            let y = bounds.min.y as i64 + y as i64;
+            if !(0..WIDTH as i64).contains(&x) || !(0..HEIGHT as i64).contains(&y) {
+                return;
+            }
+            let pixel = image.get_pixel_mut(x as u32, y as u32);
+            for (channel, &ink) in pixel.0.iter_mut().zip(&color.0) {
+                *channel =
+                    (*channel as f32 * (1.0 - coverage) + ink as f32 * coverage).round() as u8;
+            }
+        });
+    }
+}
+
+/// Save a drawn image, through a temporary file so a request never sees half of it.
+fn write(image: &RgbImage, file: &Path) -> Result<(), String> {
+    let mut partial = file.to_path_buf().into_os_string();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+    std::fs::create_dir_all(file.parent().unwrap_or(Path::new("."))).map_err(|e| e.to_string())?;
+    image
+        .save_with_format(&partial, ImageFormat::Png)
+        .map_err(|e| e.to_string())?;
+    std::fs::rename(&partial, file).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{article, testing::Fixture};
+
+    const TEST_FONT: &str = "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf";
+
+    /// A font to draw with, if the system has one where we look.
+    static TEST_FONT_VEC: LazyLock<Option<FontVec>> =
+        LazyLock::new(|| FontVec::try_from_vec(std::fs::read(TEST_FONT).ok()?).ok());
+
+    #[rocket::async_test]
+    async fn articles_without_images_get_a_generated_preview() {
+        // Fonts aren't always installed where tests run.
+        let Some(font) = TEST_FONT_VEC.as_ref() else {
+            return;
+        };
+        let fixture = Fixture::new("og-image");
+        let path = fixture
+            .article(
+                "post",
+                "---\ntitle: Drawn for sharing\nstatus: published\n---\n\nHi.\n",
+            )
+            .await;
+        let url = generated_with(font, Path::new(TEST_FONT), &path, "Drawn for sharing")
+            .await
+            .unwrap();
+        let rel = url
+            .strip_prefix(&url::absolute_url("assets"))
+            .and_then(|rel| rel.strip_prefix('/'))
+            .expect("Previews are served with the assets");
+        let file = Path::new("articles/assets").join(rel);
+        let image = image::open(&file).unwrap();
+        std::fs::remove_file(&file).unwrap();
+        std::fs::remove_dir(file.parent().unwrap()).unwrap();
+        let _ = std::fs::remove_dir(Path::new("articles/assets").join(IMAGE_DIR));
+        assert_eq!((image.width(), image.height()), (WIDTH, HEIGHT));
+        assert!(rel.starts_with(&format!("{IMAGE_DIR}/{}/post-", fixture.rel(""))));
+
+        let mut article = article::Article::clone(&article::get_article(&path).await.unwrap());
+        assert_eq!(article.og_image, None);
+        article.og_image = Some(url.clone());
+        let html = article.render(rocket_dyn_templates::context! {}).unwrap().0;
+        assert!(html.contains(&format!(r#"content="{url}""#)), "{html}");
+    }
+}
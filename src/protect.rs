@@ -0,0 +1,263 @@
+use std::{
+    convert::Infallible,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        LazyLock,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use argon2::{password_hash::PasswordHash, Argon2, PasswordVerifier};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use rocket::{
+    http::{Cookie, CookieJar, SameSite},
+    request::{FromRequest, Outcome},
+    time::Duration,
+    Request,
+};
+use sha2::{Digest, Sha256};
+
+use crate::{admin::constant_time_eq, config::CONFIG, url};
+
+/// Whether `password` matches an article's `password_hash`, an argon2 hash in PHC format.
+pub fn verify(hash: &str, password: &str) -> bool {
+    let Ok(hash) = PasswordHash::new(hash) else {
+        eprintln!("Ignoring a password attempt against a malformed password_hash");
+        return false;
+    };
+    Argon2::default()
+        .verify_password(password.as_bytes(), &hash)
+        .is_ok()
+}
+
+/// The name of the cookie unlocking the article at `path`.
+fn cookie_name(path: &Path) -> String {
+    let digest = Sha256::digest(url::article_rel(path).as_bytes());
+    format!("unlock-{}", hex(&digest[..8]))
+}
+
+/// The key for unlock tokens: the configured `unlock_secret`, or else random bytes that last as
+/// long as the process.
+static SECRET: LazyLock<Vec<u8>> = LazyLock::new(|| match &CONFIG.unlock_secret {
+    Some(secret) => secret.as_bytes().to_vec(),
+    None => {
+        let mut secret = vec![0; 32];
+        rand::thread_rng().fill_bytes(&mut secret);
+        secret
+    }
+});
+
+/// Proof that the bearer knew the article's password until `expires`.
+///
+/// It's an HMAC keyed with the server's secret, so it can't be forged from anything an article
+/// publishes, and it covers the password hash, so changing the password revokes it.
+fn token(path: &Path, hash: &str, expires: u64) -> String {
+    token_with(&SECRET, path, hash, expires)
+}
+
+fn token_with(secret: &[u8], path: &Path, hash: &str, expires: u64) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC takes keys of any length");
+    for part in [hash, &url::article_rel(path), &expires.to_string()] {
+        mac.update(part.as_bytes());
+        mac.update(&[0]);
+    }
+    hex(&mac.finalize().into_bytes())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// A request guard for reading password-protected articles, with the unlock cookies the visitor
+/// holds.
+///
+/// Asking about a protected article also marks the response private, so the
+/// [`CacheControl`](crate::cache_control::CacheControl) fairing keeps it out of shared caches.
+pub struct Unlocks<'r> {
+    cookies: &'r CookieJar<'r>,
+    private: &'r Private,
+}
+
+#[async_trait]
+impl<'r> FromRequest<'r> for Unlocks<'r> {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(Unlocks {
+            cookies: request.cookies(),
+            private: request.local_cache(Private::default),
+        })
+    }
+}
+
+impl Unlocks<'_> {
+    /// Whether the visitor may read the article at `path`, which is protected if it has a
+    /// `hash`.
+    pub fn allows(&self, path: &Path, hash: Option<&str>) -> bool {
+        let Some(hash) = hash else {
+            return true;
+        };
+        self.private.0.store(true, Ordering::Relaxed);
+        let Some(cookie) = self.cookies.get(&cookie_name(path)) else {
+            return false;
+        };
+        let Some((expires, given)) = cookie.value().split_once('.') else {
+            return false;
+        };
+        let Ok(expires) = expires.parse::<u64>() else {
+            return false;
+        };
+        expires > now() && constant_time_eq(given.as_bytes(), token(path, hash, expires).as_bytes())
+    }
+
+    /// Remember that the visitor has unlocked the article at `path`, for
+    /// [`unlock_duration`](crate::config::Config::unlock_duration).
+    pub fn unlock(&self, path: &Path, hash: &str) {
+        let expires = now() + CONFIG.unlock_duration;
+        let cookie = Cookie::build((
+            cookie_name(path),
+            format!("{expires}.{}", token(path, hash, expires)),
+        ))
+        .path(url::site_path("/"))
+        .http_only(true)
+        .secure(CONFIG.url.starts_with("https:"))
+        .same_site(SameSite::Lax)
+        .max_age(Duration::seconds(CONFIG.unlock_duration as i64));
+        self.cookies.add(cookie);
+    }
+}
+
+/// Whether a request's response concerns a protected article.
+#[derive(Default)]
+pub struct Private(AtomicBool);
+
+/// Whether the response to `request` is only for the visitor who made it.
+pub fn is_private(request: &Request<'_>) -> bool {
+    request
+        .local_cache(Private::default)
+        .0
+        .load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use argon2::{
+        password_hash::{PasswordHasher, SaltString},
+        Algorithm, Argon2, Params, Version,
+    };
+    use rocket::http::{ContentType, Cookie, Status};
+
+    use super::*;
+    use crate::{
+        article::{self, Search},
+        testing::{client, Fixture},
+    };
+
+    /// An article protected by `password`, hashed cheaply so tests stay quick.
+    fn protected(password: &str) -> String {
+        let salt = SaltString::from_b64("c2FsdHlzYWx0eXNhbHR5").unwrap();
+        let params = Params::new(Params::MIN_M_COST, 1, 1, None).unwrap();
+        let hash = Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password(password.as_bytes(), &salt)
+            .unwrap();
+        format!("---\ntitle: Behind a password\nstatus: published\npassword_hash: \"{hash}\"\n---\n\nThe secret words.\n")
+    }
+
+    #[test]
+    fn tokens_depend_on_the_secret_and_the_hash() {
+        let path = Path::new("articles/post.md");
+        let token = token_with(b"one secret", path, "hash", 10);
+        assert_eq!(token, token_with(b"one secret", path, "hash", 10));
+        assert_ne!(token, token_with(b"another secret", path, "hash", 10));
+        assert_ne!(token, token_with(b"one secret", path, "new hash", 10));
+        assert_ne!(token, token_with(b"one secret", path, "hash", 11));
+    }
+
+    #[rocket::async_test]
+    async fn protected_articles_are_locked_until_unlocked() {
+        let fixture = Fixture::new("protected");
+        let path = fixture.article("post", &protected("hunter2")).await;
+        let uri = format!("/{}/post", fixture.rel(""));
+        let client = client().await;
+
+        let response = client.get(&uri).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let html = response.into_string().await.unwrap();
+        assert!(
+            html.contains("This article is password protected"),
+            "{html}"
+        );
+        assert!(!html.contains("The secret words"));
+
+        // Cookies that weren't made with the server's secret don't unlock anything.
+        let hash = article::password_hash(&path).await.unwrap().unwrap();
+        let expires = now() + 60;
+        let forged = format!(
+            "{expires}.{}",
+            token_with(b"guessed secret", &path, &hash, expires)
+        );
+        let html = client
+            .get(&uri)
+            .cookie(Cookie::new(cookie_name(&path), forged))
+            .dispatch()
+            .await
+            .into_string()
+            .await
+            .unwrap();
+        assert!(!html.contains("The secret words"));
+
+        let response = client
+            .post(&uri)
+            .header(ContentType::Form)
+            .body("password=wrong")
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Forbidden);
+
+        let response = client
+            .post(&uri)
+            .header(ContentType::Form)
+            .body("password=hunter2")
+            .dispatch()
+            .await;
+        assert!(response.status().class().is_redirection());
+        assert!(response.cookies().get(&cookie_name(&path)).is_some());
+        let html = client
+            .get(&uri)
+            .dispatch()
+            .await
+            .into_string()
+            .await
+            .unwrap();
+        assert!(html.contains("The secret words"), "{html}");
+    }
+
+    #[rocket::async_test]
+    async fn protected_articles_are_left_out_of_listings() {
+        let fixture = Fixture::new("protected-listing");
+        fixture.article("locked", &protected("hunter2")).await;
+        fixture
+            .article(
+                "open",
+                "---\ntitle: Open to all\nstatus: published\n---\n\nHi.\n",
+            )
+            .await;
+        let found = article::search_all(&Search {
+            search_path: fixture.rel("").into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let titles: Vec<_> = found.iter().map(|(_, meta)| meta.title.as_str()).collect();
+        assert_eq!(titles, ["Open to all"]);
+    }
+}
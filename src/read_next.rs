@@ -0,0 +1,177 @@
+//! "Read next" suggestions at the end of articles.
+
+use std::{
+    cmp::Reverse, collections::hash_map::RandomState, hash::BuildHasher, path::Path, sync::Arc,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    article::{self, error::ArticleError, ArticleMeta, Search},
+    config::CONFIG,
+    roots,
+};
+
+/// Which articles are suggested after each one.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ReadNextPolicy {
+    /// How suggestions are picked, in the order they're shown. Each way fills as many of the
+    /// remaining places as it can, except `chronological`, which only ever suggests one.
+    pub by: Vec<ReadNextBy>,
+    /// The most articles suggested, or 0 for none.
+    pub limit: usize,
+}
+
+impl Default for ReadNextPolicy {
+    fn default() -> Self {
+        Self {
+            by: vec![ReadNextBy::Chronological, ReadNextBy::Related],
+            limit: 3,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadNextBy {
+    /// The next article published after this one in the same directory.
+    Chronological,
+    /// Articles sharing the most tags, newest first among equals.
+    Related,
+    /// Any articles, picked afresh for each request.
+    Random,
+}
+
+pub type Suggestion = (Arc<Path>, Arc<ArticleMeta>);
+
+/// The articles to suggest reading after the one at `path`, which has `meta`.
+pub async fn suggestions(path: &Path, meta: &ArticleMeta) -> Result<Vec<Suggestion>, ArticleError> {
+    let policy = &CONFIG.read_next;
+    if policy.limit == 0 || policy.by.is_empty() {
+        return Ok(vec![]);
+    }
+    let rel = roots::site_rel(path);
+    let mut candidates = article::search_all(&Search::default()).await?;
+    candidates.retain(|(other, _)| **other != *rel);
+    Ok(pick(&rel, meta, candidates, policy))
+}
+
+/// Choose suggestions for the article at the site-relative path `rel` from `candidates`.
+fn pick(
+    rel: &Path,
+    meta: &ArticleMeta,
+    mut candidates: Vec<Suggestion>,
+    policy: &ReadNextPolicy,
+) -> Vec<Suggestion> {
+    let mut picked = vec![];
+    for by in &policy.by {
+        let room = policy.limit.saturating_sub(picked.len());
+        if room == 0 {
+            break;
+        }
+        let chosen: Vec<Suggestion> = match by {
+            ReadNextBy::Chronological => chronological_next(rel, meta, &candidates)
+                .into_iter()
+                .collect(),
+            ReadNextBy::Related => related(meta, &candidates, room),
+            ReadNextBy::Random => random(&candidates, room),
+        };
+        candidates.retain(|(path, _)| !chosen.iter().any(|(chosen, _)| chosen == path));
+        picked.extend(chosen);
+    }
+    picked
+}
+
+/// The first article in the same directory published after this one, taking articles published
+/// the same day in order of their paths.
+fn chronological_next(
+    rel: &Path,
+    meta: &ArticleMeta,
+    candidates: &[Suggestion],
+) -> Option<Suggestion> {
+    let here = (meta.published_date(), rel);
+    candidates
+        .iter()
+        .filter(|(path, _)| path.parent() == rel.parent())
+        .filter(|(path, meta)| (meta.published_date(), &**path) > here)
+        .min_by_key(|(path, meta)| (meta.published_date(), path.clone()))
+        .cloned()
+}
+
+/// The `n` articles sharing the most of this one's tags, skipping those sharing none.
+fn related(meta: &ArticleMeta, candidates: &[Suggestion], n: usize) -> Vec<Suggestion> {
+    let shared = |other: &ArticleMeta| other.tags.iter().filter(|t| meta.tags.contains(t)).count();
+    let mut related: Vec<_> = candidates
+        .iter()
+        .filter(|(_, other)| shared(other) > 0)
+        .cloned()
+        .collect();
+    related.sort_by_cached_key(|(_, other)| Reverse((shared(other), other.published_date())));
+    related.truncate(n);
+    related
+}
+
+/// `n` articles in no particular order, different each time.
+fn random(candidates: &[Suggestion], n: usize) -> Vec<Suggestion> {
+    // The standard library's hasher is randomly keyed, which is shuffle enough for this.
+    let state = RandomState::new();
+    let mut shuffled = candidates.to_vec();
+    shuffled.sort_by_cached_key(|(path, _)| state.hash_one(path));
+    shuffled.truncate(n);
+    shuffled
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn post(path: &str, published: &str, tags: &[&str]) -> Suggestion {
+        let meta = ArticleMeta {
+            published: Some(NaiveDate::parse_from_str(published, "%Y-%m-%d").unwrap()),
+            tags: tags.iter().map(ToString::to_string).collect(),
+            ..Default::default()
+        };
+        (Path::new(path).into(), Arc::new(meta))
+    }
+
+    fn paths(suggestions: &[Suggestion]) -> Vec<&str> {
+        suggestions
+            .iter()
+            .map(|(path, _)| path.to_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn the_next_post_in_the_same_directory_is_suggested_first() {
+        let (rel, meta) = post("blog/b.md", "2024-02-01", &["rust"]);
+        let candidates = vec![
+            post("blog/a.md", "2024-01-01", &["rust"]),
+            post("blog/d.md", "2024-03-01", &[]),
+            // Published the same day, but after it by path.
+            post("blog/c.md", "2024-02-01", &[]),
+            post("notes/e.md", "2024-02-02", &["rust"]),
+        ];
+        let chronological = ReadNextPolicy {
+            by: vec![ReadNextBy::Chronological],
+            limit: 3,
+        };
+        assert_eq!(
+            paths(&pick(&rel, &meta, candidates.clone(), &chronological)),
+            ["blog/c.md"]
+        );
+
+        // Related articles fill the rest, without repeating the chronological pick.
+        let both = ReadNextPolicy::default();
+        assert_eq!(
+            paths(&pick(&rel, &meta, candidates.clone(), &both)),
+            ["blog/c.md", "notes/e.md", "blog/a.md"]
+        );
+
+        // The newest post in its directory has nothing after it.
+        let (rel, meta) = post("blog/d.md", "2024-03-01", &[]);
+        assert!(pick(&rel, &meta, candidates, &chronological).is_empty());
+    }
+}
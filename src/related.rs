@@ -0,0 +1,145 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, LazyLock},
+};
+
+use dashmap::DashMap;
+use serde::Deserialize;
+
+/// How a `related` block picks articles.
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RelatedBy {
+    /// Articles sharing the most tags.
+    #[default]
+    Tags,
+    /// Articles with the most similar text.
+    Content,
+}
+
+/// The contents of a `related` code block.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Related {
+    #[serde(default)]
+    pub by: RelatedBy,
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
+fn default_limit() -> usize {
+    5
+}
+
+/// Term frequencies of every article rendered so far, keyed by source path.
+///
+/// Document frequencies are worked out at query time, so re-indexing one article is cheap.
+static TERMS: LazyLock<DashMap<Arc<Path>, HashMap<String, f32>>> = LazyLock::new(DashMap::new);
+
+/// Words too short to say much about an article are skipped.
+const MIN_TERM_LEN: usize = 3;
+
+/// Add or replace an article's text in the index.
+pub fn index(path: &Arc<Path>, text: &str) {
+    let mut counts: HashMap<String, f32> = HashMap::new();
+    let words = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() >= MIN_TERM_LEN)
+        .map(str::to_lowercase);
+    let mut total = 0.0;
+    for word in words {
+        *counts.entry(word).or_default() += 1.0;
+        total += 1.0;
+    }
+    counts.values_mut().for_each(|count| *count /= total);
+    TERMS.insert(path.clone(), counts);
+}
+
+/// The `n` indexed articles most similar to `path` by TF-IDF cosine similarity, best first.
+pub fn related_by_content(path: &Path, n: usize) -> Vec<(Arc<Path>, f32)> {
+    let Some(target) = TERMS.get(path).map(|t| t.clone()) else {
+        return vec![];
+    };
+    let docs = TERMS.len() as f32;
+    let mut doc_freq: HashMap<&str, f32> = HashMap::new();
+    let snapshot: Vec<_> = TERMS
+        .iter()
+        .map(|kv| (kv.key().clone(), kv.value().clone()))
+        .collect();
+    for (_, terms) in &snapshot {
+        for term in terms.keys() {
+            *doc_freq.entry(term).or_default() += 1.0;
+        }
+    }
+    let idf = |term: &str| ((1.0 + docs) / (1.0 + doc_freq.get(term).unwrap_or(&0.0))).ln() + 1.0;
+    let weigh = |terms: &HashMap<String, f32>| -> HashMap<String, f32> {
+        terms
+            .iter()
+            .map(|(term, tf)| (term.clone(), tf * idf(term)))
+            .collect()
+    };
+    let norm = |v: &HashMap<String, f32>| v.values().map(|w| w * w).sum::<f32>().sqrt();
+
+    let target = weigh(&target);
+    let target_norm = norm(&target);
+    if target_norm == 0.0 {
+        return vec![];
+    }
+    let mut scores: Vec<_> = snapshot
+        .iter()
+        .filter(|(other, _)| &**other != path)
+        .filter_map(|(other, terms)| {
+            let weights = weigh(terms);
+            let other_norm = norm(&weights);
+            if other_norm == 0.0 {
+                return None;
+            }
+            let dot: f32 = target
+                .iter()
+                .filter_map(|(term, w)| Some(w * weights.get(term)?))
+                .sum();
+            Some((other.clone(), dot / (target_norm * other_norm)))
+        })
+        .filter(|(_, score)| *score > 0.0)
+        .collect();
+    scores.sort_by(|(_, l), (_, r)| r.total_cmp(l));
+    scores.truncate(n);
+    scores
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn articles_sharing_vocabulary_are_related() {
+        let paths: Vec<Arc<Path>> = ["sourdough", "baguette", "gearbox"]
+            .into_iter()
+            .map(|name| Path::new("related-test").join(name).into())
+            .collect();
+        let [sourdough, baguette, gearbox] = &paths[..] else {
+            unreachable!()
+        };
+        index(
+            sourdough,
+            "Feed the starter flour and water, then knead the dough and let the starter rise.",
+        );
+        index(
+            baguette,
+            "Knead the dough with flour and water; a lively starter makes the dough rise.",
+        );
+        index(
+            gearbox,
+            "Swap the clutch plate, then bleed the hydraulic line before refitting the gearbox.",
+        );
+
+        let related = related_by_content(sourdough, 10);
+        assert_eq!(related.first().map(|(path, _)| path), Some(baguette));
+        let score = |path: &Arc<Path>| related.iter().find(|(p, _)| p == path).map(|(_, s)| *s);
+        assert!(score(gearbox).unwrap_or(0.0) < score(baguette).unwrap());
+
+        for path in &paths {
+            TERMS.remove(path);
+        }
+    }
+}
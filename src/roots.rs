@@ -0,0 +1,180 @@
+//! Content roots: the directories articles are read from, each served under its own prefix.
+
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::config::CONFIG;
+
+/// A directory of articles, served under a prefix of the site.
+///
+/// Roots share templates, assets and search, but searches and feeds can be scoped to one by
+/// using its prefix as their path, like `/feed/notes`. Their directories shouldn't overlap.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ContentRoot {
+    /// What templates call the root, as `root`.
+    pub name: String,
+    /// Where its articles are on disk.
+    pub dir: PathBuf,
+    /// The site-relative path its articles are served under, like `notes`, or nothing for the
+    /// top of the site.
+    #[serde(default)]
+    pub prefix: PathBuf,
+}
+
+/// The default single root, serving `articles/` at the top of the site.
+pub fn default_roots() -> Vec<ContentRoot> {
+    vec![ContentRoot {
+        name: "articles".to_string(),
+        dir: PathBuf::from("articles"),
+        prefix: PathBuf::new(),
+    }]
+}
+
+/// The root a site-relative path falls under, and the path within it.
+///
+/// The root with the longest matching prefix wins, so a `notes` root takes precedence over a
+/// `notes` directory in the root at the top of the site.
+pub fn for_rel(rel: &Path) -> Option<(&'static ContentRoot, &Path)> {
+    let rel = rel.strip_prefix("/").unwrap_or(rel);
+    CONFIG
+        .roots
+        .iter()
+        .filter_map(|root| Some((root, rel.strip_prefix(&root.prefix).ok()?)))
+        .max_by_key(|(root, _)| root.prefix.components().count())
+}
+
+/// The root a file on disk is in, and its path within it.
+pub fn for_file(path: &Path) -> Option<(&'static ContentRoot, &Path)> {
+    let path = path.strip_prefix(".").unwrap_or(path);
+    CONFIG
+        .roots
+        .iter()
+        .filter_map(|root| {
+            let dir = root.dir.strip_prefix(".").unwrap_or(&root.dir);
+            Some((root, path.strip_prefix(dir).ok()?))
+        })
+        .max_by_key(|(root, _)| root.dir.components().count())
+}
+
+/// The site-relative path of a file in a root, keeping its extension, like `notes/post.md`.
+///
+/// Paths that aren't in any root are assumed to be site-relative already.
+pub fn site_rel(path: &Path) -> PathBuf {
+    match for_file(path) {
+        Some((root, rest)) => root.prefix.join(rest),
+        None => path.strip_prefix(".").unwrap_or(path).to_path_buf(),
+    }
+}
+
+/// Where the file at a site-relative path, like `notes/post.md`, is on disk.
+///
+/// Paths under no root are looked for in the first.
+pub fn disk_path(rel: &Path) -> PathBuf {
+    match for_rel(rel) {
+        Some((root, rest)) => root.dir.join(rest),
+        None => CONFIG.roots[0].dir.join(rel),
+    }
+}
+
+/// Check the configured roots make sense, panicking otherwise.
+pub fn check() {
+    assert!(
+        !CONFIG.roots.is_empty(),
+        "At least one content root is needed"
+    );
+    for root in &CONFIG.roots {
+        assert!(
+            root.dir.is_dir(),
+            "The {:?} content root's directory {:?} doesn't exist",
+            root.name,
+            root.dir
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::Status;
+
+    use super::*;
+    use crate::{
+        article::{self, Search},
+        testing::{client, Fixture},
+    };
+
+    #[test]
+    fn paths_map_to_the_root_with_the_longest_prefix() {
+        let notes = &CONFIG.roots[1];
+        let (root, rest) = for_rel(Path::new("notes/post")).unwrap();
+        assert_eq!((root.name.as_str(), rest), ("notes", Path::new("post")));
+        let (root, rest) = for_rel(Path::new("notebook/post")).unwrap();
+        assert_eq!(
+            (root.name.as_str(), rest),
+            ("articles", Path::new("notebook/post"))
+        );
+        assert_eq!(disk_path(Path::new("notes/a.md")), notes.dir.join("a.md"));
+        assert_eq!(site_rel(&notes.dir.join("a.md")), Path::new("notes/a.md"));
+        assert_eq!(
+            site_rel(&CONFIG.roots[0].dir.join("a.md")),
+            Path::new("a.md")
+        );
+    }
+
+    #[rocket::async_test]
+    async fn each_root_serves_and_searches_its_own_articles() {
+        let main = Fixture::new("two-roots");
+        let notes = Fixture::in_root(1, "two-roots");
+        main.article(
+            "post",
+            "---\ntitle: In articles\nstatus: published\n---\n\nFrom the main root.\n",
+        )
+        .await;
+        notes
+            .article(
+                "post",
+                "---\ntitle: In notes\nstatus: published\n---\n\nFrom the notes root.\n",
+            )
+            .await;
+        assert_eq!(notes.rel("post"), "notes/fixture-two-roots/post");
+
+        let client = client().await;
+        for (fixture, text) in [
+            (&main, "From the main root."),
+            (&notes, "From the notes root."),
+        ] {
+            let response = client
+                .get(format!("/{}", fixture.rel("post")))
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::Ok);
+            assert!(response.into_string().await.unwrap().contains(text));
+        }
+
+        let titles = |search_path: String| async move {
+            article::search_all(&Search {
+                search_path: search_path.into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(path, meta)| (path, meta.title.clone()))
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            titles(notes.rel("")).await,
+            [(
+                Path::new("notes/fixture-two-roots/post.md").into(),
+                "In notes".to_string()
+            )]
+        );
+        assert_eq!(
+            titles(main.rel("")).await,
+            [(
+                Path::new("fixture-two-roots/post.md").into(),
+                "In articles".to_string()
+            )]
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use std::sync::LazyLock;
+
+use ammonia::{Builder, Url, UrlRelative};
+
+/// Strips scripts, event handlers and other active content from HTML, keeping the markup pandoc,
+/// syntect and the wolog's own filters produce.
+static SANITIZER: LazyLock<Builder<'static>> = LazyLock::new(|| {
+    let mut builder = Builder::default();
+    builder
+        .add_tags([
+            "button",
+            "details",
+            "div",
+            "figcaption",
+            "figure",
+            "math",
+            "mi",
+            "mn",
+            "mo",
+            "mrow",
+            "msub",
+            "msup",
+            "msubsup",
+            "munder",
+            "mover",
+            "munderover",
+            "mfrac",
+            "msqrt",
+            "mroot",
+            "mtable",
+            "mtr",
+            "mtd",
+            "mspace",
+            "mstyle",
+            "mtext",
+            "semantics",
+            "annotation",
+            "picture",
+            "section",
+            "source",
+            "span",
+            "summary",
+        ])
+        .add_generic_attributes(["class", "id", "style", "data-lang", "role", "aria-hidden"])
+        .add_tag_attributes("img", ["loading", "width", "height", "srcset", "sizes"])
+        .add_tag_attributes("source", ["srcset", "sizes", "type", "media"])
+        .add_tag_attributes("button", ["type"])
+        .add_tag_attributes("a", ["target"])
+        .add_tag_attributes("details", ["open"])
+        .add_tag_attributes("math", ["display"])
+        .add_tag_attributes("mo", ["stretchy", "fence", "form"])
+        .add_tag_attributes("annotation", ["encoding"]);
+    builder
+});
+
+/// Sanitize untrusted HTML.
+pub fn sanitize(html: &str) -> String {
+    SANITIZER.clean(html).to_string()
+}
+
+/// Sanitize the content of a page mentioning an article, which only keeps inline formatting,
+/// paragraphs and links. Relative links are resolved against `base`, the page's URL, and links
+/// that aren't to web pages are dropped.
+pub fn mention_content(html: &str, base: &str) -> String {
+    let mut builder = Builder::empty();
+    builder
+        .add_tags([
+            "a",
+            "abbr",
+            "b",
+            "blockquote",
+            "br",
+            "cite",
+            "code",
+            "del",
+            "em",
+            "i",
+            "ins",
+            "mark",
+            "p",
+            "q",
+            "s",
+            "small",
+            "strong",
+            "sub",
+            "sup",
+            "u",
+        ])
+        .url_schemes(["http", "https"].into())
+        .link_rel(Some("nofollow noopener noreferrer"));
+    match Url::parse(base) {
+        Ok(base) => builder.url_relative(UrlRelative::RewriteWithBase(base)),
+        Err(_) => builder.url_relative(UrlRelative::Deny),
+    };
+    builder.clean(html).to_string()
+}
+
+/// `url` if it's an `http` or `https` URL, which are safe to link to and load.
+pub fn web_url(url: String) -> Option<String> {
+    (url.starts_with("https://") || url.starts_with("http://")).then_some(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scripts_are_stripped_and_markup_kept() {
+        let html = sanitize(
+            r#"<p onclick="steal()">Hi <script>steal()</script><a href="javascript:steal()">there</a></p>"#,
+        );
+        assert!(!html.contains("script"), "{html}");
+        assert!(!html.contains("onclick"), "{html}");
+        assert!(!html.contains("javascript:"), "{html}");
+        assert!(html.starts_with("<p>Hi "), "{html}");
+
+        let legitimate = r#"<div class="code-block" data-lang="rust"><details open=""><summary>More</summary><img src="/a.png" loading="lazy" width="10" height="5"></details></div>"#;
+        assert_eq!(sanitize(legitimate), legitimate);
+    }
+}
@@ -0,0 +1,97 @@
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::Header,
+    Request, Response,
+};
+
+use crate::config::CONFIG;
+
+/// Adds security headers to responses that don't already have them.
+///
+/// This replaces Rocket's `Shield`, so the policies can be configured. Routes can set their own
+/// `Content-Security-Policy`, as articles that override it do.
+pub struct SecurityHeaders;
+
+/// The `Content-Security-Policy` header for `policy`, or the site's default.
+pub fn csp(policy: Option<&str>) -> Header<'static> {
+    Header::new(
+        "Content-Security-Policy",
+        policy
+            .unwrap_or(&CONFIG.content_security_policy)
+            .to_string(),
+    )
+}
+
+#[rocket::async_trait]
+impl Fairing for SecurityHeaders {
+    fn info(&self) -> Info {
+        Info {
+            name: "Security headers",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, _: &'r Request<'_>, response: &mut Response<'r>) {
+        let headers = [
+            csp(None),
+            Header::new("X-Content-Type-Options", "nosniff"),
+            Header::new("X-Frame-Options", "SAMEORIGIN"),
+            Header::new("Referrer-Policy", CONFIG.referrer_policy.clone()),
+            Header::new("Permissions-Policy", CONFIG.permissions_policy.clone()),
+        ];
+        for header in headers {
+            if !header.value().is_empty() && !response.headers().contains(header.name().as_str()) {
+                response.set_header(header);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{client, Fixture};
+
+    #[rocket::async_test]
+    async fn pages_get_security_headers_and_their_own_csp() {
+        let fixture = Fixture::new("security");
+        fixture
+            .article(
+                "plain",
+                "---\ntitle: Plain\nstatus: published\n---\n\nHi.\n",
+            )
+            .await;
+        fixture
+            .article(
+                "embeds",
+                "---\ntitle: Embeds\nstatus: published\ncsp: \"default-src 'self'; frame-src https://video.example\"\n---\n\nHi.\n",
+            )
+            .await;
+        let client = client().await;
+
+        let response = client
+            .get(format!("/{}", fixture.rel("plain")))
+            .dispatch()
+            .await;
+        let headers = response.headers();
+        let expected = [
+            ("Content-Security-Policy", &*CONFIG.content_security_policy),
+            ("X-Content-Type-Options", "nosniff"),
+            ("X-Frame-Options", "SAMEORIGIN"),
+            ("Referrer-Policy", &*CONFIG.referrer_policy),
+            ("Permissions-Policy", &*CONFIG.permissions_policy),
+        ];
+        for (name, value) in expected {
+            assert_eq!(headers.get(name).collect::<Vec<_>>(), [value], "{name}");
+        }
+
+        let response = client
+            .get(format!("/{}", fixture.rel("embeds")))
+            .dispatch()
+            .await;
+        assert_eq!(
+            response.headers().get_one("Content-Security-Policy"),
+            Some("default-src 'self'; frame-src https://video.example")
+        );
+    }
+}
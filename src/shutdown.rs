@@ -0,0 +1,80 @@
+use std::{future::Future, sync::LazyLock, time::Duration};
+
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    tokio, Orbit, Rocket,
+};
+use tokio_util::task::TaskTracker;
+
+use crate::config::CONFIG;
+
+/// Background work that should finish before the server exits, like processing webmentions.
+static TASKS: LazyLock<TaskTracker> = LazyLock::new(TaskTracker::new);
+
+/// Spawn a task that shutdown waits for.
+pub fn spawn(task: impl Future<Output = ()> + Send + 'static) {
+    TASKS.spawn(task);
+}
+
+/// Waits for background tasks on shutdown, up to the configured timeout, so in-flight
+/// webmentions aren't lost when the server restarts.
+pub struct FlushTasks;
+
+#[rocket::async_trait]
+impl Fairing for FlushTasks {
+    fn info(&self) -> Info {
+        Info {
+            name: "Flush background tasks",
+            kind: Kind::Shutdown,
+        }
+    }
+
+    async fn on_shutdown(&self, _: &Rocket<Orbit>) {
+        TASKS.close();
+        if TASKS.is_empty() {
+            return;
+        }
+        println!("Waiting for {} background tasks", TASKS.len());
+        let timeout = Duration::from_secs(CONFIG.shutdown_timeout);
+        if tokio::time::timeout(timeout, TASKS.wait()).await.is_err() {
+            eprintln!(
+                "Gave up on {} background tasks after {timeout:?}",
+                TASKS.len()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db,
+        testing::{client, serve_html, Fixture},
+        url,
+    };
+
+    #[rocket::async_test]
+    async fn mentions_spawned_before_shutdown_are_saved() {
+        let fixture = Fixture::new("shutdown");
+        let target = fixture.rel("post");
+        let source = serve_html(format!(
+            r#"<p>I liked <a href="{}">this</a>.</p>"#,
+            url::absolute_url(&target)
+        ))
+        .await;
+        let client = client().await;
+        spawn({
+            let (source, target) = (source.clone(), target.clone());
+            async move {
+                // Still running when shutdown starts.
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                db::received_webmention(source, target).await;
+            }
+        });
+        client.terminate().await;
+
+        let mentions = db::mentions_of(&[&target], None, 0).await;
+        assert!(mentions.iter().any(|m| m.source == source), "{mentions:?}");
+    }
+}
@@ -0,0 +1,149 @@
+//! Cutting text down to a summary, the same way everywhere one is shown.
+
+use serde::Deserialize;
+
+/// How long summaries may be, and where they're cut.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SummaryPolicy {
+    /// The most words a summary has, not counting the ellipsis.
+    pub max_words: Option<usize>,
+    /// The most characters a summary has, counting the ellipsis.
+    pub max_chars: Option<usize>,
+    /// Where a summary that's too long is cut.
+    pub boundary: Boundary,
+    /// What's added to a summary that was cut short.
+    pub ellipsis: String,
+}
+
+impl Default for SummaryPolicy {
+    fn default() -> Self {
+        Self {
+            max_words: Some(60),
+            max_chars: None,
+            boundary: Boundary::Word,
+            ellipsis: "…".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Boundary {
+    /// After the last whole word that fits.
+    Word,
+    /// After the last whole sentence that fits, or the last word if not even one does.
+    Sentence,
+}
+
+/// Shorten `text` to fit `policy`, collapsing its whitespace.
+pub fn summarize(text: &str, policy: &SummaryPolicy) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut end = text.len();
+    if let Some(max_words) = policy.max_words {
+        if let Some((space, _)) = text.match_indices(' ').nth(max_words.wrapping_sub(1)) {
+            end = end.min(space);
+        } else if max_words == 0 {
+            end = 0;
+        }
+    }
+    if let Some(max_chars) = policy.max_chars {
+        if text.chars().count() > max_chars {
+            let room = max_chars.saturating_sub(policy.ellipsis.chars().count());
+            let cap = text.char_indices().nth(room).map_or(text.len(), |(i, _)| i);
+            end = end.min(cap);
+        }
+    }
+    if end >= text.len() {
+        return text;
+    }
+
+    let cut = &text[..end];
+    let sentence = match policy.boundary {
+        Boundary::Sentence => cut
+            .char_indices()
+            .rev()
+            .find(|&(i, c)| {
+                matches!(c, '.' | '!' | '?') && cut[i + c.len_utf8()..].starts_with(' ')
+            })
+            .map(|(i, c)| &cut[..i + c.len_utf8()]),
+        Boundary::Word => None,
+    };
+    // Whole sentences keep their punctuation, so the ellipsis stands apart from it.
+    let separator = if sentence.is_some() { " " } else { "" };
+    let cut = match sentence {
+        Some(sentence) => sentence,
+        // A word cut in half isn't a word; a single word too long to fit is cut anyway.
+        None if !text[end..].starts_with(' ') => cut.rfind(' ').map_or(cut, |space| &cut[..space]),
+        None => cut,
+    };
+    let cut = cut.trim_end_matches([' ', ',', ';', ':', '-', '–', '—']);
+    format!("{cut}{separator}{}", policy.ellipsis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(
+        max_words: Option<usize>,
+        max_chars: Option<usize>,
+        boundary: Boundary,
+    ) -> SummaryPolicy {
+        SummaryPolicy {
+            max_words,
+            max_chars,
+            boundary,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn word_boundaries_keep_whole_words() {
+        let words = policy(Some(3), None, Boundary::Word);
+        assert_eq!(
+            summarize("one two\n three  four five", &words),
+            "one two three…"
+        );
+        assert_eq!(summarize("one two three", &words), "one two three");
+        // A character cap that falls inside a word drops the whole word.
+        let chars = policy(None, Some(12), Boundary::Word);
+        assert_eq!(summarize("alpha beta gamma delta", &chars), "alpha beta…");
+    }
+
+    #[test]
+    fn sentence_boundaries_keep_whole_sentences() {
+        let sentences = policy(Some(4), None, Boundary::Sentence);
+        assert_eq!(
+            summarize("First one. Second sentence here. Third.", &sentences),
+            "First one. …"
+        );
+        // Without a whole sentence that fits, it falls back to words.
+        assert_eq!(
+            summarize("A very long opening sentence without a break.", &sentences),
+            "A very long opening…"
+        );
+    }
+
+    #[test]
+    fn character_caps_are_never_exceeded() {
+        assert_eq!(
+            summarize("abcde", &policy(None, Some(5), Boundary::Word)),
+            "abcde"
+        );
+        assert_eq!(
+            summarize("abcdef", &policy(None, Some(5), Boundary::Word)),
+            "abcd…"
+        );
+        let text = "Short words, then a sentence. And another one follows it here.";
+        for max in 1..=text.len() {
+            for boundary in [Boundary::Word, Boundary::Sentence] {
+                let summary = summarize(text, &policy(None, Some(max), boundary));
+                assert!(
+                    summary.chars().count() <= max,
+                    "{max} {boundary:?}: {summary}"
+                );
+            }
+        }
+    }
+}
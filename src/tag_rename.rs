@@ -0,0 +1,161 @@
+//! Renaming a tag in the front matter of every article that has it.
+
+use std::path::{Path, PathBuf};
+
+use rocket::tokio;
+use serde::Serialize;
+
+use crate::{
+    article::{self, error::ArticleError},
+    config::CONFIG,
+    roots,
+};
+
+/// What renaming a tag changed.
+#[derive(Serialize, Debug, Default)]
+pub struct Report {
+    /// How many files were rewritten.
+    pub changed: usize,
+    /// Their site-relative paths.
+    pub files: Vec<PathBuf>,
+    /// Files that have the tag but couldn't be rewritten, and why.
+    pub failed: Vec<Failure>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Failure {
+    pub file: PathBuf,
+    pub problem: String,
+}
+
+/// Rename the tag `from` to `to` in every markdown file in every content root, drafts included.
+///
+/// Files are changed with [`article::write_source`], so each is replaced in one go and
+/// re-rendered straight away.
+pub async fn rename(from: &str, to: &str) -> Result<Report, ArticleError> {
+    for tag in [from, to] {
+        if tag.trim().is_empty() || tag.trim() != tag {
+            return Err(ArticleError::BadTag(tag.to_string()));
+        }
+    }
+    let mut report = Report::default();
+    if from == to {
+        return Ok(report);
+    }
+    let files: Vec<PathBuf> = CONFIG
+        .roots
+        .iter()
+        .flat_map(|root| walkdir::WalkDir::new(&root.dir).sort_by_file_name())
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .map(walkdir::DirEntry::into_path)
+        .filter(|path| path.extension().is_some_and(|ext| ext == "md"))
+        .collect();
+    for file in files {
+        match rewrite(&file, from, to).await {
+            Ok(false) => {}
+            Ok(true) => {
+                println!("Renamed tag {from:?} to {to:?} in {file:?}");
+                report.changed += 1;
+                report.files.push(roots::site_rel(&file));
+            }
+            Err(e) => {
+                eprintln!("Couldn't rename tag {from:?} in {file:?}: {e}");
+                report.failed.push(Failure {
+                    file: roots::site_rel(&file),
+                    problem: e.to_string(),
+                });
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Rename the tag in one file, returning whether it had it.
+async fn rewrite(file: &Path, from: &str, to: &str) -> Result<bool, ArticleError> {
+    let source = tokio::fs::read_to_string(file).await?;
+    match article::rename_tag(&source, from, to) {
+        Ok(Some(rewritten)) => {
+            article::write_source(&file.into(), &rewritten).await?;
+            Ok(true)
+        }
+        Ok(None) => Ok(false),
+        // Front matter too unusual to edit in place is written out afresh, losing its comments.
+        Err(ArticleError::TagsNotRewritable) => {
+            let (mut meta, body) = article::read_article(file).await?;
+            let mut tags = vec![];
+            for tag in meta.tags {
+                let tag = if tag == from { to.to_string() } else { tag };
+                if !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+            }
+            meta.tags = tags;
+            article::write_article(&file.into(), &meta, &body).await?;
+            Ok(true)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::{Header, Status};
+
+    use crate::testing::{client, Fixture, ADMIN_TOKEN};
+
+    #[rocket::async_test]
+    async fn tags_are_renamed_leaving_the_rest_alone() {
+        let fixture = Fixture::new("tag-rename");
+        let flow = "---\ntitle: Flow # kept\nstatus: published\ntags: [keep, rename-old]\ncustom: value\n---\n\nBody *stays*.\n";
+        let block = "---\ntitle: Block\ntags:\n  - rename-old\n  - keep\nauthors:\n  - Ada\n---\n\nOther body.\n";
+        let toml = "+++\ntitle = \"Toml\"\ntags = [\"rename-old\"]\n+++\n\nToml body.\n";
+        let untouched = "---\ntitle: Untouched\ntags: [keep]\n---\n\nNothing.\n";
+        for (name, source) in [
+            ("flow", flow),
+            ("block", block),
+            ("toml", toml),
+            ("untouched", untouched),
+        ] {
+            fixture.write(name, source);
+        }
+
+        let client = client().await;
+        let response = client
+            .post("/admin/tags/rename?from=rename-old&to=rename-new")
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {ADMIN_TOKEN}"),
+            ))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let report: serde_json::Value = response.into_json().await.unwrap();
+        let mut files: Vec<_> = report["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|f| f.as_str())
+            .filter(|f| f.starts_with(&fixture.rel("")))
+            .collect();
+        files.sort();
+        let expected: Vec<_> = ["block.md", "flow.md", "toml.md"]
+            .map(|name| fixture.rel(name))
+            .to_vec();
+        assert_eq!(files, expected);
+
+        let read =
+            |name: &str| std::fs::read_to_string(fixture.dir().join(format!("{name}.md"))).unwrap();
+        assert_eq!(read("flow"), flow.replace("rename-old", "rename-new"));
+        // Block lists are written back on one line, with everything around them kept.
+        assert_eq!(
+            read("block"),
+            block.replace(
+                "tags:\n  - rename-old\n  - keep\n",
+                "tags: [rename-new, keep]\n"
+            )
+        );
+        assert_eq!(read("toml"), toml.replace("rename-old", "rename-new"));
+        assert_eq!(read("untouched"), untouched);
+    }
+}
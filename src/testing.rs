@@ -0,0 +1,211 @@
+//! Fixtures for tests that need articles on disk or the whole site.
+
+use std::{
+    cell::Cell,
+    future::Future,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
+
+use rocket::{
+    local::asynchronous::Client,
+    tokio::{
+        self,
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    },
+};
+
+use crate::{
+    article::{self, Renderer},
+    config::{Config, CONFIG},
+    roots::ContentRoot,
+};
+
+/// The admin token configured for tests.
+pub const ADMIN_TOKEN: &str = "test-admin-token";
+
+/// What tests run with unless they're run with [`run_with`].
+static DEFAULT: LazyLock<Config> = LazyLock::new(|| config(|_| {}));
+
+thread_local! {
+    /// The configuration [`run_with`] injected into the threads of a test's runtime.
+    static INJECTED: Cell<Option<&'static Config>> = const { Cell::new(None) };
+}
+
+/// The configuration [`CONFIG`] derefs to on this thread.
+pub fn config_in_effect() -> &'static Config {
+    INJECTED.get().unwrap_or(&DEFAULT)
+}
+
+/// The configuration tests run with, after `change`: the defaults, but with the native renderer
+/// so they don't need pandoc, [`ADMIN_TOKEN`], and the content [`roots`].
+pub fn config(change: impl FnOnce(&mut Config)) -> Config {
+    let mut config = Config {
+        renderer: Renderer::Native,
+        admin_token: Some(ADMIN_TOKEN.to_string()),
+        roots: roots(),
+        ..Default::default()
+    };
+    change(&mut config);
+    config.finish()
+}
+
+/// Run a test on a runtime of its own, every thread of which sees `config` as [`CONFIG`], for
+/// tests of settings other tests shouldn't run with.
+pub fn run_with<F: Future>(config: Config, test: F) -> F::Output {
+    let config: &'static Config = Box::leak(Box::new(config));
+    INJECTED.set(Some(config));
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .enable_all()
+        .on_thread_start(move || INJECTED.set(Some(config)))
+        .build()
+        .expect("Couldn't start the test's runtime");
+    let output = runtime.block_on(test);
+    runtime.shutdown_timeout(Duration::from_millis(500));
+    INJECTED.set(None);
+    output
+}
+
+/// Whether pandoc can be run here. It isn't installed everywhere tests run, so tests of the pandoc
+/// renderer pass without it.
+pub fn pandoc_installed() -> bool {
+    std::process::Command::new("pandoc")
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+/// The content roots tests run with: `articles`, served at the top of the site, and `notes`,
+/// served under `/notes`. Both are temporary directories of the test process's own, so fixtures
+/// never end up among the site's articles, or in its searches.
+pub fn roots() -> Vec<ContentRoot> {
+    let dir = |name| std::env::temp_dir().join(format!("wolog-test-{name}-{}", std::process::id()));
+    vec![
+        ContentRoot {
+            name: "articles".to_string(),
+            dir: dir("articles"),
+            prefix: PathBuf::new(),
+        },
+        ContentRoot {
+            name: "notes".to_string(),
+            dir: dir("notes"),
+            prefix: PathBuf::from("notes"),
+        },
+    ]
+}
+
+/// A directory of articles in a content root, removed along with everything rendered from it
+/// when it's dropped.
+///
+/// Tests run at once, so each should use a fixture of its own name and only search within it.
+pub struct Fixture {
+    name: String,
+    root: usize,
+}
+
+impl Fixture {
+    /// A fixture in the first content root.
+    pub fn new(name: &str) -> Self {
+        Self::in_root(0, name)
+    }
+
+    /// A fixture in the `root`th content root.
+    pub fn in_root(root: usize, name: &str) -> Self {
+        let fixture = Self {
+            name: format!("fixture-{name}"),
+            root,
+        };
+        let _ = std::fs::remove_dir_all(fixture.dir());
+        std::fs::create_dir_all(fixture.dir()).expect("Couldn't make the fixture directory");
+        fixture
+    }
+
+    /// The fixture's directory on disk.
+    pub fn dir(&self) -> PathBuf {
+        CONFIG.roots[self.root].dir.join(&self.name)
+    }
+
+    /// The site-relative path of `rel` in the fixture, like `fixture-name/post`.
+    pub fn rel(&self, rel: &str) -> String {
+        let dir = CONFIG.roots[self.root].prefix.join(&self.name);
+        let dir = dir.to_string_lossy();
+        if rel.is_empty() {
+            dir.into_owned()
+        } else {
+            format!("{dir}/{rel}")
+        }
+    }
+
+    /// Write the article at `rel` (without its `.md`) and render it, returning its source path.
+    pub async fn article(&self, rel: &str, source: &str) -> Arc<Path> {
+        let path = self.write(rel, source);
+        article::refresh(&path).await;
+        path
+    }
+
+    /// Write the article at `rel` without rendering it.
+    pub fn write(&self, rel: &str, source: &str) -> Arc<Path> {
+        let path: Arc<Path> = self.dir().join(format!("{rel}.md")).into();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).expect("Couldn't make the article's directory");
+        }
+        std::fs::write(&path, source).expect("Couldn't write the article");
+        path
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(self.dir());
+        article::forget(&self.dir());
+    }
+}
+
+/// A client for the whole site, as `main` launches it.
+pub async fn client() -> Client {
+    Client::tracked(crate::rocket())
+        .await
+        .expect("The site should launch")
+}
+
+/// Serve `html` to every request on a local port, as the page a webmention came from, returning
+/// its URL.
+pub async fn serve_html(html: String) -> String {
+    serve("200 OK", html).await
+}
+
+/// Answer every request on a local port with `status`, like `503 Service Unavailable`, and
+/// `html`, returning its URL.
+pub async fn serve(status: &'static str, html: String) -> String {
+    serve_with(status, move || html.clone()).await
+}
+
+/// Answer every request on a local port with `status` and whatever `page` gives at the time, for
+/// pages that change between requests, returning its URL.
+pub async fn serve_with(
+    status: &'static str,
+    page: impl Fn() -> String + Send + Sync + 'static,
+) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("Couldn't bind a local port");
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let html = page();
+            tokio::spawn(async move {
+                let mut request = [0; 4096];
+                let _ = stream.read(&mut request).await;
+                let response = format!(
+                    "HTTP/1.1 {status}\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{html}",
+                    html.len()
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            });
+        }
+    });
+    url
+}
@@ -0,0 +1,109 @@
+//! Dates and times in the site's time zone.
+
+use chrono::{
+    DateTime, FixedOffset, Local, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc,
+};
+
+use chrono_tz::Tz;
+
+use crate::config::CONFIG;
+
+/// The current time in the site's time zone.
+pub fn now() -> DateTime<FixedOffset> {
+    in_zone(Utc::now())
+}
+
+/// A moment in time, in the site's time zone.
+pub fn in_zone(time: impl Into<DateTime<Utc>>) -> DateTime<FixedOffset> {
+    let time = time.into();
+    match CONFIG.timezone {
+        Some(tz) => time.with_timezone(&tz).fixed_offset(),
+        None => time.with_timezone(&Local).fixed_offset(),
+    }
+}
+
+/// When a wall-clock time in the site's time zone happens.
+///
+/// An ambiguous time, repeated when the clocks go back, is the first of its two moments. A time
+/// skipped when the clocks go forward is read as UTC, so it's at least close.
+pub fn local(time: NaiveDateTime) -> DateTime<FixedOffset> {
+    local_in(CONFIG.timezone, time)
+}
+
+/// Like [`local`], in `tz` rather than the configured zone, or the system's if it's `None`.
+fn local_in(tz: Option<Tz>, time: NaiveDateTime) -> DateTime<FixedOffset> {
+    fn earliest<Tz: TimeZone>(result: LocalResult<DateTime<Tz>>) -> Option<DateTime<FixedOffset>> {
+        result.earliest().map(|time| time.fixed_offset())
+    }
+    match tz {
+        Some(tz) => earliest(tz.from_local_datetime(&time)),
+        None => earliest(Local.from_local_datetime(&time)),
+    }
+    .unwrap_or_else(|| time.and_utc().fixed_offset())
+}
+
+/// Midnight at the start of a date in the site's time zone.
+pub fn start_of(date: NaiveDate) -> DateTime<FixedOffset> {
+    local(date.and_time(NaiveTime::MIN))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chrono_tz::{America::New_York, Asia::Tokyo};
+
+    use super::*;
+    use crate::testing::{client, Fixture};
+
+    fn at(date: &str, time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(&format!("{date} {time}"), "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn configured_zones_shift_timestamps() {
+        let midnight = at("2024-05-01", "00:00");
+        let tokyo = local_in(Some(Tokyo), midnight);
+        assert_eq!(tokyo.to_rfc3339(), "2024-05-01T00:00:00+09:00");
+        assert_eq!(
+            tokyo.with_timezone(&Utc),
+            at("2024-04-30", "15:00").and_utc()
+        );
+        assert_eq!(
+            local_in(Some(chrono_tz::UTC), midnight).to_rfc3339(),
+            "2024-05-01T00:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn clock_changes_have_an_answer() {
+        // Repeated when the clocks go back: the first of the two.
+        let repeated = local_in(Some(New_York), at("2024-11-03", "01:30"));
+        assert_eq!(repeated.to_rfc3339(), "2024-11-03T01:30:00-04:00");
+        // Skipped when they go forward: read as UTC.
+        let skipped = local_in(Some(New_York), at("2024-03-10", "02:30"));
+        assert_eq!(skipped.to_rfc3339(), "2024-03-10T02:30:00+00:00");
+    }
+
+    #[rocket::async_test]
+    async fn feed_timestamps_start_the_day_in_the_site_zone() {
+        let fixture = Fixture::new("feed-zone");
+        fixture
+            .article(
+                "post",
+                "---\ntitle: Zoned\nstatus: published\ncreated: 2024-05-01\nupdated: 2024-05-02\n---\n\nHi.\n",
+            )
+            .await;
+        let xml = client()
+            .await
+            .get(format!("/feed/{}", fixture.rel("")))
+            .dispatch()
+            .await
+            .into_string()
+            .await
+            .unwrap();
+        let feed = atom_syndication::Feed::from_str(&xml).unwrap();
+        let updated = start_of(NaiveDate::from_ymd_opt(2024, 5, 2).unwrap());
+        assert_eq!(*feed.entries()[0].updated(), updated);
+    }
+}
@@ -0,0 +1,141 @@
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::{uri::Origin, Method, Status},
+    request::{FromRequest, Outcome},
+    Data, Request,
+};
+use serde::Deserialize;
+
+use crate::config::CONFIG;
+
+/// Whether URLs canonically end in a slash.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TrailingSlash {
+    /// `/foo/` redirects to `/foo`.
+    #[default]
+    Strip,
+    /// `/foo` redirects to `/foo/`, except for paths that look like files.
+    Add,
+    /// Both forms are served as-is.
+    Allow,
+}
+
+/// Where requests needing a redirect are sent, so the router never sees the original path.
+pub const REDIRECT_PATH: &str = "/.trailing-slash";
+
+/// Prefixes served by file servers, which handle directories themselves.
+const EXEMPT: &[&str] = &["/static/", "/assets/"];
+
+/// The canonical form of `path` under `policy`, if it isn't already canonical.
+fn canonical(path: &str, policy: TrailingSlash) -> Option<String> {
+    if path == "/" || EXEMPT.iter().any(|prefix| path.starts_with(prefix)) {
+        return None;
+    }
+    match policy {
+        TrailingSlash::Strip if path.ends_with('/') => {
+            let stripped = path.trim_end_matches('/');
+            Some(if stripped.is_empty() { "/" } else { stripped }.to_string())
+        }
+        TrailingSlash::Add if !path.ends_with('/') => {
+            let last = path.rsplit('/').next().unwrap_or_default();
+            (!last.contains('.')).then(|| format!("{path}/"))
+        }
+        _ => None,
+    }
+}
+
+/// Redirects requests to the canonical form of their path, per [`CONFIG`]'s `trailing_slash`.
+///
+/// Fairings can't answer requests themselves, so a request needing a redirect is rewritten to
+/// [`REDIRECT_PATH`], whose route reads the target back through [`Canonical`].
+pub struct TrailingSlashRedirect;
+
+#[rocket::async_trait]
+impl Fairing for TrailingSlashRedirect {
+    fn info(&self) -> Info {
+        Info {
+            name: "Trailing slash redirects",
+            kind: Kind::Request,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        if !matches!(request.method(), Method::Get | Method::Head) {
+            return;
+        }
+        let Some(path) = canonical(request.uri().path().as_str(), CONFIG.trailing_slash) else {
+            return;
+        };
+        let path = crate::url::site_path(&path);
+        let target = match request.uri().query() {
+            Some(query) => format!("{path}?{query}"),
+            None => path,
+        };
+        request.local_cache(|| Target(Some(target)));
+        request.set_uri(Origin::parse(REDIRECT_PATH).expect("Redirect path is a valid URI"));
+    }
+}
+
+/// The redirect target stashed by [`TrailingSlashRedirect`], if any.
+struct Target(Option<String>);
+
+/// The canonical URL a request was rewritten away from by [`TrailingSlashRedirect`].
+pub struct Canonical(pub String);
+
+#[async_trait]
+impl<'r> FromRequest<'r> for Canonical {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.local_cache(|| Target(None)) {
+            Target(Some(target)) => Outcome::Success(Canonical(target.clone())),
+            Target(None) => Outcome::Forward(Status::NotFound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::Status;
+
+    use super::*;
+    use crate::testing::client;
+
+    #[test]
+    fn each_policy_picks_its_canonical_path() {
+        assert_eq!(
+            canonical("/foo/", TrailingSlash::Strip).as_deref(),
+            Some("/foo")
+        );
+        assert_eq!(canonical("/foo", TrailingSlash::Strip), None);
+        assert_eq!(
+            canonical("/foo", TrailingSlash::Add).as_deref(),
+            Some("/foo/")
+        );
+        assert_eq!(canonical("/feed.xml", TrailingSlash::Add), None);
+        assert_eq!(canonical("/foo/", TrailingSlash::Allow), None);
+        assert_eq!(canonical("/static/dir/", TrailingSlash::Strip), None);
+        for policy in [
+            TrailingSlash::Strip,
+            TrailingSlash::Add,
+            TrailingSlash::Allow,
+        ] {
+            assert_eq!(canonical("/", policy), None);
+        }
+    }
+
+    #[rocket::async_test]
+    async fn slashes_are_stripped_but_not_from_the_root() {
+        let client = client().await;
+        let response = client.get("/foo/?page=2").dispatch().await;
+        assert_eq!(response.status(), Status::MovedPermanently);
+        assert_eq!(
+            response.headers().get_one("Location"),
+            Some(&*crate::url::site_path("/foo?page=2"))
+        );
+
+        let response = client.get("/").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+    }
+}
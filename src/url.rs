@@ -0,0 +1,323 @@
+use std::{path::Path, sync::LazyLock};
+
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use rocket::http::RawStr;
+use serde::Serialize;
+
+use crate::config::CONFIG;
+
+/// A feed, as offered to readers by `<link rel="alternate">` and the feed index.
+#[derive(Serialize, Debug, Clone)]
+pub struct FeedLink {
+    pub title: String,
+    pub href: String,
+}
+
+impl FeedLink {
+    /// The site's primary feed, which is everything on it unless configured otherwise.
+    pub fn main() -> Self {
+        Self {
+            href: primary_feed(),
+            ..Self::new("", &[])
+        }
+    }
+
+    /// The feed of the articles routed into it by name with `feeds`.
+    pub fn named(name: &str) -> Self {
+        Self {
+            title: format!("{}'s blog: {name}", CONFIG.author),
+            href: site_path(&format!(
+                "/feed/named/{}",
+                RawStr::new(name).percent_encode()
+            )),
+        }
+    }
+
+    /// The feed of articles under `path` carrying all of `tags`.
+    pub fn new(path: &str, tags: &[String]) -> Self {
+        let path = encode_path(path);
+        let mut title = CONFIG.author.clone() + "'s blog";
+        if !path.is_empty() {
+            title += &format!(" ({path})");
+        }
+        for tag in tags {
+            title += &format!(" #{tag}");
+        }
+        let query: Vec<_> = tags
+            .iter()
+            .map(|t| format!("tags={}", RawStr::new(t).percent_encode()))
+            .collect();
+        let mut href = if path.is_empty() {
+            site_path("/feed")
+        } else {
+            site_path(&format!("/feed/{path}"))
+        };
+        if !query.is_empty() {
+            href += &format!("?{}", query.join("&"));
+        }
+        Self { title, href }
+    }
+}
+
+/// The root-relative URL of the configured primary feed.
+pub fn primary_feed() -> String {
+    site_path(&format!("/{}", CONFIG.primary_feed.trim_start_matches('/')))
+}
+
+/// Whether the primary feed is the feed of everything at `/feed`, rather than one it redirects to.
+pub fn primary_feed_is_default() -> bool {
+    primary_feed() == site_path("/feed")
+}
+
+/// The site author's profile URL.
+pub fn author_url() -> String {
+    CONFIG
+        .author_url
+        .clone()
+        .unwrap_or_else(|| absolute_url(""))
+}
+
+/// Build the public URL of an article from its path on disk or relative to the site.
+pub fn article_url(path: &Path) -> String {
+    absolute_url(&article_rel(path))
+}
+
+/// Build an absolute URL from a site-relative path.
+pub fn absolute_url(rel: &str) -> String {
+    url_under(&CONFIG.url, base_path(), rel)
+}
+
+/// The URL of the site-relative path `rel` on a site at `site` under the base path `base`.
+fn url_under(site: &str, base: &str, rel: &str) -> String {
+    format!("{}{base}/{}", site.trim_end_matches('/'), encode_path(rel))
+}
+
+/// The configured base path, like `/blog`, or an empty string if the site is at the root.
+pub fn base_path() -> &'static str {
+    static BASE: LazyLock<String> = LazyLock::new(|| normalize_base(&CONFIG.base_path));
+    &BASE
+}
+
+/// Spell a base path with a leading slash and no trailing one, or as nothing for the root.
+fn normalize_base(base: &str) -> String {
+    let base = base.trim_matches('/');
+    if base.is_empty() {
+        String::new()
+    } else {
+        format!("/{base}")
+    }
+}
+
+/// Prefix a root-relative path, like `/search`, with the base path.
+pub fn site_path(path: &str) -> String {
+    format!("{}{path}", base_path())
+}
+
+/// The URL for editing an article's source in its repository, if one is configured.
+pub fn edit_url(path: &Path) -> Option<String> {
+    let base = CONFIG.repo_edit_base.as_ref()?;
+    let path = path.strip_prefix(".").unwrap_or(path);
+    let path = path.strip_prefix("articles").unwrap_or(path);
+    Some(format!(
+        "{}/{}",
+        base.trim_end_matches('/'),
+        encode_path(&path.to_string_lossy())
+    ))
+}
+
+/// Parse an absolute `http` or `https` URL, as webmentions must be.
+pub fn parse_http_url(url: &str) -> Option<reqwest::Url> {
+    reqwest::Url::parse(url)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https") && url.has_host())
+}
+
+/// Whether a link leads off the site: an absolute `http` or `https` URL on another host than the
+/// configured base URL. Relative links and other schemes, like `mailto:`, aren't external.
+pub fn is_external(target: &str) -> bool {
+    let Some(target) = parse_http_url(target) else {
+        return false;
+    };
+    let Some(site) = parse_http_url(&CONFIG.url) else {
+        return true;
+    };
+    target.host_str() != site.host_str()
+        || target.port_or_known_default() != site.port_or_known_default()
+}
+
+/// Strip the configured base URL from an absolute URL, giving the site-relative path.
+pub fn strip_base(url: &str) -> Option<&str> {
+    url.strip_prefix(CONFIG.url.trim_end_matches('/'))
+        .and_then(|rest| rest.strip_prefix(base_path()))
+        .filter(|rest| rest.is_empty() || rest.starts_with('/'))
+        .map(|rest| rest.trim_start_matches('/'))
+}
+
+/// The site-relative path of an article, under its content root's prefix and without the `.md`
+/// extension.
+pub fn article_rel(path: &Path) -> String {
+    let path = crate::roots::site_rel(path);
+    let path = if path.extension().is_some_and(|e| e == "md") {
+        path.with_extension("")
+    } else {
+        path
+    };
+    path.to_string_lossy().to_string()
+}
+
+/// The site-relative URL path of an article given a slug, which replaces its file name.
+pub fn slug_path(path: &Path, slug: &str) -> String {
+    format!("/{}", encode_path(&slug_rel(path, slug)))
+}
+
+/// The raw site-relative path of an article given a slug, as [`article_rel`] is without one.
+pub fn slug_rel(path: &Path, slug: &str) -> String {
+    let rel = article_rel(path);
+    let dir = Path::new(&rel).parent().unwrap_or(Path::new(""));
+    dir.join(slug).to_string_lossy().to_string()
+}
+
+/// What's percent-encoded in a path segment: anything that would end it or the path, or that
+/// isn't safe to leave bare in a URL. Non-ASCII characters are always encoded.
+const SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'[')
+    .add(b'\\')
+    .add(b']')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+/// Percent-encode a raw site-relative path, like an article's path on disk, into its canonical
+/// form in URLs.
+///
+/// The path is taken as it is, so a `%` in it is encoded too. Paths taken from URLs should go
+/// through [`decode_path`] first.
+pub fn encode_path(rel: &str) -> String {
+    rel.split('/')
+        .filter(|s| !s.is_empty())
+        .map(|segment| utf8_percent_encode(segment, SEGMENT).to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Decode the path of a URL, like one [`strip_base`] returns, back into a raw site-relative path.
+pub fn decode_path(path: &str) -> String {
+    percent_decode_str(path).decode_utf8_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::{roots, testing};
+
+    /// Run `test` with the site's own `articles` root, as paths in it are spelled on disk.
+    fn with_site_roots(test: impl std::future::Future<Output = ()>) {
+        let config = testing::config(|c| c.roots = roots::default_roots());
+        testing::run_with(config, test);
+    }
+
+    #[test]
+    fn article_paths_lose_their_root_and_extension() {
+        with_site_roots(async {
+            assert_eq!(article_rel(Path::new("articles/post.md")), "post");
+            assert_eq!(article_rel(Path::new("./articles/post.md")), "post");
+            assert_eq!(
+                article_rel(Path::new("articles/notes/2024/post.md")),
+                "notes/2024/post"
+            );
+        });
+    }
+
+    #[test]
+    fn nested_articles_keep_their_directories() {
+        with_site_roots(async {
+            assert_eq!(
+                article_url(Path::new("articles/notes/2024/post.md")),
+                "https://wolo.dev/notes/2024/post"
+            );
+            assert_eq!(
+                absolute_url("/notes//2024/post/"),
+                "https://wolo.dev/notes/2024/post"
+            );
+        });
+    }
+
+    #[test]
+    fn spaces_are_encoded() {
+        with_site_roots(async {
+            assert_eq!(
+                article_url(Path::new("articles/my notes/first post.md")),
+                "https://wolo.dev/my%20notes/first%20post"
+            );
+            assert_eq!(
+                strip_base("https://wolo.dev/my%20notes/first%20post"),
+                Some("my%20notes/first%20post")
+            );
+        });
+    }
+
+    #[test]
+    fn reserved_and_non_ascii_characters_are_encoded() {
+        assert_eq!(encode_path("100% done?"), "100%25%20done%3F");
+        assert_eq!(encode_path("c#/notes"), "c%23/notes");
+        assert_eq!(encode_path("café"), "caf%C3%A9");
+    }
+
+    #[test]
+    fn mention_targets_match_article_paths() {
+        // A mention's target is stored as it's found at the end of its URL, and looked up by
+        // the article's path, which have to agree.
+        for path in ["articles/my notes/first post.md", "articles/100% done?.md"] {
+            let path = Path::new(path);
+            let url = article_url(path);
+            let target = strip_base(&url).unwrap();
+            assert_eq!(decode_path(target), article_rel(path));
+        }
+    }
+
+    #[test]
+    fn decoding_undoes_encoding() {
+        for path in ["my notes/first post", "100% done?", "café", "c#/notes"] {
+            assert_eq!(decode_path(&encode_path(path)), path, "{path:?}");
+        }
+    }
+
+    #[test]
+    fn raw_paths_are_not_decoded() {
+        assert_eq!(encode_path("%41 and %20"), "%2541%20and%20%2520");
+    }
+
+    #[test]
+    fn only_urls_on_the_site_are_stripped() {
+        assert_eq!(strip_base("https://wolo.dev"), Some(""));
+        assert_eq!(strip_base("https://wolo.dev.example.com/post"), None);
+        assert_eq!(strip_base("https://example.com/post"), None);
+    }
+
+    #[test]
+    fn urls_include_the_base_path() {
+        for (configured, base) in [("", ""), ("/", ""), ("blog", "/blog"), ("/blog/", "/blog")] {
+            assert_eq!(normalize_base(configured), base, "{configured:?}");
+        }
+        assert_eq!(
+            url_under("https://example.com/", "/blog", "notes/a post"),
+            "https://example.com/blog/notes/a%20post"
+        );
+        assert_eq!(
+            url_under("https://example.com", "", ""),
+            "https://example.com/"
+        );
+    }
+}
@@ -0,0 +1,10 @@
+// Copies a code block's contents when its copy button is pressed.
+document.addEventListener("click", async (event) => {
+    const button = event.target.closest(".copy-code");
+    if (!button) return;
+    const code = button.closest(".code-block").querySelector("code").cloneNode(true);
+    code.querySelectorAll(".line-number").forEach((number) => number.remove());
+    await navigator.clipboard.writeText(code.textContent);
+    button.textContent = "Copied";
+    setTimeout(() => (button.textContent = "Copy"), 2000);
+});